@@ -9,9 +9,9 @@ use std::path::{ Path, PathBuf };
 use anyhow::Result;
 
 
-/// Supported audio extensions for highlighting.
+/// Supported audio extensions (and cue sheets) for highlighting.
 const AUDIO_EXTENSIONS: &[&str] = &[
-    "mp3", "flac", "ogg", "wav", "m4a", "aac", "opus", "wma", "aiff", "alac",
+    "mp3", "flac", "ogg", "wav", "m4a", "aac", "opus", "wma", "aiff", "alac", "cue",
 ];
 
 
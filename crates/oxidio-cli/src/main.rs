@@ -8,8 +8,9 @@ mod media_controls;
 mod settings;
 mod view;
 
+use std::collections::HashMap;
 use std::io;
-use std::path::PathBuf;
+use std::path::{ Path, PathBuf };
 use std::sync::mpsc;
 use std::time::Duration;
 
@@ -35,13 +36,19 @@ use media_controls::{ create_media_controls_channel, MediaControlCommand, MediaC
 use view::{ ViewMode, VisualizerStyle };
 
 use oxidio_core::{
-    command::{ self, RepeatModeArg },
+    command::{ self, BookmarkArg, ChapterArg, EqArg, LoopArg, RepeatModeArg },
+    cue::{ self, CueSheet },
+    equalizer::MAX_GAIN_DB,
     library::LibraryScanner,
-    player::PlaybackState,
-    Command, Player, RepeatMode,
+    player::{ PlaybackState, PlayerEvent },
+    AudioStream, Bookmark, Chapter, Command, EqPreset, OutputDeviceInfo, Player, RepeatMode, Spectrum, MAX_VOLUME_DB, MIN_VOLUME_DB,
 };
 
 
+/// Volume change per press of the volume keys.
+const VOLUME_STEP_DB: f32 = 2.0;
+
+
 /// Converts a file path to a file:// URL for SMTC album art.
 #[cfg( target_os = "windows" )]
 fn path_to_file_url( path: &std::path::Path ) -> Option<String> {
@@ -81,12 +88,19 @@ struct App {
     // Visualizer style
     visualizer_style: VisualizerStyle,
 
-    // Volume (0.0 to 1.0)
-    volume: f32,
+    // Volume in dB, kept while muted so unmuting restores it
+    volume_db: f32,
+    muted: bool,
+
+    // A-B loop start marked with /loop a, for the track it was marked in
+    loop_start: Option<( PathBuf, Duration )>,
 
     // Flag to scroll to playing track without changing selection
     scroll_to_playing: bool,
 
+    // Playlist labels of cue sheet tracks, filled in as they're first shown
+    cue_labels: HashMap<PathBuf, String>,
+
     // Mouse click tracking for double-click detection
     last_click_time: Option<std::time::Instant>,
     last_click_row: Option<u16>,
@@ -97,6 +111,10 @@ struct App {
     // Help view scroll offset
     help_scroll: u16,
 
+    // All tags pane of the track info view, and its scroll offset
+    show_all_tags: bool,
+    tags_scroll: u16,
+
     // Status message (shown in status bar)
     status_message: Option<String>,
     status_clear_at: Option<std::time::Instant>,
@@ -116,6 +134,19 @@ struct App {
     // Settings
     settings: settings::Settings,
     settings_selected: usize,
+    /// Output devices listed by the device picker, while it's open
+    device_list: Option<Vec<OutputDeviceInfo>>,
+    device_selected: usize,
+
+    // Equalizer
+    eq_selected: usize,
+    /// Equalizer changes not yet saved, written once the EQ view is left
+    eq_unsaved: bool,
+    /// User-defined presets, in addition to the built-in ones
+    eq_presets: Vec<EqPreset>,
+
+    // Bookmarks
+    bookmarks_selected: usize,
 }
 
 
@@ -139,7 +170,8 @@ impl App {
         };
 
         let mut initial_track_index: Option<usize> = None;
-        let mut initial_volume: f32 = 1.0;
+        let mut initial_volume_db: f32 = 0.0;
+        let mut initial_speed: f32 = 1.0;
 
         // Add any files passed on command line to playlist
         if !args.files.is_empty() {
@@ -153,7 +185,7 @@ impl App {
                         playlist.add_many( tracks.into_iter().map( |t| t.path ) );
                     }
                 } else {
-                    playlist.add( file.clone() );
+                    playlist.add_many( cue::expand( file ) );
                 }
             }
         } else {
@@ -168,14 +200,16 @@ impl App {
                         playlist.set_shuffle( session.shuffle );
                         playlist.set_repeat( session.repeat );
                         initial_track_index = session.track_index;
-                        initial_volume = session.volume;
+                        initial_volume_db = session.volume_db;
+                        initial_speed = session.speed;
                         tracing::info!(
-                            "Restored session: {}, track {}, shuffle={}, repeat={:?}, volume={}",
+                            "Restored session: {}, track {}, shuffle={}, repeat={:?}, volume={} dB, speed={}",
                             session.playlist_name,
                             session.track_index.unwrap_or( 0 ),
                             session.shuffle,
                             session.repeat,
-                            session.volume
+                            session.volume_db,
+                            session.speed
                         );
                     }
                 }
@@ -190,8 +224,21 @@ impl App {
             tracing::info!( "System media controls initialized" );
         }
 
-        // Apply initial volume to player
-        player.set_volume( initial_volume );
+        // Apply initial volume and speed to player
+        player.set_volume_db( initial_volume_db );
+        player.set_speed( initial_speed );
+
+        let settings = settings::Settings::load();
+        player.set_crossfade( settings.crossfade() );
+        player.set_replay_gain( settings.replay_gain() );
+        player.set_equalizer( settings.equalizer.clone() );
+        player.set_spectrum_settings( settings.spectrum() );
+        player.set_normalize_downmix( settings.normalize_downmix );
+        player.set_resample_settings( settings.resample() );
+        player.set_fade( settings.fade() );
+        player.set_resume_threshold( settings.resume_threshold() );
+        // Nothing is open yet, so this only records the preference
+        let _ = player.set_output_device( settings.output_device.clone() );
 
         let mut playlist_state = ListState::default();
         if initial_track_index.is_some() {
@@ -208,12 +255,17 @@ impl App {
             input_buffer: InputBuffer::new(),
             edit_mode: false,
             visualizer_style: VisualizerStyle::default(),
-            volume: initial_volume,
+            volume_db: initial_volume_db,
+            muted: false,
+            loop_start: None,
             scroll_to_playing: false,
+            cue_labels: HashMap::new(),
             last_click_time: None,
             last_click_row: None,
             playlist_area: None,
             help_scroll: 0,
+            show_all_tags: false,
+            tags_scroll: 0,
             status_message: None,
             status_clear_at: None,
             media_controls,
@@ -223,12 +275,176 @@ impl App {
             force_smtc_update: false,
             discord: discord::DiscordPresence::new(),
             last_discord_track: None,
-            settings: settings::Settings::load(),
+            settings,
             settings_selected: 0,
+            device_list: None,
+            device_selected: 0,
+            eq_selected: 0,
+            eq_unsaved: false,
+            eq_presets: settings::Settings::load_eq_presets(),
+            bookmarks_selected: 0,
         })
     }
 
 
+    /// Sets the volume, unmuting, and shows the new level.
+    fn set_volume_db( &mut self, db: f32 ) {
+        self.volume_db = db.clamp( MIN_VOLUME_DB, MAX_VOLUME_DB );
+        self.muted = false;
+        self.player.set_volume_db( self.volume_db );
+        self.set_status( format!( "Volume: {}", self.volume_label() ) );
+    }
+
+
+    /// Raises or lowers the volume by `step_db`, from the level before any mute.
+    fn step_volume( &mut self, step_db: f32 ) {
+        self.set_volume_db( self.volume_db + step_db );
+    }
+
+
+    /// Mutes, or restores the volume from before muting.
+    fn toggle_mute( &mut self ) {
+        if self.muted || self.volume_db <= MIN_VOLUME_DB {
+            // Coming back from a muted session has no level to restore
+            let db = if self.volume_db <= MIN_VOLUME_DB { 0.0 } else { self.volume_db };
+            self.set_volume_db( db );
+        } else {
+            self.muted = true;
+            self.player.set_volume_db( MIN_VOLUME_DB );
+            self.set_status( "Muted" );
+        }
+    }
+
+
+    /// Moves to the next, previous or a numbered chapter of the current track.
+    fn go_to_chapter( &mut self, arg: ChapterArg ) {
+        let chapters = self.player.chapters();
+        if chapters.is_empty() {
+            self.set_status( "This track has no chapters" );
+            return;
+        }
+
+        let result = match arg {
+            ChapterArg::Next => self.player.next_chapter(),
+            ChapterArg::Prev => self.player.previous_chapter(),
+            ChapterArg::Number( n ) => self.player.seek_chapter( n - 1 ).map( |_| true ),
+        };
+        match result {
+            Ok( true ) => {
+                if let Some( index ) = self.player.current_chapter() {
+                    let title = chapter_title( &chapters[ index ], index );
+                    self.set_status( format!( "Chapter {}/{}: {}", index + 1, chapters.len(), title ) );
+                }
+            }
+            Ok( false ) => self.set_status( "Already at the last chapter" ),
+            Err( e ) => self.set_status( format!( "Error: {}", e ) ),
+        }
+    }
+
+
+    /// Switches to audio stream `number` (counting from 1), or the next one.
+    fn switch_audio_stream( &mut self, number: Option<usize> ) {
+        let streams = self.player.audio_streams();
+        if streams.len() < 2 {
+            self.set_status( "This track has only one audio stream" );
+            return;
+        }
+
+        let index = match number {
+            Some( n ) => n - 1,
+            None => self.player.audio_stream().map_or( 0, |i| ( i + 1 ) % streams.len() ),
+        };
+        match self.player.select_audio_stream( index ) {
+            Ok(()) => self.set_status( format!( "Audio stream {}/{}: {}", index + 1, streams.len(), stream_label( &streams[ index ] ) ) ),
+            Err( e ) => self.set_status( format!( "Error: {}", e ) ),
+        }
+    }
+
+
+    /// Bookmarks the current position, named after the chapter or time if no name is given.
+    fn add_bookmark( &mut self, name: Option<String> ) {
+        let name = name.unwrap_or_else( || {
+            let chapters = self.player.chapters();
+            match self.player.current_chapter() {
+                Some( index ) => chapter_title( &chapters[ index ], index ),
+                None => format_time( self.player.position() ),
+            }
+        });
+
+        match self.player.add_bookmark( &name ) {
+            Ok( bookmark ) => self.set_status( format!(
+                "Bookmarked \"{}\" at {}",
+                bookmark.name.unwrap_or_default(),
+                format_time( bookmark.position )
+            )),
+            Err( e ) => self.set_status( format!( "Error: {}", e ) ),
+        }
+    }
+
+
+    /// Marks an A-B loop point at the current position, or stops looping.
+    fn set_loop_point( &mut self, arg: LoopArg ) {
+        let Some( track ) = self.player.current_track() else {
+            self.set_status( "Nothing playing to loop" );
+            return;
+        };
+        let position = self.player.position();
+
+        match arg {
+            LoopArg::A => {
+                self.loop_start = Some(( track, position ));
+                // Move the start of a running loop, as long as it stays before the end
+                match self.player.loop_region() {
+                    Some(( _, end )) if end > position => {
+                        self.player.set_loop( Some(( position, end )) );
+                        self.set_status( format!( "Looping {} - {}", format_time( position ), format_time( end ) ) );
+                    }
+                    _ => {
+                        self.player.set_loop( None );
+                        self.set_status( format!( "Loop start: {} (/loop b to set the end)", format_time( position ) ) );
+                    }
+                }
+            }
+            LoopArg::B => {
+                match self.loop_marker() {
+                    Some( start ) if position > start => {
+                        self.player.set_loop( Some(( start, position )) );
+                        self.set_status( format!( "Looping {} - {}", format_time( start ), format_time( position ) ) );
+                    }
+                    Some( _ ) => self.set_status( "Loop end must come after the start" ),
+                    None => self.set_status( "Set the loop start first with /loop a" ),
+                }
+            }
+            LoopArg::Off => {
+                self.loop_start = None;
+                self.player.set_loop( None );
+                self.set_status( "Loop off" );
+            }
+        }
+    }
+
+
+    /// Returns the loop start marked in the current track, if any.
+    fn loop_marker( &self ) -> Option<Duration> {
+        let current = self.player.current_track();
+        self.loop_start.as_ref()
+            .filter( |( track, _ )| Some( track ) == current.as_ref() )
+            .map( |( _, start )| *start )
+    }
+
+
+    /// Describes the current volume for the status line.
+    fn volume_label( &self ) -> String {
+        if self.muted || self.volume_db <= MIN_VOLUME_DB {
+            "muted".to_string()
+        } else if self.volume_db > 0.0 {
+            format!( "{} (boost, limited)", format_volume( self.volume_db ) )
+        } else {
+            format_volume( self.volume_db )
+        }
+    }
+
+
     /// Sets a status message that auto-clears after a delay.
     fn set_status( &mut self, msg: impl Into<String> ) {
         self.status_message = Some( msg.into() );
@@ -263,6 +479,14 @@ impl App {
             }
         }
 
+        // Gapless transitions change track without the current one ever "ending"
+        while let Some( event ) = self.player.poll_event() {
+            if let PlayerEvent::TrackChanged { .. } = event {
+                self.scroll_to_playing = true;
+                self.force_smtc_update = true;
+            }
+        }
+
         // Handle media control events (SMTC/MPRIS) - only if enabled
         while let Ok( cmd ) = self.media_controls_rx.try_recv() {
             if !self.settings.smtc_enabled {
@@ -351,18 +575,10 @@ impl App {
                 let artist = metadata.as_ref().and_then( |m| m.artist.clone() );
                 let album = metadata.as_ref().and_then( |m| m.album.clone() );
 
-                // Find album art and copy to temp (SMTC can't access network paths)
-                // Only use cover_url if the file was successfully copied to temp
-                let cover_url = Self::find_album_art( track_path ).filter( |_| {
-                    // Verify temp cover file exists
-                    let temp_path = std::env::temp_dir().join( "oxidio" );
-                    temp_path.read_dir()
-                        .map( |mut entries| entries.any( |e| {
-                            e.map( |e| e.file_name().to_string_lossy().starts_with( "cover." ) )
-                                .unwrap_or( false )
-                        }))
-                        .unwrap_or( false )
-                });
+                // Embedded or folder art, written to temp (SMTC can't read network paths or tags)
+                let cover_url = metadata.as_ref()
+                    .and_then( |m| m.cover() )
+                    .and_then( Self::write_cover_to_temp );
 
                 // Debug: show what we're sending to SMTC
                 tracing::debug!(
@@ -385,75 +601,16 @@ impl App {
     }
 
 
-    /// Finds album art in the same folder as the track.
-    /// Returns a file:// URL if found.
+    /// Writes cover art to the temp directory and returns a file:// URL to the copy.
+    /// This is needed because SMTC can't access network paths or embedded images directly.
     #[cfg( target_os = "windows" )]
-    fn find_album_art( track_path: &std::path::Path ) -> Option<String> {
-        let parent = track_path.parent()?;
-
-        // Common album art filenames (case-insensitive search)
-        let art_names = [
-            "cover", "folder", "album", "front", "art", "albumart", "album_art",
-        ];
-        let extensions = [ "jpg", "jpeg", "png", "bmp", "gif" ];
-
-        tracing::debug!( "Looking for album art in: {:?}", parent );
-
-        let mut found_path: Option<std::path::PathBuf> = None;
-
-        // Read directory and do case-insensitive matching (works for UNC paths to Linux NAS)
-        match std::fs::read_dir( parent ) {
-            Ok( entries ) => {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    let filename = path.file_stem()
-                        .and_then( |s| s.to_str() )
-                        .map( |s| s.to_lowercase() );
-                    let ext = path.extension()
-                        .and_then( |e| e.to_str() )
-                        .map( |e| e.to_lowercase() );
-
-                    if let ( Some( name ), Some( ext ) ) = ( filename, ext ) {
-                        // Check if it's an image with a preferred art name
-                        if extensions.contains( &ext.as_str() ) {
-                            if art_names.contains( &name.as_str() ) {
-                                tracing::debug!( "Found album art: {:?}", path );
-                                found_path = Some( path );
-                                break;
-                            }
-                            // Remember any image file as fallback
-                            if found_path.is_none() {
-                                found_path = Some( path );
-                            }
-                        }
-                    }
-                }
-            }
-            Err( e ) => {
-                tracing::warn!( "Failed to read directory {:?}: {}", parent, e );
-            }
-        }
-
-        if found_path.is_none() {
-            tracing::debug!( "No album art found in {:?}", parent );
-        }
-
-        // If we found album art, copy it to temp and return the local path
-        let source_path = found_path?;
-        Self::copy_to_temp_and_get_url( &source_path )
-    }
-
-
-    /// Copies a file to the temp directory and returns a file:// URL to the copy.
-    /// This is needed because SMTC can't access network paths directly.
-    #[cfg( target_os = "windows" )]
-    fn copy_to_temp_and_get_url( source: &std::path::Path ) -> Option<String> {
+    fn write_cover_to_temp( art: &oxidio_core::Artwork ) -> Option<String> {
         use std::os::windows::fs::MetadataExt;
 
         let temp_dir = std::env::temp_dir();
         let oxidio_temp = temp_dir.join( "oxidio" );
 
-        tracing::debug!( "Attempting to copy album art from: {:?}", source );
+        tracing::debug!( "Writing {} album art ({} bytes, from {:?})", art.mime_type, art.data.len(), art.path );
 
         // Create oxidio temp dir if it doesn't exist
         if !oxidio_temp.exists() {
@@ -464,8 +621,7 @@ impl App {
         }
 
         // Use a fixed filename so we overwrite the old cover each time
-        let ext = source.extension().and_then( |e| e.to_str() ).unwrap_or( "jpg" );
-        let dest_path = oxidio_temp.join( format!( "cover.{}", ext ) );
+        let dest_path = oxidio_temp.join( format!( "cover.{}", art.extension() ) );
 
         // Remove existing file first to avoid permission issues
         if dest_path.exists() {
@@ -484,15 +640,10 @@ impl App {
             }
         }
 
-        // Copy the file
-        match std::fs::copy( source, &dest_path ) {
-            Ok( bytes ) => {
-                tracing::debug!( "Copied {} bytes to {:?}", bytes, dest_path );
-            }
-            Err( e ) => {
-                tracing::warn!( "Failed to copy album art from {:?} to {:?}: {}", source, dest_path, e );
-                return None;
-            }
+        // Write the image
+        if let Err( e ) = std::fs::write( &dest_path, &art.data ) {
+            tracing::warn!( "Failed to write album art to {:?}: {}", dest_path, e );
+            return None;
         }
 
         // Clear file attributes (hidden, archive, system, read-only) so SMTC can access it
@@ -517,13 +668,6 @@ impl App {
     }
 
 
-    /// Stub for non-Windows.
-    #[cfg( not( target_os = "windows" ) )]
-    fn copy_to_temp_and_get_url( _source: &std::path::Path ) -> Option<String> {
-        None
-    }
-
-
     /// Stub for non-Windows platforms.
     #[cfg( not( target_os = "windows" ) )]
     fn update_media_controls( &mut self ) {
@@ -587,6 +731,12 @@ impl App {
             InputMode::Command => self.handle_command_key( code ),
             InputMode::Search => self.handle_search_key( code ),
         }
+
+        // Adjusting the EQ band by band saves once, when the view is left
+        if self.eq_unsaved && ( self.view_mode != ViewMode::Equalizer || self.should_quit ) {
+            self.settings.save();
+            self.eq_unsaved = false;
+        }
     }
 
 
@@ -670,6 +820,14 @@ impl App {
                 self.view_mode = ViewMode::Help;
                 return;
             }
+            KeyCode::Char( '[' ) => {
+                self.set_speed( self.player.speed() - 0.1 );
+                return;
+            }
+            KeyCode::Char( ']' ) => {
+                self.set_speed( self.player.speed() + 0.1 );
+                return;
+            }
             KeyCode::Esc => {
                 if self.view_mode == ViewMode::Help || self.view_mode == ViewMode::TrackInfo || self.view_mode == ViewMode::Visualizer {
                     self.view_mode = ViewMode::Playlist;
@@ -691,7 +849,9 @@ impl App {
             ViewMode::Help => self.handle_help_key( code ),
             ViewMode::TrackInfo => self.handle_track_info_key( code, modifiers ),
             ViewMode::Visualizer => self.handle_visualizer_key( code, modifiers ),
+            ViewMode::Equalizer => self.handle_equalizer_key( code ),
             ViewMode::Settings => self.handle_settings_key( code ),
+            ViewMode::Bookmarks => self.handle_bookmarks_key( code ),
         }
     }
 
@@ -811,33 +971,16 @@ impl App {
                 self.visualizer_style = self.visualizer_style.next();
                 self.set_status( format!( "Visualizer: {}", self.visualizer_style.name() ) );
             }
-            KeyCode::Char( '+' ) | KeyCode::Char( '=' ) => {
-                // Volume up
-                self.volume = ( self.volume + 0.05 ).min( 1.0 );
-                self.player.set_volume( self.volume );
-                self.set_status( format!( "Volume: {}%", ( self.volume * 100.0 ) as i32 ) );
-            }
-            KeyCode::Char( '-' ) | KeyCode::Char( '_' ) => {
-                // Volume down
-                self.volume = ( self.volume - 0.05 ).max( 0.0 );
-                self.player.set_volume( self.volume );
-                self.set_status( format!( "Volume: {}%", ( self.volume * 100.0 ) as i32 ) );
-            }
-            KeyCode::Char( 'm' ) => {
-                // Mute/unmute toggle
-                if self.volume > 0.0 {
-                    self.volume = 0.0;
-                    self.set_status( "Muted" );
-                } else {
-                    self.volume = 1.0;
-                    self.set_status( "Volume: 100%" );
-                }
-                self.player.set_volume( self.volume );
-            }
+            KeyCode::Char( '+' ) | KeyCode::Char( '=' ) => self.step_volume( VOLUME_STEP_DB ),
+            KeyCode::Char( '-' ) | KeyCode::Char( '_' ) => self.step_volume( -VOLUME_STEP_DB ),
+            KeyCode::Char( 'm' ) => self.toggle_mute(),
             KeyCode::Char( 'i' ) => {
                 // Show track info
                 self.view_mode = ViewMode::TrackInfo;
             }
+            KeyCode::Char( 'b' ) => {
+                self.view_mode = ViewMode::Bookmarks;
+            }
             KeyCode::Home | KeyCode::Char( 'g' ) => {
                 self.playlist_state.select( Some( 0 ) );
             }
@@ -869,7 +1012,7 @@ impl App {
                     // Add file to playlist
                     let playlist_arc = self.player.playlist();
                     let mut playlist = playlist_arc.write().unwrap();
-                    playlist.add( file_path );
+                    playlist.add_many( cue::expand( &file_path ) );
                     self.set_status( "Added to playlist" );
                 }
             }
@@ -896,7 +1039,7 @@ impl App {
                     } else if is_audio {
                         let playlist_arc = self.player.playlist();
                         let mut playlist = playlist_arc.write().unwrap();
-                        playlist.add( path );
+                        playlist.add_many( cue::expand( &path ) );
                         self.set_status( "Added to playlist" );
                     }
                 }
@@ -928,26 +1071,9 @@ impl App {
             KeyCode::Char( 'p' ) => self.play_previous(),
             KeyCode::Left => self.play_previous(),
             KeyCode::Right => self.play_next(),
-            KeyCode::Char( '+' ) | KeyCode::Char( '=' ) => {
-                self.volume = ( self.volume + 0.05 ).min( 1.0 );
-                self.player.set_volume( self.volume );
-                self.set_status( format!( "Volume: {}%", ( self.volume * 100.0 ) as i32 ) );
-            }
-            KeyCode::Char( '-' ) | KeyCode::Char( '_' ) => {
-                self.volume = ( self.volume - 0.05 ).max( 0.0 );
-                self.player.set_volume( self.volume );
-                self.set_status( format!( "Volume: {}%", ( self.volume * 100.0 ) as i32 ) );
-            }
-            KeyCode::Char( 'm' ) => {
-                if self.volume > 0.0 {
-                    self.volume = 0.0;
-                    self.set_status( "Muted" );
-                } else {
-                    self.volume = 1.0;
-                    self.set_status( "Volume: 100%" );
-                }
-                self.player.set_volume( self.volume );
-            }
+            KeyCode::Char( '+' ) | KeyCode::Char( '=' ) => self.step_volume( VOLUME_STEP_DB ),
+            KeyCode::Char( '-' ) | KeyCode::Char( '_' ) => self.step_volume( -VOLUME_STEP_DB ),
+            KeyCode::Char( 'm' ) => self.toggle_mute(),
             _ => {}
         }
     }
@@ -987,6 +1113,26 @@ impl App {
             KeyCode::Esc | KeyCode::Char( 'i' ) => {
                 self.view_mode = ViewMode::Playlist;
             }
+            KeyCode::Char( 'a' ) => self.switch_audio_stream( None ),
+            KeyCode::Char( 't' ) => {
+                self.show_all_tags = !self.show_all_tags;
+                self.tags_scroll = 0;
+            }
+            KeyCode::Up | KeyCode::Char( 'k' ) if self.show_all_tags => {
+                self.tags_scroll = self.tags_scroll.saturating_sub( 1 );
+            }
+            KeyCode::Down | KeyCode::Char( 'j' ) if self.show_all_tags => {
+                self.tags_scroll = self.tags_scroll.saturating_add( 1 );
+            }
+            KeyCode::PageUp if self.show_all_tags => {
+                self.tags_scroll = self.tags_scroll.saturating_sub( 10 );
+            }
+            KeyCode::PageDown if self.show_all_tags => {
+                self.tags_scroll = self.tags_scroll.saturating_add( 10 );
+            }
+            KeyCode::Home if self.show_all_tags => {
+                self.tags_scroll = 0;
+            }
             // Playback controls
             KeyCode::Char( ' ' ) => {
                 match self.player.state() {
@@ -1013,26 +1159,9 @@ impl App {
             }
             KeyCode::Right => self.play_next(),
             KeyCode::Left => self.play_previous(),
-            KeyCode::Char( '+' ) | KeyCode::Char( '=' ) => {
-                self.volume = ( self.volume + 0.05 ).min( 1.0 );
-                self.player.set_volume( self.volume );
-                self.set_status( format!( "Volume: {}%", ( self.volume * 100.0 ) as i32 ) );
-            }
-            KeyCode::Char( '-' ) | KeyCode::Char( '_' ) => {
-                self.volume = ( self.volume - 0.05 ).max( 0.0 );
-                self.player.set_volume( self.volume );
-                self.set_status( format!( "Volume: {}%", ( self.volume * 100.0 ) as i32 ) );
-            }
-            KeyCode::Char( 'm' ) => {
-                if self.volume > 0.0 {
-                    self.volume = 0.0;
-                    self.set_status( "Muted" );
-                } else {
-                    self.volume = 1.0;
-                    self.set_status( "Volume: 100%" );
-                }
-                self.player.set_volume( self.volume );
-            }
+            KeyCode::Char( '+' ) | KeyCode::Char( '=' ) => self.step_volume( VOLUME_STEP_DB ),
+            KeyCode::Char( '-' ) | KeyCode::Char( '_' ) => self.step_volume( -VOLUME_STEP_DB ),
+            KeyCode::Char( 'm' ) => self.toggle_mute(),
             _ => {}
         }
     }
@@ -1077,26 +1206,9 @@ impl App {
             }
             KeyCode::Right => self.play_next(),
             KeyCode::Left => self.play_previous(),
-            KeyCode::Char( '+' ) | KeyCode::Char( '=' ) => {
-                self.volume = ( self.volume + 0.05 ).min( 1.0 );
-                self.player.set_volume( self.volume );
-                self.set_status( format!( "Volume: {}%", ( self.volume * 100.0 ) as i32 ) );
-            }
-            KeyCode::Char( '-' ) | KeyCode::Char( '_' ) => {
-                self.volume = ( self.volume - 0.05 ).max( 0.0 );
-                self.player.set_volume( self.volume );
-                self.set_status( format!( "Volume: {}%", ( self.volume * 100.0 ) as i32 ) );
-            }
-            KeyCode::Char( 'm' ) => {
-                if self.volume > 0.0 {
-                    self.volume = 0.0;
-                    self.set_status( "Muted" );
-                } else {
-                    self.volume = 1.0;
-                    self.set_status( "Volume: 100%" );
-                }
-                self.player.set_volume( self.volume );
-            }
+            KeyCode::Char( '+' ) | KeyCode::Char( '=' ) => self.step_volume( VOLUME_STEP_DB ),
+            KeyCode::Char( '-' ) | KeyCode::Char( '_' ) => self.step_volume( -VOLUME_STEP_DB ),
+            KeyCode::Char( 'm' ) => self.toggle_mute(),
             _ => {}
         }
     }
@@ -1104,7 +1216,12 @@ impl App {
 
     fn handle_settings_key( &mut self, code: KeyCode ) {
         // Number of settings items
-        const SETTINGS_COUNT: usize = 2;
+        const SETTINGS_COUNT: usize = 5;
+
+        if self.device_list.is_some() {
+            self.handle_device_picker_key( code );
+            return;
+        }
 
         match code {
             KeyCode::Char( 'q' ) => {
@@ -1148,6 +1265,18 @@ impl App {
                             self.force_smtc_update = true;
                         }
                     }
+                    2 => {
+                        self.open_device_picker();
+                        return;
+                    }
+                    3 => {
+                        self.settings.normalize_downmix = !self.settings.normalize_downmix;
+                        self.player.set_normalize_downmix( self.settings.normalize_downmix );
+                    }
+                    4 => {
+                        self.settings.resample_quality = self.settings.resample_quality.next();
+                        self.player.set_resample_settings( self.settings.resample() );
+                    }
                     _ => {}
                 }
                 self.settings.save();
@@ -1164,31 +1293,210 @@ impl App {
             KeyCode::Char( 'p' ) => self.play_previous(),
             KeyCode::Left => self.play_previous(),
             KeyCode::Right => self.play_next(),
-            KeyCode::Char( '+' ) | KeyCode::Char( '=' ) => {
-                self.volume = ( self.volume + 0.05 ).min( 1.0 );
-                self.player.set_volume( self.volume );
-                self.set_status( format!( "Volume: {}%", ( self.volume * 100.0 ) as i32 ) );
-            }
-            KeyCode::Char( '-' ) | KeyCode::Char( '_' ) => {
-                self.volume = ( self.volume - 0.05 ).max( 0.0 );
-                self.player.set_volume( self.volume );
-                self.set_status( format!( "Volume: {}%", ( self.volume * 100.0 ) as i32 ) );
-            }
-            KeyCode::Char( 'm' ) => {
-                if self.volume > 0.0 {
-                    self.volume = 0.0;
-                    self.set_status( "Muted" );
-                } else {
-                    self.volume = 1.0;
-                    self.set_status( "Volume: 100%" );
+            KeyCode::Char( '+' ) | KeyCode::Char( '=' ) => self.step_volume( VOLUME_STEP_DB ),
+            KeyCode::Char( '-' ) | KeyCode::Char( '_' ) => self.step_volume( -VOLUME_STEP_DB ),
+            KeyCode::Char( 'm' ) => self.toggle_mute(),
+            _ => {}
+        }
+    }
+
+
+    /// Lists the output devices and selects the preferred one in the picker.
+    fn open_device_picker( &mut self ) {
+        let devices = oxidio_core::list_output_devices();
+        // Entry 0 is the system default
+        self.device_selected = self.settings.output_device.as_ref()
+            .and_then( |id| devices.iter().position( |d| d.id == *id ) )
+            .map_or( 0, |index| index + 1 );
+        self.device_list = Some( devices );
+    }
+
+
+    fn handle_device_picker_key( &mut self, code: KeyCode ) {
+        let count = self.device_list.as_ref().map_or( 0, |d| d.len() ) + 1;
+
+        match code {
+            KeyCode::Esc | KeyCode::Char( 'q' ) => {
+                self.device_list = None;
+            }
+            KeyCode::Up | KeyCode::Char( 'k' ) => {
+                self.device_selected = self.device_selected.saturating_sub( 1 );
+            }
+            KeyCode::Down | KeyCode::Char( 'j' ) if self.device_selected + 1 < count => {
+                self.device_selected += 1;
+            }
+            KeyCode::Enter | KeyCode::Char( ' ' ) => {
+                let devices = self.device_list.take().unwrap_or_default();
+                let device = self.device_selected.checked_sub( 1 )
+                    .and_then( |index| devices.get( index ) )
+                    .map( |d| d.id.clone() );
+                let name = device.as_ref().map_or( "System default".to_string(), |d| d.name.clone() );
+
+                self.settings.output_device = device.clone();
+                self.settings.save();
+                match self.player.set_output_device( device ) {
+                    Ok(()) => self.set_status( format!( "Output device: {}", name ) ),
+                    Err( e ) => self.set_status( format!( "Failed to switch output device: {}", e ) ),
+                }
+            }
+            _ => {}
+        }
+    }
+
+
+    fn handle_equalizer_key( &mut self, code: KeyCode ) {
+        let band_count = self.settings.equalizer.bands.len();
+
+        match code {
+            KeyCode::Char( 'q' ) => {
+                self.should_quit = true;
+            }
+            KeyCode::Esc => {
+                self.view_mode = ViewMode::Playlist;
+            }
+            KeyCode::Left | KeyCode::Char( 'h' ) => {
+                self.eq_selected = self.eq_selected.saturating_sub( 1 );
+            }
+            KeyCode::Right | KeyCode::Char( 'l' ) if self.eq_selected + 1 < band_count => {
+                self.eq_selected += 1;
+            }
+            KeyCode::Up | KeyCode::Char( 'k' ) => self.adjust_eq_band( 1.0 ),
+            KeyCode::Down | KeyCode::Char( 'j' ) => self.adjust_eq_band( -1.0 ),
+            KeyCode::Enter | KeyCode::Char( 'e' ) => {
+                self.settings.equalizer.enabled = !self.settings.equalizer.enabled;
+                self.apply_equalizer();
+                self.set_status( format!( "Equalizer: {}", if self.settings.equalizer.enabled { "on" } else { "off" } ) );
+            }
+            KeyCode::Char( 'r' ) => {
+                for band in &mut self.settings.equalizer.bands {
+                    band.gain_db = 0.0;
+                }
+                self.apply_equalizer();
+                self.set_status( "Equalizer: reset to flat" );
+            }
+            // Playback controls
+            KeyCode::Char( ' ' ) => {
+                match self.player.state() {
+                    PlaybackState::Playing => { let _ = self.player.pause(); }
+                    PlaybackState::Paused => { let _ = self.player.resume(); }
+                    PlaybackState::Stopped => { self.play_selected(); }
+                }
+            }
+            KeyCode::Char( 'n' ) => self.play_next(),
+            KeyCode::Char( 'p' ) => self.play_previous(),
+            _ => {}
+        }
+    }
+
+
+    fn handle_bookmarks_key( &mut self, code: KeyCode ) {
+        let bookmarks = self.player.bookmarks();
+
+        match code {
+            KeyCode::Char( 'q' ) => {
+                self.should_quit = true;
+            }
+            KeyCode::Esc | KeyCode::Char( 'b' ) => {
+                self.view_mode = ViewMode::Playlist;
+            }
+            KeyCode::Up | KeyCode::Char( 'k' ) => {
+                self.bookmarks_selected = self.bookmarks_selected.saturating_sub( 1 );
+            }
+            KeyCode::Down | KeyCode::Char( 'j' ) if self.bookmarks_selected + 1 < bookmarks.len() => {
+                self.bookmarks_selected += 1;
+            }
+            KeyCode::Enter => {
+                if let Some( bookmark ) = bookmarks.get( self.bookmarks_selected ) {
+                    self.jump_to_bookmark( bookmark );
+                }
+            }
+            KeyCode::Char( 'd' ) | KeyCode::Delete => {
+                if let Some( bookmark ) = bookmarks.get( self.bookmarks_selected ) {
+                    self.player.remove_bookmark( bookmark );
+                    self.bookmarks_selected = self.bookmarks_selected.min( bookmarks.len().saturating_sub( 2 ) );
+                    self.set_status( "Bookmark deleted" );
                 }
-                self.player.set_volume( self.volume );
             }
+            // Playback controls
+            KeyCode::Char( ' ' ) => {
+                match self.player.state() {
+                    PlaybackState::Playing => { let _ = self.player.pause(); }
+                    PlaybackState::Paused => { let _ = self.player.resume(); }
+                    PlaybackState::Stopped => { self.play_selected(); }
+                }
+            }
+            KeyCode::Char( 'n' ) => self.play_next(),
+            KeyCode::Char( 'p' ) => self.play_previous(),
             _ => {}
         }
     }
 
 
+    /// Plays from a bookmark, keeping the playlist position in step if the track is in it.
+    fn jump_to_bookmark( &mut self, bookmark: &Bookmark ) {
+        let playlist = self.player.playlist();
+        let mut playlist = playlist.write().unwrap();
+        if let Some( index ) = playlist.tracks().iter().position( |p| *p == bookmark.path ) {
+            playlist.jump_to( index );
+            self.playlist_state.select( Some( index ) );
+        }
+        drop( playlist );
+
+        match self.player.jump_to_bookmark( bookmark ) {
+            Ok(()) => {
+                self.force_smtc_update = true;
+                self.set_status( format!( "Jumped to {}", format_time( bookmark.position ) ) );
+            }
+            Err( e ) => self.set_status( format!( "Play error: {}", e ) ),
+        }
+    }
+
+
+    /// Sets the playback speed, rounded to a tenth, and reports it.
+    fn set_speed( &mut self, speed: f32 ) {
+        self.player.set_speed( ( speed * 10.0 ).round() / 10.0 );
+        self.set_status( format!( "Speed: {}x", self.player.speed() ) );
+    }
+
+
+    /// Changes the gain of the selected equalizer band, switching the equalizer on.
+    fn adjust_eq_band( &mut self, delta_db: f32 ) {
+        let Some( band ) = self.settings.equalizer.bands.get_mut( self.eq_selected ) else {
+            return;
+        };
+        band.gain_db = ( band.gain_db + delta_db ).clamp( -MAX_GAIN_DB, MAX_GAIN_DB );
+        let frequency = band.frequency;
+        let gain_db = band.gain_db;
+
+        self.settings.equalizer.enabled = true;
+        self.apply_equalizer();
+        self.set_status( format!( "EQ {}: {:+.0} dB", format_frequency( frequency ), gain_db ) );
+    }
+
+
+    /// Pushes the equalizer settings to the player, marking them to be saved.
+    fn apply_equalizer( &mut self ) {
+        self.player.set_equalizer( self.settings.equalizer.clone() );
+        self.eq_unsaved = true;
+    }
+
+
+    /// Returns the built-in presets followed by the user's own.
+    fn all_eq_presets( &self ) -> Vec<EqPreset> {
+        let mut presets = EqPreset::builtin();
+        presets.extend( self.eq_presets.iter().cloned() );
+        presets
+    }
+
+
+    /// Returns the name of the preset matching the current bands, if any.
+    fn current_eq_preset( &self ) -> Option<String> {
+        self.all_eq_presets().into_iter()
+            .find( |p| p.bands == self.settings.equalizer.bands )
+            .map( |p| p.name )
+    }
+
+
     fn handle_command_key( &mut self, code: KeyCode ) {
         match code {
             KeyCode::Enter => {
@@ -1282,7 +1590,7 @@ impl App {
                 } else {
                     let playlist_arc = self.player.playlist();
                     let mut playlist = playlist_arc.write().unwrap();
-                    playlist.add( path );
+                    playlist.add_many( cue::expand( &path ) );
                     self.set_status( "Added to playlist" );
                 }
             }
@@ -1403,17 +1711,128 @@ impl App {
                     Err( e ) => self.set_status( format!( "Seek error: {}", e ) ),
                 }
             }
+            Command::Crossfade { seconds, curve } => {
+                if let Some( seconds ) = seconds {
+                    self.settings.crossfade_secs = seconds;
+                }
+                if let Some( curve ) = curve {
+                    self.settings.crossfade_curve = curve;
+                }
+                if seconds.is_some() || curve.is_some() {
+                    self.player.set_crossfade( self.settings.crossfade() );
+                    self.settings.save();
+                }
+
+                if self.settings.crossfade_secs > 0.0 {
+                    self.set_status( format!(
+                        "Crossfade: {}s ({})",
+                        self.settings.crossfade_secs,
+                        self.settings.crossfade_curve.name()
+                    ));
+                } else {
+                    self.set_status( "Crossfade: off" );
+                }
+            }
+            Command::ReplayGain { mode, preamp_db, prevent_clipping, target_lufs } => {
+                if let Some( mode ) = mode {
+                    self.settings.replaygain_mode = mode;
+                }
+                if let Some( preamp_db ) = preamp_db {
+                    self.settings.replaygain_preamp_db = preamp_db;
+                }
+                if let Some( prevent_clipping ) = prevent_clipping {
+                    self.settings.replaygain_prevent_clipping = prevent_clipping;
+                }
+                if let Some( target_lufs ) = target_lufs {
+                    self.settings.loudness_target_lufs = target_lufs;
+                }
+                if mode.is_some() || preamp_db.is_some() || prevent_clipping.is_some() || target_lufs.is_some() {
+                    self.player.set_replay_gain( self.settings.replay_gain() );
+                    self.settings.save();
+                }
+
+                self.set_status( format!(
+                    "ReplayGain: {} (preamp {:+.1} dB, clipping prevention {}, untagged target {} LUFS)",
+                    self.settings.replaygain_mode.name(),
+                    self.settings.replaygain_preamp_db,
+                    if self.settings.replaygain_prevent_clipping { "on" } else { "off" },
+                    self.settings.loudness_target_lufs
+                ));
+            }
+            Command::Eq { arg } => {
+                match arg {
+                    None => {}
+                    Some( EqArg::On ) => {
+                        self.settings.equalizer.enabled = true;
+                        self.apply_equalizer();
+                    }
+                    Some( EqArg::Off ) => {
+                        self.settings.equalizer.enabled = false;
+                        self.apply_equalizer();
+                    }
+                    Some( EqArg::Preset( name ) ) => {
+                        let presets = self.all_eq_presets();
+                        match EqPreset::find( &presets, &name ) {
+                            Some( preset ) => {
+                                self.settings.equalizer.bands = preset.bands.clone();
+                                self.settings.equalizer.enabled = true;
+                                self.eq_selected = self.eq_selected.min( preset.bands.len().saturating_sub( 1 ) );
+                                self.apply_equalizer();
+                            }
+                            None => {
+                                let names: Vec<_> = presets.iter().map( |p| p.name.as_str() ).collect();
+                                self.set_status( format!( "Unknown preset: {}. Available: {}", name, names.join( ", " ) ) );
+                                return Ok(());
+                            }
+                        }
+                    }
+                    Some( EqArg::Save( name ) ) => {
+                        if EqPreset::find( &EqPreset::builtin(), &name ).is_some() {
+                            self.set_status( format!( "Cannot overwrite built-in preset: {}", name ) );
+                            return Ok(());
+                        }
+                        let preset = EqPreset { name: name.clone(), bands: self.settings.equalizer.bands.clone() };
+                        match self.eq_presets.iter().position( |p| EqPreset::find( std::slice::from_ref( p ), &name ).is_some() ) {
+                            Some( index ) => self.eq_presets[ index ] = preset,
+                            None => self.eq_presets.push( preset ),
+                        }
+                        settings::Settings::save_eq_presets( &self.eq_presets );
+                        self.set_status( format!( "Saved equalizer preset: {}", name ) );
+                        return Ok(());
+                    }
+                }
+
+                if self.settings.equalizer.enabled {
+                    let preset = self.current_eq_preset().unwrap_or_else( || "custom".to_string() );
+                    self.set_status( format!( "Equalizer: on ({})", preset ) );
+                } else {
+                    self.set_status( "Equalizer: off" );
+                }
+            }
+            Command::Speed { factor } => {
+                match factor {
+                    Some( factor ) => {
+                        self.player.set_speed( factor );
+                        self.set_status( format!( "Speed: {}x", self.player.speed() ) );
+                    }
+                    None => self.set_status( format!( "Speed: {}x", self.player.speed() ) ),
+                }
+            }
+            Command::Loop { arg } => self.set_loop_point( arg ),
+            Command::Chapter { arg } => self.go_to_chapter( arg ),
+            Command::Bookmark { arg: BookmarkArg::Add( name ) } => self.add_bookmark( name ),
+            Command::Bookmark { arg: BookmarkArg::List } => {
+                self.view_mode = ViewMode::Bookmarks;
+            }
+            Command::Stream { number } => self.switch_audio_stream( number ),
             Command::Vis => {
                 self.visualizer_style = self.visualizer_style.next();
                 self.set_status( format!( "Visualizer: {}", self.visualizer_style.name() ) );
             }
-            Command::Volume { level } => {
-                if let Some( level ) = level {
-                    self.volume = ( level as f32 / 100.0 ).clamp( 0.0, 1.0 );
-                    self.player.set_volume( self.volume );
-                    self.set_status( format!( "Volume: {}%", level.min( 100 ) ) );
-                } else {
-                    self.set_status( format!( "Volume: {}%", ( self.volume * 100.0 ) as i32 ) );
+            Command::Volume { db } => {
+                match db {
+                    Some( db ) => self.set_volume_db( db ),
+                    None => self.set_status( format!( "Volume: {}", self.volume_label() ) ),
                 }
             }
         }
@@ -1482,7 +1901,7 @@ impl App {
         if let Some( path ) = playlist.next() {
             let path = path.clone();
             drop( playlist );
-            if let Err( e ) = self.player.play( path ) {
+            if let Err( e ) = self.player.transition_to( path ) {
                 self.set_status( format!( "Play error: {}", e ) );
             } else {
                 self.force_smtc_update = true;
@@ -1497,7 +1916,7 @@ impl App {
         if let Some( path ) = playlist.previous() {
             let path = path.clone();
             drop( playlist );
-            if let Err( e ) = self.player.play( path ) {
+            if let Err( e ) = self.player.transition_to( path ) {
                 self.set_status( format!( "Play error: {}", e ) );
             } else {
                 self.force_smtc_update = true;
@@ -1568,13 +1987,14 @@ impl App {
             }
         }
 
-        // Save the session state including shuffle, repeat, and volume
+        // Save the session state including shuffle, repeat, volume and speed
         let state = oxidio_core::playlist::SessionState {
             playlist_name: "_last".to_string(),
             track_index: playlist.current_index().or( self.playlist_state.selected() ),
             shuffle: playlist.shuffle(),
             repeat: playlist.repeat(),
-            volume: self.volume,
+            volume_db: self.player.volume_db(),
+            speed: self.player.speed(),
         };
 
         if let Err( e ) = oxidio_core::Playlist::save_session( &state ) {
@@ -1656,7 +2076,9 @@ fn draw_ui( frame: &mut Frame, app: &mut App ) {
         ViewMode::Help => "HELP",
         ViewMode::TrackInfo => "TRACK INFO",
         ViewMode::Visualizer => "VISUALIZER",
+        ViewMode::Equalizer => "EQUALIZER",
         ViewMode::Settings => "SETTINGS",
+        ViewMode::Bookmarks => "BOOKMARKS",
     };
 
     let header = Paragraph::new( format!( "  OXIDIO - {}", view_indicator ) )
@@ -1671,7 +2093,9 @@ fn draw_ui( frame: &mut Frame, app: &mut App ) {
         ViewMode::Help => draw_help( frame, app, chunks[1] ),
         ViewMode::TrackInfo => draw_track_info( frame, app, chunks[1] ),
         ViewMode::Visualizer => draw_visualizer( frame, app, chunks[1] ),
+        ViewMode::Equalizer => draw_equalizer( frame, app, chunks[1] ),
         ViewMode::Settings => draw_settings( frame, app, chunks[1] ),
+        ViewMode::Bookmarks => draw_bookmarks( frame, app, chunks[1] ),
     }
 
     // Now playing
@@ -1682,6 +2106,26 @@ fn draw_ui( frame: &mut Frame, app: &mut App ) {
 }
 
 
+/// Returns the playlist label of a cue sheet track, e.g. `Album.cue 03. Title`.
+///
+/// The whole sheet is read the first time one of its tracks is shown.
+fn cue_label<'a>( labels: &'a mut HashMap<PathBuf, String>, path: &Path ) -> &'a str {
+    if !labels.contains_key( path ) {
+        let ( cue_path, _ ) = cue::parse_track_path( path ).unwrap_or_default();
+        let name = cue_path.file_name().map( |n| n.to_string_lossy().into_owned() ).unwrap_or_default();
+        if let Ok( sheet ) = CueSheet::load( &cue_path ) {
+            for track in &sheet.tracks {
+                let title = track.title.clone().unwrap_or_else( || format!( "Track {}", track.number ) );
+                labels.insert( cue::track_path( &cue_path, track.number ), format!( "{} {:02}. {}", name, track.number, title ) );
+            }
+        }
+        // Tracks missing from the sheet keep their path
+        labels.entry( path.to_path_buf() ).or_insert_with( || path.to_string_lossy().into_owned() );
+    }
+    &labels[ path ]
+}
+
+
 fn draw_playlist( frame: &mut Frame, app: &mut App, area: Rect ) {
     // Store area for mouse hit detection
     app.playlist_area = Some( area );
@@ -1718,10 +2162,13 @@ fn draw_playlist( frame: &mut Frame, app: &mut App, area: Rect ) {
         .iter()
         .enumerate()
         .map( |( i, path )| {
-            let filename = path
-                .file_name()
-                .and_then( |n| n.to_str() )
-                .unwrap_or( "Unknown" );
+            let filename = match cue::parse_track_path( path ) {
+                Some( _ ) => cue_label( &mut app.cue_labels, path ),
+                None => path
+                    .file_name()
+                    .and_then( |n| n.to_str() )
+                    .unwrap_or( "Unknown" ),
+            };
             let prefix = if Some( i ) == playing_index {
                 "▶ "
             } else if app.edit_mode {
@@ -1836,9 +2283,21 @@ fn draw_help( frame: &mut Frame, app: &mut App, area: Rect ) {
 }
 
 
-fn draw_track_info( frame: &mut Frame, app: &App, area: Rect ) {
+fn draw_track_info( frame: &mut Frame, app: &mut App, area: Rect ) {
     let mut lines = Vec::new();
 
+    // Tags as stored in the file, beside the summary
+    let area = if app.show_all_tags {
+        let halves = Layout::default()
+            .direction( Direction::Horizontal )
+            .constraints([ Constraint::Percentage( 50 ), Constraint::Percentage( 50 ) ])
+            .split( area );
+        draw_all_tags( frame, app, halves[1] );
+        halves[0]
+    } else {
+        area
+    };
+
     // Get the track path - either currently playing or selected
     let track_path = app.player.current_track().or_else( || {
         app.playlist_state.selected().and_then( |idx| {
@@ -1893,7 +2352,13 @@ fn draw_track_info( frame: &mut Frame, app: &App, area: Rect ) {
             if let Some( track_num ) = meta.track_number {
                 lines.push( Line::from( vec![
                     Span::styled( "Track #: ", Style::default().fg( Color::Gray ) ),
-                    Span::raw( track_num.to_string() ),
+                    Span::raw( number_of( track_num, meta.track_total ) ),
+                ]));
+            }
+            if let Some( disc ) = meta.disc_number {
+                lines.push( Line::from( vec![
+                    Span::styled( "Disc:   ", Style::default().fg( Color::Gray ) ),
+                    Span::raw( number_of( disc, meta.disc_total ) ),
                 ]));
             }
             if let Some( genre ) = &meta.genre {
@@ -1908,6 +2373,38 @@ fn draw_track_info( frame: &mut Frame, app: &App, area: Rect ) {
                     Span::raw( year.to_string() ),
                 ]));
             }
+            let people = [ ( "Composer: ", &meta.composer ), ( "Conductor: ", &meta.conductor ) ];
+            for ( label, value ) in people {
+                if let Some( value ) = value {
+                    lines.push( Line::from( vec![
+                        Span::styled( label, Style::default().fg( Color::Gray ) ),
+                        Span::raw( value.clone() ),
+                    ]));
+                }
+            }
+            if let Some( bpm ) = meta.bpm {
+                lines.push( Line::from( vec![
+                    Span::styled( "BPM:    ", Style::default().fg( Color::Gray ) ),
+                    Span::raw( bpm.to_string() ),
+                ]));
+            }
+            if let Some( isrc ) = &meta.isrc {
+                lines.push( Line::from( vec![
+                    Span::styled( "ISRC:   ", Style::default().fg( Color::Gray ) ),
+                    Span::raw( isrc.clone() ),
+                ]));
+            }
+            if let Some( art ) = meta.cover() {
+                let source = if art.is_embedded() { "embedded" } else { "folder" };
+                let mut label = format!( "{}, {}, {} KB ({})", art.picture_type.name(), art.mime_type, art.data.len().div_ceil( 1024 ), source );
+                if meta.artwork.len() > 1 {
+                    label.push_str( &format!( ", {} images", meta.artwork.len() ) );
+                }
+                lines.push( Line::from( vec![
+                    Span::styled( "Artwork: ", Style::default().fg( Color::Gray ) ),
+                    Span::raw( label ),
+                ]));
+            }
         }
 
         lines.push( Line::from( "" ) );
@@ -1931,11 +2428,18 @@ fn draw_track_info( frame: &mut Frame, app: &App, area: Rect ) {
                 ]));
             }
 
-            // Sample rate
-            if let Some( sample_rate ) = meta.sample_rate {
+            // Sample rate, and what it is converted to for the output
+            let rates = match ( app.player.sample_rates(), meta.sample_rate ) {
+                ( Some(( source, output, Some( quality ) )), _ ) => {
+                    Some( format!( "{} Hz → {} Hz ({})", source, output, quality.name() ) )
+                }
+                ( Some(( source, _, None )), _ ) => Some( format!( "{} Hz", source ) ),
+                ( None, sample_rate ) => sample_rate.map( |rate| format!( "{} Hz", rate ) ),
+            };
+            if let Some( rates ) = rates {
                 lines.push( Line::from( vec![
                     Span::styled( "Sample Rate: ", Style::default().fg( Color::Gray ) ),
-                    Span::raw( format!( "{} Hz", sample_rate ) ),
+                    Span::raw( rates ),
                 ]));
             }
 
@@ -1962,6 +2466,70 @@ fn draw_track_info( frame: &mut Frame, app: &App, area: Rect ) {
             ]));
         }
 
+        // Output dropouts
+        let underruns = app.player.underruns();
+        let underrun_style = if underruns > 0 { Style::default().fg( Color::Yellow ) } else { Style::default() };
+        lines.push( Line::from( vec![
+            Span::styled( "Dropouts:    ", Style::default().fg( Color::Gray ) ),
+            Span::styled( underruns.to_string(), underrun_style ),
+        ]));
+
+        // Audio streams to choose from
+        let streams = app.player.audio_streams();
+        if streams.len() > 1 {
+            let current = app.player.audio_stream();
+            lines.push( Line::from( "" ) );
+            lines.push( Line::from( Span::styled(
+                format!( "─── Audio Streams ({}, a or /stream to switch) ───", streams.len() ),
+                Style::default().fg( Color::DarkGray ),
+            )));
+            for ( i, stream ) in streams.iter().enumerate() {
+                let text = format!( "{:>3}. {}", i + 1, stream_label( stream ) );
+                if Some( i ) == current {
+                    lines.push( Line::from( Span::styled( format!( "▶{}", text ), Style::default().fg( Color::Cyan ).bold() ) ) );
+                } else {
+                    lines.push( Line::from( format!( " {}", text ) ) );
+                }
+            }
+        }
+
+        // ReplayGain and measured loudness
+        if let Some( meta ) = meta.as_ref().filter( |m| m.replay_gain.is_tagged() || m.loudness.is_some() ) {
+            let gain = meta.replay_gain;
+            lines.push( Line::from( "" ) );
+            lines.push( Line::from( Span::styled( "─── Loudness ───", Style::default().fg( Color::DarkGray ) ) ) );
+
+            let format_gain = |gain: Option<f32>, peak: Option<f32>| {
+                match ( gain, peak ) {
+                    ( Some( gain ), Some( peak ) ) => format!( "{:+.2} dB (peak {:.3})", gain, peak ),
+                    ( Some( gain ), None ) => format!( "{:+.2} dB", gain ),
+                    _ => "-".to_string(),
+                }
+            };
+            lines.push( Line::from( vec![
+                Span::styled( "Track Gain:  ", Style::default().fg( Color::Gray ) ),
+                Span::raw( format_gain( gain.track_gain, gain.track_peak ) ),
+            ]));
+            lines.push( Line::from( vec![
+                Span::styled( "Album Gain:  ", Style::default().fg( Color::Gray ) ),
+                Span::raw( format_gain( gain.album_gain, gain.album_peak ) ),
+            ]));
+            if let Some( loudness ) = meta.loudness {
+                lines.push( Line::from( vec![
+                    Span::styled( "Measured:    ", Style::default().fg( Color::Gray ) ),
+                    Span::raw( format!( "{:.1} LUFS (peak {:.3})", loudness.integrated_lufs, loudness.peak ) ),
+                ]));
+            }
+
+            let settings = app.settings.replay_gain();
+            let in_album = app.player.playlist().read().unwrap().in_album_order( path );
+            let factor = settings.factor( &gain, meta.loudness, in_album );
+            lines.push( Line::from( vec![
+                Span::styled( "Applied:     ", Style::default().fg( Color::Gray ) ),
+                Span::raw( format!( "{:+.2} dB ({})", 20.0 * factor.log10(), settings.mode.name() ) ),
+            ]));
+        }
+
         lines.push( Line::from( "" ) );
         lines.push( Line::from( Span::styled( "─── File ───", Style::default().fg( Color::DarkGray ) ) ) );
 
@@ -1979,6 +2547,28 @@ fn draw_track_info( frame: &mut Frame, app: &App, area: Rect ) {
             Span::styled( "Path: ", Style::default().fg( Color::Gray ) ),
             Span::raw( path.display().to_string() ),
         ]));
+
+        // Chapters, as many around the current one as fit
+        let chapters = app.player.chapters();
+        if !chapters.is_empty() {
+            let current = app.player.current_chapter();
+            lines.push( Line::from( "" ) );
+            lines.push( Line::from( Span::styled(
+                format!( "─── Chapters ({}) ───", chapters.len() ),
+                Style::default().fg( Color::DarkGray ),
+            )));
+
+            let room = ( area.height as usize ).saturating_sub( lines.len() + 2 ).max( 1 );
+            let first = current.unwrap_or( 0 ).saturating_sub( room / 2 ).min( chapters.len().saturating_sub( room ) );
+            for ( i, chapter ) in chapters.iter().enumerate().skip( first ).take( room ) {
+                let text = format!( "{:>3}. {:>7}  {}", i + 1, format_time( chapter.start ), chapter_title( chapter, i ) );
+                if Some( i ) == current {
+                    lines.push( Line::from( Span::styled( format!( "▶{}", text ), Style::default().fg( Color::Cyan ).bold() ) ) );
+                } else {
+                    lines.push( Line::from( format!( " {}", text ) ) );
+                }
+            }
+        }
     } else {
         lines.push( Line::from( Span::styled(
             "No track selected or playing",
@@ -2006,6 +2596,44 @@ fn draw_track_info( frame: &mut Frame, app: &App, area: Rect ) {
 }
 
 
+/// Draws every tag of the current track, one `KEY  value` per line, scrolled by `tags_scroll`.
+fn draw_all_tags( frame: &mut Frame, app: &mut App, area: Rect ) {
+    let tags = app.player.metadata().map( |m| m.raw_tags ).unwrap_or_default();
+    let key_width = tags.iter().map( |( key, _ )| key.chars().count() ).max().unwrap_or( 0 ).min( 24 );
+
+    // Multi-line values (lyrics, comments) continue under the first line
+    let mut lines = Vec::new();
+    for ( key, value ) in &tags {
+        for ( i, text ) in value.lines().enumerate() {
+            let key = if i == 0 { key.as_str() } else { "" };
+            lines.push( Line::from( vec![
+                Span::styled( format!( "{:<width$}  ", key, width = key_width ), Style::default().fg( Color::Gray ) ),
+                Span::raw( text.to_string() ),
+            ]));
+        }
+    }
+    if lines.is_empty() {
+        lines.push( Line::from( Span::styled( "No tags", Style::default().fg( Color::DarkGray ).italic() ) ) );
+    }
+
+    // Clamp scroll to valid range
+    let visible_height = area.height.saturating_sub( 2 );
+    let max_scroll = ( lines.len() as u16 ).saturating_sub( visible_height );
+    if app.tags_scroll > max_scroll {
+        app.tags_scroll = max_scroll;
+    }
+
+    let pane = Paragraph::new( lines )
+        .block( Block::default()
+            .title( format!( " All Tags ({}, ↑↓ scroll, t to hide) ", tags.len() ) )
+            .borders( Borders::ALL )
+        )
+        .scroll(( app.tags_scroll, 0 ));
+
+    frame.render_widget( pane, area );
+}
+
+
 fn draw_now_playing( frame: &mut Frame, app: &App, area: Rect ) {
     let state = app.player.state();
     let state_str = match state {
@@ -2045,25 +2673,40 @@ fn draw_now_playing( frame: &mut Frame, app: &App, area: Rect ) {
     let position = app.player.position();
     let duration = app.player.duration().unwrap_or( std::time::Duration::ZERO );
 
-    // Format time as M:SS
-    let format_time = |d: std::time::Duration| -> String {
-        let secs = d.as_secs();
-        format!( "{}:{:02}", secs / 60, secs % 60 )
-    };
-
     // Calculate progress bar
     let progress_width = 20;
-    let progress = if duration.as_secs() > 0 {
-        ( position.as_secs_f64() / duration.as_secs_f64() ).min( 1.0 )
-    } else {
-        0.0
+    let column = |d: std::time::Duration| -> Option<usize> {
+        ( duration.as_secs() > 0 ).then( || {
+            ( ( d.as_secs_f64() / duration.as_secs_f64() ).min( 1.0 ) * progress_width as f64 ).round() as usize
+        })
     };
-    let filled = ( progress * progress_width as f64 ).round() as usize;
-    let bar = format!(
-        "[{}{}]",
-        "━".repeat( filled ),
-        "─".repeat( progress_width - filled )
-    );
+    let filled = column( position ).unwrap_or( 0 );
+
+    // Mark the A-B loop, or just its start while the end is still to be set
+    let ( loop_a, loop_b ) = match app.player.loop_region() {
+        Some(( start, end )) => ( Some( start ), Some( end ) ),
+        None => ( app.loop_marker(), None ),
+    };
+    let marker_at = |d: Option<std::time::Duration>| d.and_then( column ).map( |c| c.min( progress_width - 1 ) );
+    let ( loop_a, loop_b ) = ( marker_at( loop_a ), marker_at( loop_b ) );
+
+    // Tick where each chapter after the first starts
+    let ticks: Vec<usize> = app.player.chapters().iter()
+        .filter( |c| !c.start.is_zero() )
+        .filter_map( |c| marker_at( Some( c.start ) ) )
+        .collect();
+
+    let mut bar = vec![ Span::raw( " [" ) ];
+    for i in 0..progress_width {
+        let marker = if Some( i ) == loop_b { Some( "B" ) } else if Some( i ) == loop_a { Some( "A" ) } else { None };
+        match marker {
+            Some( marker ) => bar.push( Span::styled( marker, Style::default().fg( Color::Yellow ).bold() ) ),
+            None if ticks.contains( &i ) => {
+                bar.push( Span::styled( if i < filled { "┿" } else { "┼" }, Style::default().fg( Color::Cyan ) ) );
+            }
+            None => bar.push( Span::raw( if i < filled { "━" } else { "─" } ) ),
+        }
+    }
 
     let mut lines = vec![
         Line::from( Span::styled( format!( " {} {} ", state_str, title ), Style::default().bold() ) ),
@@ -2075,10 +2718,20 @@ fn draw_now_playing( frame: &mut Frame, app: &App, area: Rect ) {
     }
 
     // Show volume indicator
-    let vol_pct = ( app.volume * 100.0 ) as i32;
-    let vol_str = if vol_pct == 0 { "🔇".to_string() } else { format!( "🔊{}%", vol_pct ) };
+    let mut vol_str = if app.muted || app.volume_db <= MIN_VOLUME_DB {
+        "🔇".to_string()
+    } else {
+        format!( "🔊{}", format_volume( app.volume_db ) )
+    };
+
+    // Show the speed only when it's not normal
+    let speed = app.player.speed();
+    if speed != 1.0 {
+        vol_str.push_str( &format!( "  {}x", speed ) );
+    }
 
-    lines.push( Line::from( format!( " {} {} / {}  {} ", bar, format_time( position ), format_time( duration ), vol_str ) ) );
+    bar.push( Span::raw( format!( "] {} / {}  {} ", format_time( position ), format_time( duration ), vol_str ) ) );
+    lines.push( Line::from( bar ) );
 
     let now_playing = Paragraph::new( lines )
         .block( Block::default().title( " Now Playing " ).borders( Borders::ALL ) );
@@ -2088,27 +2741,34 @@ fn draw_now_playing( frame: &mut Frame, app: &App, area: Rect ) {
 
 
 fn draw_visualizer( frame: &mut Frame, app: &App, area: Rect ) {
-    let vis_data = app.player.vis_data();
-
     // Use the full height of the content area for visualization
     let inner_height = area.height.saturating_sub( 2 ) as usize; // Account for borders
     let inner_width = area.width.saturating_sub( 2 ) as usize;
 
+    // One band per bar or column the style has room for
+    let bins = match app.visualizer_style {
+        VisualizerStyle::Bars => inner_width / 3,
+        VisualizerStyle::Spectrum => inner_width / 2,
+        VisualizerStyle::Waveform => inner_width,
+        VisualizerStyle::LevelMeter => 32,
+    };
+    let vis_data = app.player.vis_data( bins.max( 1 ) );
+
     let mut lines = Vec::with_capacity( inner_height );
 
-    if let Some( data ) = vis_data {
+    if let Some( spectrum ) = vis_data {
         match app.visualizer_style {
             VisualizerStyle::Bars => {
-                draw_vis_bars( &mut lines, &data, inner_height, inner_width );
+                draw_vis_bars( &mut lines, &spectrum, inner_height );
             }
             VisualizerStyle::Spectrum => {
-                draw_vis_spectrum( &mut lines, &data, inner_height, inner_width );
+                draw_vis_spectrum( &mut lines, &spectrum, inner_height );
             }
             VisualizerStyle::Waveform => {
-                draw_vis_waveform( &mut lines, &data, inner_height, inner_width );
+                draw_vis_waveform( &mut lines, &spectrum.levels, inner_height, inner_width );
             }
             VisualizerStyle::LevelMeter => {
-                draw_vis_level_meter( &mut lines, &data, inner_height, inner_width );
+                draw_vis_level_meter( &mut lines, &spectrum.levels, inner_height, inner_width );
             }
         }
     } else {
@@ -2133,30 +2793,31 @@ fn draw_visualizer( frame: &mut Frame, app: &App, area: Rect ) {
 }
 
 
-fn draw_vis_bars( lines: &mut Vec<Line<'static>>, data: &[f32; 32], height: usize, width: usize ) {
+/// Returns the character drawn for a bar of `amp` (0.0 to 1.0) in the row spanning
+/// `row / rows` to `( row + 1 ) / rows`, with its held peak marked above it.
+fn vis_cell( amp: f32, peak: f32, row: usize, rows: usize ) -> char {
     let vis_chars = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let threshold = row as f32 / rows as f32;
 
-    let bar_width = 2;
-    let max_bars = width / ( bar_width + 1 );
-    let num_bars = max_bars.min( 32 );
+    if amp > threshold {
+        let level = ((( amp - threshold ) * rows as f32 * 8.0 ) as usize ).min( 7 );
+        vis_chars[ level ]
+    } else if peak > threshold && peak <= ( row + 1 ) as f32 / rows as f32 && peak > 0.0 {
+        '▔'
+    } else {
+        ' '
+    }
+}
 
+
+fn draw_vis_bars( lines: &mut Vec<Line<'static>>, spectrum: &Spectrum, height: usize ) {
     for row in ( 0..height ).rev() {
-        let threshold = ( row as f32 + 0.5 ) / height as f32;
         let mut line_content = String::new();
 
-        for bar_idx in 0..num_bars {
-            let data_idx = ( bar_idx * 32 ) / num_bars;
-            let amp = data[ data_idx.min( 31 ) ];
-            let scaled_amp = ( amp * 4.0 ).min( 1.0 );
-
-            if scaled_amp >= threshold {
-                let level = ((( scaled_amp - threshold ) * height as f32 * 8.0 ) as usize ).min( 7 );
-                let ch = vis_chars[ level ];
-                line_content.push( ch );
-                line_content.push( ch );
-            } else {
-                line_content.push_str( "  " );
-            }
+        for ( amp, peak ) in spectrum.levels.iter().zip( &spectrum.peaks ) {
+            let ch = vis_cell( *amp, *peak, row, height );
+            line_content.push( ch );
+            line_content.push( ch );
             line_content.push( ' ' );
         }
 
@@ -2165,12 +2826,7 @@ fn draw_vis_bars( lines: &mut Vec<Line<'static>>, data: &[f32; 32], height: usiz
 }
 
 
-fn draw_vis_spectrum( lines: &mut Vec<Line<'static>>, data: &[f32; 32], height: usize, width: usize ) {
-    let vis_chars = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
-
-    let bar_width = 1;
-    let max_bars = width / ( bar_width + 1 );
-    let num_bars = max_bars.min( 32 );
+fn draw_vis_spectrum( lines: &mut Vec<Line<'static>>, spectrum: &Spectrum, height: usize ) {
     let half_height = height / 2;
 
     // Draw mirrored spectrum (top half mirrors bottom half)
@@ -2181,22 +2837,11 @@ fn draw_vis_spectrum( lines: &mut Vec<Line<'static>>, data: &[f32; 32], height:
         } else {
             row - half_height
         };
-        let threshold = ( row_in_half as f32 + 0.5 ) / half_height as f32;
 
         let mut line_content = String::new();
 
-        for bar_idx in 0..num_bars {
-            let data_idx = ( bar_idx * 32 ) / num_bars;
-            let amp = data[ data_idx.min( 31 ) ];
-            let scaled_amp = ( amp * 4.0 ).min( 1.0 );
-
-            if scaled_amp >= threshold {
-                let level = ((( scaled_amp - threshold ) * half_height as f32 * 8.0 ) as usize ).min( 7 );
-                let ch = vis_chars[ level ];
-                line_content.push( ch );
-            } else {
-                line_content.push( ' ' );
-            }
+        for ( amp, peak ) in spectrum.levels.iter().zip( &spectrum.peaks ) {
+            line_content.push( vis_cell( *amp, *peak, row_in_half, half_height.max( 1 ) ) );
             line_content.push( ' ' );
         }
 
@@ -2206,18 +2851,15 @@ fn draw_vis_spectrum( lines: &mut Vec<Line<'static>>, data: &[f32; 32], height:
 }
 
 
-fn draw_vis_waveform( lines: &mut Vec<Line<'static>>, data: &[f32; 32], height: usize, width: usize ) {
+fn draw_vis_waveform( lines: &mut Vec<Line<'static>>, data: &[f32], height: usize, width: usize ) {
     let center_row = height / 2;
 
     // Build the waveform grid
     let mut grid: Vec<Vec<char>> = vec![vec![' '; width]; height];
 
-    for x in 0..width {
-        let data_idx = ( x * 32 ) / width;
-        let amp = data[ data_idx.min( 31 ) ];
-
+    for ( x, amp ) in data.iter().enumerate().take( width ) {
         // Convert amplitude to y offset from center
-        let y_offset = ( amp * 3.0 * center_row as f32 ) as isize;
+        let y_offset = ( amp * center_row as f32 ) as isize;
         let y = ( center_row as isize - y_offset ).clamp( 0, ( height - 1 ) as isize ) as usize;
 
         grid[ y ][ x ] = '●';
@@ -2247,16 +2889,18 @@ fn draw_vis_waveform( lines: &mut Vec<Line<'static>>, data: &[f32; 32], height:
 }
 
 
-fn draw_vis_level_meter( lines: &mut Vec<Line<'static>>, data: &[f32; 32], height: usize, width: usize ) {
-    // Calculate average amplitude for left and right channels (simple stereo simulation)
-    let left_amp: f32 = data[ 0..16 ].iter().sum::<f32>() / 16.0;
-    let right_amp: f32 = data[ 16..32 ].iter().sum::<f32>() / 16.0;
-    let total_amp: f32 = data.iter().sum::<f32>() / 32.0;
+fn draw_vis_level_meter( lines: &mut Vec<Line<'static>>, data: &[f32], height: usize, width: usize ) {
+    // Average the lower and upper halves of the spectrum (simple stereo simulation)
+    let average = |bands: &[f32]| bands.iter().sum::<f32>() / bands.len().max( 1 ) as f32;
+    let ( low, high ) = data.split_at( data.len() / 2 );
+    let left_amp = average( low );
+    let right_amp = average( high );
+    let total_amp = average( data );
 
     let meter_width = width.saturating_sub( 10 );
-    let left_filled = (( left_amp * 4.0 ).min( 1.0 ) * meter_width as f32 ) as usize;
-    let right_filled = (( right_amp * 4.0 ).min( 1.0 ) * meter_width as f32 ) as usize;
-    let total_filled = (( total_amp * 4.0 ).min( 1.0 ) * meter_width as f32 ) as usize;
+    let left_filled = ( left_amp.min( 1.0 ) * meter_width as f32 ) as usize;
+    let right_filled = ( right_amp.min( 1.0 ) * meter_width as f32 ) as usize;
+    let total_filled = ( total_amp.min( 1.0 ) * meter_width as f32 ) as usize;
 
     // Create meter characters
     let create_meter = |filled: usize, total: usize| -> String {
@@ -2301,21 +2945,207 @@ fn draw_vis_level_meter( lines: &mut Vec<Line<'static>>, data: &[f32; 32], heigh
 }
 
 
+fn draw_equalizer( frame: &mut Frame, app: &App, area: Rect ) {
+    let eq = &app.settings.equalizer;
+    const COLUMN_WIDTH: usize = 7;
+
+    // Leave room for the band details and the gain and frequency labels
+    let inner_height = area.height.saturating_sub( 2 ) as usize;
+    let slider_height = inner_height.saturating_sub( 4 ).max( 3 );
+    let step = 2.0 * MAX_GAIN_DB / slider_height as f32;
+
+    let band_style = |idx: usize| {
+        if idx == app.eq_selected {
+            Style::default().fg( Color::Yellow ).bold()
+        } else if eq.enabled {
+            Style::default().fg( Color::Cyan )
+        } else {
+            Style::default().fg( Color::DarkGray )
+        }
+    };
+
+    let mut lines = Vec::with_capacity( inner_height );
+
+    match eq.bands.get( app.eq_selected ) {
+        Some( band ) => lines.push( Line::from( Span::styled(
+            format!(
+                "{} Hz {}  {:+.1} dB  Q {:.2}",
+                band.frequency, band.kind.name(), band.gain_db, band.q
+            ),
+            Style::default().fg( Color::Gray ),
+        ))),
+        None => lines.push( Line::from( Span::styled( "No bands configured", Style::default().fg( Color::DarkGray ).italic() ) ) ),
+    }
+    lines.push( Line::from( "" ) );
+
+    // Sliders, from +MAX_GAIN_DB at the top to -MAX_GAIN_DB at the bottom
+    for row in 0..slider_height {
+        let level = MAX_GAIN_DB - step * ( row as f32 + 0.5 );
+        let spans: Vec<Span> = eq.bands.iter().enumerate().map( |( idx, band )| {
+            let filled = ( level > 0.0 && level <= band.gain_db + step / 2.0 )
+                || ( level < 0.0 && level >= band.gain_db - step / 2.0 );
+            let cell = if level.abs() < step / 2.0 {
+                "──┼──"
+            } else if filled {
+                " ███ "
+            } else {
+                "  │  "
+            };
+            Span::styled( format!( "{:^width$}", cell, width = COLUMN_WIDTH ), band_style( idx ) )
+        }).collect();
+        lines.push( Line::from( spans ) );
+    }
+
+    let gains: Vec<Span> = eq.bands.iter().enumerate().map( |( idx, band )| {
+        Span::styled( format!( "{:^width$}", format!( "{:+.0}", band.gain_db ), width = COLUMN_WIDTH ), band_style( idx ) )
+    }).collect();
+    lines.push( Line::from( gains ) );
+
+    let frequencies: Vec<Span> = eq.bands.iter().enumerate().map( |( idx, band )| {
+        Span::styled( format!( "{:^width$}", format_frequency( band.frequency ), width = COLUMN_WIDTH ), band_style( idx ) )
+    }).collect();
+    lines.push( Line::from( frequencies ) );
+
+    let state = if eq.enabled {
+        format!( "on ({})", app.current_eq_preset().unwrap_or_else( || "custom".to_string() ) )
+    } else {
+        "off".to_string()
+    };
+    let title = format!( " Equalizer: {} (/eq <preset>, Esc to close) ", state );
+    let equalizer = Paragraph::new( lines )
+        .block( Block::default()
+            .title( title )
+            .borders( Borders::ALL )
+        )
+        .alignment( Alignment::Center );
+
+    frame.render_widget( equalizer, area );
+}
+
+
+fn draw_bookmarks( frame: &mut Frame, app: &mut App, area: Rect ) {
+    let bookmarks = app.player.bookmarks();
+    // Resume points come and go as tracks play
+    app.bookmarks_selected = app.bookmarks_selected.min( bookmarks.len().saturating_sub( 1 ) );
+
+    let items: Vec<ListItem> = if bookmarks.is_empty() {
+        vec![ ListItem::new( "  No bookmarks yet - /bookmark add [name] saves the current position" )
+            .style( Style::default().fg( Color::DarkGray ).italic() ) ]
+    } else {
+        bookmarks.iter().enumerate().map( |( idx, bookmark )| {
+            let track = match cue::parse_track_path( &bookmark.path ) {
+                Some( _ ) => cue_label( &mut app.cue_labels, &bookmark.path ).to_string(),
+                None => bookmark.path
+                    .file_name()
+                    .map_or( "Unknown".to_string(), |n| n.to_string_lossy().into_owned() ),
+            };
+            let ( name, name_style ) = match bookmark.name {
+                Some( ref name ) => ( name.as_str(), Style::default().fg( Color::White ) ),
+                None => ( "Resume point", Style::default().fg( Color::Cyan ).italic() ),
+            };
+            let selected = idx == app.bookmarks_selected;
+            let highlight = |style: Style| if selected { Style::default().fg( Color::Yellow ).bold() } else { style };
+
+            ListItem::new( Line::from( vec![
+                Span::styled( format!( " {:>8}  ", format_time( bookmark.position ) ), highlight( Style::default().fg( Color::Gray ) ) ),
+                Span::styled( name.to_string(), highlight( name_style ) ),
+                Span::styled( format!( "  {}", track ), highlight( Style::default().fg( Color::DarkGray ) ) ),
+            ]))
+        }).collect()
+    };
+
+    let list = List::new( items )
+        .block(
+            Block::default()
+                .title( format!( " Bookmarks ({}) ", bookmarks.len() ) )
+                .borders( Borders::ALL )
+                .border_style( Style::default().fg( Color::Cyan ) )
+        );
+
+    // Keeps the selection in view
+    let mut state = ListState::default();
+    state.select( Some( app.bookmarks_selected ) );
+    frame.render_stateful_widget( list, area, &mut state );
+}
+
+
+/// Formats a track position as M:SS.
+fn format_time( d: Duration ) -> String {
+    let secs = d.as_secs();
+    format!( "{}:{:02}", secs / 60, secs % 60 )
+}
+
+
+/// Formats a track or disc number with the total when known, e.g. "3/12".
+fn number_of( number: u32, total: Option<u32> ) -> String {
+    match total {
+        Some( total ) => format!( "{}/{}", number, total ),
+        None => number.to_string(),
+    }
+}
+
+
+/// Describes an audio stream, e.g. `AAC, eng, 48000 Hz, 2 ch`.
+fn stream_label( stream: &AudioStream ) -> String {
+    let mut parts = vec![ stream.codec.clone() ];
+    parts.extend( stream.language.clone() );
+    parts.extend( stream.sample_rate.map( |rate| format!( "{} Hz", rate ) ) );
+    parts.extend( stream.channels.map( |channels| format!( "{} ch", channels ) ) );
+    parts.join( ", " )
+}
+
+
+/// Returns a chapter's title, or "Chapter n" for untitled ones.
+fn chapter_title( chapter: &Chapter, index: usize ) -> String {
+    chapter.title.clone().unwrap_or_else( || format!( "Chapter {}", index + 1 ) )
+}
+
+
+/// Formats a volume in dB, e.g. "0 dB" or "-7.5 dB".
+fn format_volume( db: f32 ) -> String {
+    if db.abs() < 0.05 {
+        "0 dB".to_string()
+    } else {
+        format!( "{:+.1} dB", db )
+    }
+}
+
+
+/// Formats a band frequency compactly, e.g. "125" or "2k".
+fn format_frequency( frequency: f32 ) -> String {
+    if frequency >= 1000.0 {
+        format!( "{}k", frequency / 1000.0 )
+    } else {
+        format!( "{}", frequency )
+    }
+}
+
+
 fn draw_settings( frame: &mut Frame, app: &App, area: Rect ) {
-    let settings_items = vec![
-        ( "Discord Rich Presence", app.settings.discord_enabled ),
-        ( "System Media Controls (SMTC)", app.settings.smtc_enabled ),
+    if let Some( ref devices ) = app.device_list {
+        draw_device_picker( frame, app, devices, area );
+        return;
+    }
+
+    let checkbox = |enabled: bool| if enabled { "[x]" } else { "[ ]" };
+    let device = app.settings.output_device.as_ref()
+        .map_or( "System default".to_string(), |d| format!( "{} ({})", d.name, d.host ) );
+    let settings_items = [
+        format!( "{} Discord Rich Presence", checkbox( app.settings.discord_enabled ) ),
+        format!( "{} System Media Controls (SMTC)", checkbox( app.settings.smtc_enabled ) ),
+        format!( "    Output device: {}", device ),
+        format!( "{} Normalize surround downmix", checkbox( app.settings.normalize_downmix ) ),
+        format!( "    Resampler: {} (applies from the next track)", app.settings.resample_quality.name() ),
     ];
 
-    let items: Vec<ListItem> = settings_items.iter().enumerate().map( |( idx, ( name, enabled ) )| {
-        let checkbox = if *enabled { "[x]" } else { "[ ]" };
+    let items: Vec<ListItem> = settings_items.iter().enumerate().map( |( idx, label )| {
         let style = if idx == app.settings_selected {
             Style::default().fg( Color::Yellow ).bold()
         } else {
             Style::default().fg( Color::White )
         };
 
-        ListItem::new( format!( " {} {}", checkbox, name ) ).style( style )
+        ListItem::new( format!( " {}", label ) ).style( style )
     }).collect();
 
     let list = List::new( items )
@@ -2331,6 +3161,49 @@ fn draw_settings( frame: &mut Frame, app: &App, area: Rect ) {
 }
 
 
+fn draw_device_picker( frame: &mut Frame, app: &App, devices: &[OutputDeviceInfo], area: Rect ) {
+    let mut entries = vec![ ( "System default".to_string(), String::new() ) ];
+    entries.extend( devices.iter().map( |device| {
+        let default = if device.is_default { " [default]" } else { "" };
+        let configs: Vec<String> = device.configs.iter().map( |c| {
+            if c.min_sample_rate == c.max_sample_rate {
+                format!( "{}ch {} Hz {}", c.channels, c.min_sample_rate, c.sample_format )
+            } else {
+                format!( "{}ch {}-{} Hz {}", c.channels, c.min_sample_rate, c.max_sample_rate, c.sample_format )
+            }
+        }).collect();
+        ( format!( "{} ({}){}", device.id.name, device.id.host, default ), configs.join( ", " ) )
+    }));
+
+    let items: Vec<ListItem> = entries.into_iter().enumerate().map( |( idx, ( name, configs ) )| {
+        let style = if idx == app.device_selected {
+            Style::default().fg( Color::Yellow ).bold()
+        } else {
+            Style::default().fg( Color::White )
+        };
+
+        let mut lines = vec![ Line::from( Span::styled( format!( " {}", name ), style ) ) ];
+        if !configs.is_empty() {
+            lines.push( Line::from( Span::styled( format!( "   {}", configs ), Style::default().fg( Color::DarkGray ) ) ) );
+        }
+        ListItem::new( lines )
+    }).collect();
+
+    let mut state = ListState::default();
+    state.select( Some( app.device_selected ) );
+
+    let list = List::new( items )
+        .block(
+            Block::default()
+                .title( " Output Device " )
+                .borders( Borders::ALL )
+                .border_style( Style::default().fg( Color::Cyan ) )
+        );
+
+    frame.render_stateful_widget( list, area, &mut state );
+}
+
+
 fn draw_status_bar( frame: &mut Frame, app: &App, area: Rect ) {
     let ( text, style ) = match app.input_mode {
         InputMode::Command => {
@@ -2347,9 +3220,12 @@ fn draw_status_bar( frame: &mut Frame, app: &App, area: Rect ) {
                     ViewMode::Playlist => " [/]Cmd [Tab]Views [Space]Play [e]Edit [v]Vis [i]Info [?]Help [q]Quit ",
                     ViewMode::Browser => " [/]Cmd [Tab]Views [Enter]Open [a]Add [~]Home [?]Help ",
                     ViewMode::Help => " [?]Close [Esc]Close ",
-                    ViewMode::TrackInfo => " [Tab]Views [Space]Play [←→]Skip [i/Esc]Close ",
+                    ViewMode::TrackInfo => " [Tab]Views [Space]Play [←→]Skip [a]Audio Stream [t]All Tags [i/Esc]Close ",
                     ViewMode::Visualizer => " [Tab]Views [Space]Play [←→]Skip [v]Style [Esc]Close ",
+                    ViewMode::Equalizer => " [←→]Band [↑↓]Gain [Enter]On/Off [r]Reset [Tab]Views [Esc]Close ",
+                    ViewMode::Settings if app.device_list.is_some() => " [↑↓]Navigate [Enter]Select [Esc]Back ",
                     ViewMode::Settings => " [↑↓]Navigate [Enter/Space]Toggle [Tab]Views [Esc]Close ",
+                    ViewMode::Bookmarks => " [↑↓]Navigate [Enter]Jump [d]Delete [Tab]Views [Esc]Close ",
                 };
                 ( hint.to_string(), Style::default().fg( Color::DarkGray ) )
             }
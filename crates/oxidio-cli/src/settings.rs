@@ -1,10 +1,16 @@
 //! Application settings management
 //!
-//! Handles persistent settings for features like Discord Rich Presence and SMTC.
+//! Handles persistent settings for features like Discord Rich Presence and SMTC,
+//! and the user's equalizer presets.
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{ Path, PathBuf };
 
+use oxidio_core::bookmarks::DEFAULT_RESUME_THRESHOLD;
+use oxidio_core::command::MAX_CROSSFADE_SECS;
+use oxidio_core::loudness::DEFAULT_TARGET_LUFS;
+use oxidio_core::player::{ DEFAULT_FADE, MAX_FADE };
+use oxidio_core::{ Crossfade, CrossfadeCurve, EqPreset, EqualizerSettings, OutputDeviceId, ReplayGainMode, ReplayGainSettings, ResampleQuality, ResampleSettings, SincWindow, SpectrumSettings };
 use serde::{ Deserialize, Serialize };
 
 
@@ -17,6 +23,54 @@ pub struct Settings {
 
     /// Enable System Media Transport Controls (Windows)
     pub smtc_enabled: bool,
+
+    /// Crossfade length between tracks in seconds (0 disables crossfading)
+    pub crossfade_secs: f32,
+
+    /// Gain curve used while crossfading
+    pub crossfade_curve: CrossfadeCurve,
+
+    /// Which ReplayGain values to apply
+    pub replaygain_mode: ReplayGainMode,
+
+    /// Extra gain in dB added to tagged tracks
+    pub replaygain_preamp_db: f32,
+
+    /// Use the tagged peak to keep ReplayGain from clipping
+    pub replaygain_prevent_clipping: bool,
+
+    /// Loudness in LUFS that untagged files are normalized to once measured
+    pub loudness_target_lufs: f32,
+
+    /// Scale surround downmixes down so they can't clip
+    pub normalize_downmix: bool,
+
+    /// Quality used to convert tracks to the output sample rate
+    pub resample_quality: ResampleQuality,
+
+    /// Window of the sinc resampling filter
+    pub resample_window: SincWindow,
+
+    /// Sinc filter cutoff relative to Nyquist (None picks one to suit the window)
+    pub resample_cutoff: Option<f32>,
+
+    /// Milliseconds of fade on pause, resume, stop, seek and volume changes (0 switches instantly)
+    pub fade_ms: u32,
+
+    /// Tracks at least this many minutes long pick up where they were left off (0 never does)
+    pub resume_after_mins: u32,
+
+    /// Equalizer on/off state and band layout
+    pub equalizer: EqualizerSettings,
+
+    /// Preferred output device (None uses the system default)
+    pub output_device: Option<OutputDeviceId>,
+
+    /// Speed in dB per second at which visualizer bars fall
+    pub visualizer_falloff_db: f32,
+
+    /// Seconds visualizer peaks are held before falling
+    pub visualizer_peak_hold_secs: f32,
 }
 
 
@@ -25,18 +79,93 @@ impl Default for Settings {
         Self {
             discord_enabled: true,
             smtc_enabled: true,
+            crossfade_secs: 0.0,
+            crossfade_curve: CrossfadeCurve::default(),
+            replaygain_mode: ReplayGainMode::default(),
+            replaygain_preamp_db: 0.0,
+            replaygain_prevent_clipping: true,
+            loudness_target_lufs: DEFAULT_TARGET_LUFS,
+            normalize_downmix: false,
+            resample_quality: ResampleQuality::default(),
+            resample_window: SincWindow::default(),
+            resample_cutoff: None,
+            fade_ms: DEFAULT_FADE.as_millis() as u32,
+            resume_after_mins: ( DEFAULT_RESUME_THRESHOLD.as_secs() / 60 ) as u32,
+            equalizer: EqualizerSettings::default(),
+            output_device: None,
+            visualizer_falloff_db: SpectrumSettings::default().falloff,
+            visualizer_peak_hold_secs: SpectrumSettings::default().peak_hold.as_secs_f32(),
         }
     }
 }
 
 
 impl Settings {
+    /// Returns the crossfade settings in the form the player expects.
+    pub fn crossfade( &self ) -> Crossfade {
+        Crossfade {
+            // NaN gets through the clamp, and means no crossfade
+            duration: std::time::Duration::try_from_secs_f32( self.crossfade_secs.clamp( 0.0, MAX_CROSSFADE_SECS ) )
+                .unwrap_or_default(),
+            curve: self.crossfade_curve,
+        }
+    }
+
+
+    /// Returns the ReplayGain settings in the form the player expects.
+    pub fn replay_gain( &self ) -> ReplayGainSettings {
+        ReplayGainSettings {
+            mode: self.replaygain_mode,
+            preamp_db: self.replaygain_preamp_db.clamp( -15.0, 15.0 ),
+            prevent_clipping: self.replaygain_prevent_clipping,
+            target_lufs: self.loudness_target_lufs.clamp( -30.0, -5.0 ),
+        }
+    }
+
+
+    /// Returns the resampler settings in the form the player expects.
+    pub fn resample( &self ) -> ResampleSettings {
+        ResampleSettings {
+            quality: self.resample_quality,
+            window: self.resample_window,
+            cutoff: self.resample_cutoff,
+        }
+    }
+
+
+    /// Returns the length of the fades on pause, resume, stop, seek and volume changes.
+    pub fn fade( &self ) -> std::time::Duration {
+        std::time::Duration::from_millis( self.fade_ms as u64 ).min( MAX_FADE )
+    }
+
+
+    /// Returns how long a track must be to resume where it was left off, if at all.
+    pub fn resume_threshold( &self ) -> Option<std::time::Duration> {
+        ( self.resume_after_mins > 0 ).then( || std::time::Duration::from_secs( self.resume_after_mins as u64 * 60 ) )
+    }
+
+
+    /// Returns the visualizer settings in the form the player expects.
+    pub fn spectrum( &self ) -> SpectrumSettings {
+        SpectrumSettings {
+            falloff: self.visualizer_falloff_db.max( 0.0 ),
+            peak_hold: std::time::Duration::from_secs_f32( self.visualizer_peak_hold_secs.clamp( 0.0, 10.0 ) ),
+        }
+    }
+
+
     /// Returns the path to the settings file.
     fn settings_path() -> Option<PathBuf> {
         dirs::config_dir().map( |p| p.join( "oxidio" ).join( "settings.json" ) )
     }
 
 
+    /// Returns the path to the user equalizer presets, next to the settings file.
+    fn eq_presets_path() -> Option<PathBuf> {
+        Self::settings_path().map( |p| p.with_file_name( "eq_presets.json" ) )
+    }
+
+
     /// Loads settings from disk, or returns defaults if not found.
     pub fn load() -> Self {
         let path = match Self::settings_path() {
@@ -62,30 +191,62 @@ impl Settings {
 
     /// Saves settings to disk.
     pub fn save( &self ) {
-        let path = match Self::settings_path() {
-            Some( p ) => p,
-            None => return,
+        if let Some( path ) = Self::settings_path() {
+            write_json( &path, self );
+        }
+    }
+
+
+    /// Loads the user's equalizer presets, or an empty list if there are none.
+    pub fn load_eq_presets() -> Vec<EqPreset> {
+        let path = match Self::eq_presets_path() {
+            Some( p ) if p.exists() => p,
+            _ => return Vec::new(),
         };
 
-        // Create parent directory if needed
-        if let Some( parent ) = path.parent() {
-            if !parent.exists() {
-                if let Err( e ) = fs::create_dir_all( parent ) {
-                    tracing::warn!( "Failed to create settings directory: {}", e );
-                    return;
-                }
+        match fs::read_to_string( &path ) {
+            Ok( contents ) => {
+                serde_json::from_str( &contents ).unwrap_or_else( |e| {
+                    tracing::warn!( "Failed to parse equalizer presets: {}", e );
+                    Vec::new()
+                })
+            }
+            Err( e ) => {
+                tracing::warn!( "Failed to read equalizer presets: {}", e );
+                Vec::new()
             }
         }
+    }
 
-        match serde_json::to_string_pretty( self ) {
-            Ok( json ) => {
-                if let Err( e ) = fs::write( &path, json ) {
-                    tracing::warn!( "Failed to save settings: {}", e );
-                }
+
+    /// Saves the user's equalizer presets to disk.
+    pub fn save_eq_presets( presets: &[EqPreset] ) {
+        if let Some( path ) = Self::eq_presets_path() {
+            write_json( &path, &presets );
+        }
+    }
+}
+
+
+/// Writes `value` as pretty JSON, creating the parent directory if needed.
+fn write_json<T: Serialize>( path: &Path, value: &T ) {
+    if let Some( parent ) = path.parent() {
+        if !parent.exists() {
+            if let Err( e ) = fs::create_dir_all( parent ) {
+                tracing::warn!( "Failed to create settings directory: {}", e );
+                return;
             }
-            Err( e ) => {
-                tracing::warn!( "Failed to serialize settings: {}", e );
+        }
+    }
+
+    match serde_json::to_string_pretty( value ) {
+        Ok( json ) => {
+            if let Err( e ) = fs::write( path, json ) {
+                tracing::warn!( "Failed to save {}: {}", path.display(), e );
             }
         }
+        Err( e ) => {
+            tracing::warn!( "Failed to serialize {}: {}", path.display(), e );
+        }
     }
 }
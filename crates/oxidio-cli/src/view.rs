@@ -64,8 +64,14 @@ pub enum ViewMode {
     /// Large visualizer - full screen audio visualization.
     Visualizer,
 
+    /// Equalizer - per-band gain sliders and presets.
+    Equalizer,
+
     /// Settings view - configure app options.
     Settings,
+
+    /// Bookmarks - saved positions and where long tracks were left off.
+    Bookmarks,
 }
 
 
@@ -76,8 +82,10 @@ impl ViewMode {
             ViewMode::Playlist => ViewMode::Browser,
             ViewMode::Browser => ViewMode::TrackInfo,
             ViewMode::TrackInfo => ViewMode::Visualizer,
-            ViewMode::Visualizer => ViewMode::Settings,
-            ViewMode::Settings => ViewMode::Playlist,
+            ViewMode::Visualizer => ViewMode::Equalizer,
+            ViewMode::Equalizer => ViewMode::Settings,
+            ViewMode::Settings => ViewMode::Bookmarks,
+            ViewMode::Bookmarks => ViewMode::Playlist,
             ViewMode::Help => ViewMode::Help, // Help stays on Help until dismissed
         }
     }
@@ -86,11 +94,13 @@ impl ViewMode {
     /// Returns the previous view in tab order (excluding Help overlay).
     pub fn prev_tab( self ) -> Self {
         match self {
-            ViewMode::Playlist => ViewMode::Settings,
+            ViewMode::Playlist => ViewMode::Bookmarks,
             ViewMode::Browser => ViewMode::Playlist,
             ViewMode::TrackInfo => ViewMode::Browser,
             ViewMode::Visualizer => ViewMode::TrackInfo,
-            ViewMode::Settings => ViewMode::Visualizer,
+            ViewMode::Equalizer => ViewMode::Visualizer,
+            ViewMode::Settings => ViewMode::Equalizer,
+            ViewMode::Bookmarks => ViewMode::Settings,
             ViewMode::Help => ViewMode::Help, // Help stays on Help until dismissed
         }
     }
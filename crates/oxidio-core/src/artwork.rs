@@ -0,0 +1,261 @@
+//! Cover art
+//!
+//! Images embedded in a file's tags (ID3 APIC, FLAC PICTURE, MP4 covr), with
+//! images lying next to the file (cover.jpg, folder.png, ...) as the fallback.
+
+use std::fs;
+use std::path::{ Path, PathBuf };
+use std::sync::Arc;
+
+use symphonia::core::meta::{ StandardVisualKey, Visual };
+
+
+/// Folder images with these names (case-insensitive) are taken to be the front cover.
+const COVER_NAMES: &[&str] = &[ "cover", "folder", "album", "front", "art", "albumart", "album_art" ];
+
+/// Image files considered when looking for folder art.
+const IMAGE_EXTENSIONS: &[&str] = &[ "jpg", "jpeg", "png", "bmp", "gif", "webp" ];
+
+
+/// What an image shows, as far as the file says.
+#[derive( Debug, Clone, Copy, PartialEq, Eq )]
+pub enum PictureType {
+    FrontCover,
+    BackCover,
+    Leaflet,
+    /// The disc or tape itself
+    Media,
+    /// The artist, band, conductor or composer
+    Artist,
+    Illustration,
+    Other,
+}
+
+
+impl PictureType {
+    /// Returns the display name of the picture type.
+    pub fn name( &self ) -> &'static str {
+        match self {
+            PictureType::FrontCover => "front cover",
+            PictureType::BackCover => "back cover",
+            PictureType::Leaflet => "leaflet",
+            PictureType::Media => "media",
+            PictureType::Artist => "artist",
+            PictureType::Illustration => "illustration",
+            PictureType::Other => "other",
+        }
+    }
+}
+
+
+impl From<StandardVisualKey> for PictureType {
+    fn from( key: StandardVisualKey ) -> Self {
+        match key {
+            StandardVisualKey::FrontCover => PictureType::FrontCover,
+            StandardVisualKey::BackCover => PictureType::BackCover,
+            StandardVisualKey::Leaflet => PictureType::Leaflet,
+            StandardVisualKey::Media => PictureType::Media,
+            StandardVisualKey::LeadArtistPerformerSoloist
+            | StandardVisualKey::ArtistPerformer
+            | StandardVisualKey::Conductor
+            | StandardVisualKey::BandOrchestra
+            | StandardVisualKey::Composer
+            | StandardVisualKey::Lyricist => PictureType::Artist,
+            StandardVisualKey::Illustration => PictureType::Illustration,
+            _ => PictureType::Other,
+        }
+    }
+}
+
+
+/// An image belonging to a track.
+#[derive( Debug, Clone, PartialEq )]
+pub struct Artwork {
+    /// MIME type, e.g. "image/jpeg"
+    pub mime_type: String,
+    pub picture_type: PictureType,
+    /// The encoded image, shared so copies of a track's metadata stay cheap
+    pub data: Arc<[u8]>,
+    /// The image file, for folder art (None = embedded in the track)
+    pub path: Option<PathBuf>,
+}
+
+
+impl Artwork {
+    /// Converts an image Symphonia found in the tags, or None if it's empty or only a link.
+    pub( crate ) fn from_visual( visual: &Visual ) -> Option<Self> {
+        if visual.data.is_empty() {
+            return None;
+        }
+
+        // ID3v2.2 gives a format ("JPG") rather than a MIME type, and MP4 may give none
+        let mime_type = match sniff_mime_type( &visual.data ) {
+            Some( sniffed ) => sniffed.to_string(),
+            None if visual.media_type.contains( '/' ) => visual.media_type.to_lowercase(),
+            None => return None,
+        };
+
+        Some( Self {
+            mime_type,
+            picture_type: visual.usage.map_or( PictureType::Other, PictureType::from ),
+            data: Arc::from( &visual.data[ .. ] ),
+            path: None,
+        })
+    }
+
+
+    /// Returns true if the image came from the track's folder rather than the file.
+    pub fn is_embedded( &self ) -> bool {
+        self.path.is_none()
+    }
+
+
+    /// Returns the usual file extension for the image's format, e.g. "jpg".
+    pub fn extension( &self ) -> &'static str {
+        match self.mime_type.as_str() {
+            "image/png" => "png",
+            "image/gif" => "gif",
+            "image/bmp" => "bmp",
+            "image/webp" => "webp",
+            _ => "jpg",
+        }
+    }
+}
+
+
+/// Picks the image to show for a track: the front cover if there is one, else the first.
+pub fn cover( artwork: &[Artwork] ) -> Option<&Artwork> {
+    artwork.iter()
+        .find( |a| a.picture_type == PictureType::FrontCover )
+        .or( artwork.first() )
+}
+
+
+/// Looks for cover art in the folder of `track`.
+///
+/// Images named like a cover (cover.jpg, Folder.png, ...) win; otherwise any image in
+/// the folder will do. Ties go to the first by file name (ignoring case), so the pick
+/// doesn't depend on directory order.
+pub fn folder_image( track: &Path ) -> Option<Artwork> {
+    let parent = track.parent()?;
+    let entries = match fs::read_dir( parent ) {
+        Ok( entries ) => entries,
+        Err( e ) => {
+            tracing::debug!( "Failed to read directory {:?}: {}", parent, e );
+            return None;
+        }
+    };
+
+    // (lowercase stem, path) of each image
+    let mut images: Vec<( String, PathBuf )> = entries.flatten()
+        .map( |e| e.path() )
+        .filter_map( |path| {
+            let stem = path.file_stem()?.to_str()?.to_lowercase();
+            let ext = path.extension()?.to_str()?.to_lowercase();
+            IMAGE_EXTENSIONS.contains( &ext.as_str() ).then_some(( stem, path ))
+        })
+        .collect();
+    images.sort_by_cached_key( |( _, path )| path.file_name().map( |n| n.to_string_lossy().to_lowercase() ) );
+
+    let found = images.iter().position( |( stem, _ )| COVER_NAMES.contains( &stem.as_str() ) );
+    let picture_type = if found.is_some() { PictureType::FrontCover } else { PictureType::Other };
+    let ( _, path ) = images.into_iter().nth( found.unwrap_or( 0 ) )?;
+    let data = match fs::read( &path ) {
+        Ok( data ) => data,
+        Err( e ) => {
+            tracing::warn!( "Failed to read album art {:?}: {}", path, e );
+            return None;
+        }
+    };
+    tracing::debug!( "Found album art: {:?}", path );
+
+    Some( Artwork {
+        mime_type: sniff_mime_type( &data ).unwrap_or( "image/jpeg" ).to_string(),
+        picture_type,
+        data: data.into(),
+        path: Some( path ),
+    })
+}
+
+
+/// Recognizes the common image formats from their first bytes.
+fn sniff_mime_type( data: &[u8] ) -> Option<&'static str> {
+    match data {
+        [ 0xFF, 0xD8, 0xFF, .. ] => Some( "image/jpeg" ),
+        [ 0x89, b'P', b'N', b'G', .. ] => Some( "image/png" ),
+        [ b'G', b'I', b'F', b'8', .. ] => Some( "image/gif" ),
+        [ b'B', b'M', .. ] => Some( "image/bmp" ),
+        [ b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', .. ] => Some( "image/webp" ),
+        _ => None,
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+
+    fn visual( media_type: &str, usage: Option<StandardVisualKey>, data: &[u8] ) -> Visual {
+        Visual {
+            media_type: media_type.into(),
+            dimensions: None,
+            bits_per_pixel: None,
+            color_mode: None,
+            usage,
+            tags: Vec::new(),
+            data: data.into(),
+        }
+    }
+
+
+    #[test]
+    fn test_embedded_images_are_typed_and_sniffed() {
+        // ID3v2.2 style format names and missing MIME types are recognized from the data
+        let art = Artwork::from_visual( &visual( "PNG", Some( StandardVisualKey::BackCover ), PNG ) ).unwrap();
+        assert_eq!( art.mime_type, "image/png" );
+        assert_eq!( art.picture_type, PictureType::BackCover );
+        assert!( art.is_embedded() );
+
+        let front = Artwork::from_visual( &visual( "image/jpeg", Some( StandardVisualKey::FrontCover ), &[ 0xFF, 0xD8, 0xFF, 0xE0 ] ) ).unwrap();
+        assert_eq!( front.extension(), "jpg" );
+
+        // Links and empty pictures aren't images
+        assert_eq!( Artwork::from_visual( &visual( "-->", None, b"http://example.com/a.jpg" ) ), None );
+        assert_eq!( Artwork::from_visual( &visual( "image/png", None, b"" ) ), None );
+
+        assert_eq!( cover( &[ art.clone(), front.clone() ] ), Some( &front ) );
+        assert_eq!( cover( std::slice::from_ref( &art ) ), Some( &art ) );
+    }
+
+
+    #[test]
+    fn test_folder_image_prefers_cover_names() {
+        let dir = std::env::temp_dir().join( format!( "oxidio-artwork-{}", std::process::id() ) );
+        let _ = fs::remove_dir_all( &dir );
+        fs::create_dir_all( &dir ).unwrap();
+        let track = dir.join( "01.flac" );
+        assert_eq!( folder_image( &track ), None );
+
+        fs::write( dir.join( "scan.png" ), PNG ).unwrap();
+        fs::write( dir.join( "back.png" ), PNG ).unwrap();
+        let art = folder_image( &track ).unwrap();
+        assert_eq!( art.path, Some( dir.join( "back.png" ) ) );
+        assert_eq!( art.picture_type, PictureType::Other );
+
+        fs::write( dir.join( "Folder.PNG" ), PNG ).unwrap();
+        let art = folder_image( &track ).unwrap();
+        assert_eq!( art.path, Some( dir.join( "Folder.PNG" ) ) );
+        assert_eq!( art.picture_type, PictureType::FrontCover );
+        assert_eq!( art.mime_type, "image/png" );
+        assert!( !art.is_embedded() );
+
+        fs::write( dir.join( "cover.png" ), PNG ).unwrap();
+        assert_eq!( folder_image( &track ).unwrap().path, Some( dir.join( "cover.png" ) ) );
+
+        let _ = fs::remove_dir_all( &dir );
+    }
+}
@@ -0,0 +1,278 @@
+//! Resume points and bookmarks
+//!
+//! Remembers where long tracks (audiobooks, podcasts) were left off so they pick up
+//! there next time, along with positions the user bookmarked by name. Both are kept
+//! in one file so they survive restarts.
+
+use std::fs::{ self, File };
+use std::io::{ BufRead, BufReader, Write };
+use std::path::{ Path, PathBuf };
+use std::sync::Mutex;
+use std::time::Duration;
+
+
+/// Default length a track must have for its position to be remembered.
+pub const DEFAULT_RESUME_THRESHOLD: Duration = Duration::from_secs( 20 * 60 );
+
+/// Positions this close to the start or end of a track aren't worth resuming from.
+const RESUME_MARGIN: Duration = Duration::from_secs( 10 );
+
+
+/// A saved position in a track.
+#[derive( Debug, Clone, PartialEq )]
+pub struct Bookmark {
+    pub path: PathBuf,
+    /// Name given by the user, or None for the automatic resume point
+    pub name: Option<String>,
+    pub position: Duration,
+}
+
+
+impl Bookmark {
+    /// Returns true if this is where the track was left off rather than a named bookmark.
+    pub fn is_resume_point( &self ) -> bool {
+        self.name.is_none()
+    }
+}
+
+
+/// Resume points and named bookmarks, persisted across sessions.
+///
+/// Each track has at most one resume point; named bookmarks are kept in the order
+/// they were added.
+pub struct Bookmarks {
+    file: Option<PathBuf>,
+    entries: Mutex<Vec<Bookmark>>,
+}
+
+
+impl Bookmarks {
+    /// Gets the default bookmarks file path.
+    pub fn bookmarks_file() -> Option<PathBuf> {
+        dirs::data_local_dir().map( |d| d.join( "oxidio" ).join( "bookmarks" ) )
+    }
+
+
+    /// Loads bookmarks from the default location, starting empty if there are none.
+    pub fn load() -> Self {
+        Self::open( Self::bookmarks_file() )
+    }
+
+
+    /// Loads bookmarks from `file`, saving changes back to it (nowhere with None).
+    pub fn open( file: Option<PathBuf> ) -> Self {
+        let entries = file.as_deref()
+            .and_then( |f| Self::read_entries( f ).ok() )
+            .unwrap_or_default();
+
+        Self {
+            file,
+            entries: Mutex::new( entries ),
+        }
+    }
+
+
+    /// Reads lines of the form `resume<TAB>secs<TAB>path` or `mark<TAB>secs<TAB>name<TAB>path`.
+    fn read_entries( file: &Path ) -> std::io::Result<Vec<Bookmark>> {
+        let reader = BufReader::new( File::open( file )? );
+        let mut entries = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let mut fields = line.split( '\t' );
+            let parsed = ( || {
+                let kind = fields.next()?;
+                let position = Duration::try_from_secs_f64( fields.next()?.parse().ok()? ).ok()?;
+                let name = match kind {
+                    "resume" => None,
+                    "mark" => Some( fields.next()?.to_string() ),
+                    _ => return None,
+                };
+                let path = PathBuf::from( fields.next()? );
+                Some( Bookmark { path, name, position } )
+            })();
+
+            if let Some( bookmark ) = parsed {
+                entries.push( bookmark );
+            }
+        }
+
+        Ok( entries )
+    }
+
+
+    /// Gets where `path` was left off, if it's remembered.
+    pub fn resume_point( &self, path: &Path ) -> Option<Duration> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().find( |b| b.is_resume_point() && b.path == path ).map( |b| b.position )
+    }
+
+
+    /// Records that playback of `path` got to `position`.
+    ///
+    /// Only tracks at least `threshold` long (None = never) are remembered. Stopping
+    /// near the start or the end forgets the track's resume point instead, so it
+    /// plays from the top next time.
+    pub fn remember( &self, path: &Path, position: Duration, duration: Option<Duration>, threshold: Option<Duration> ) {
+        let ( Some( duration ), Some( threshold ) ) = ( duration, threshold ) else {
+            return;
+        };
+        if duration < threshold {
+            return;
+        }
+
+        let worth_resuming = position >= RESUME_MARGIN && position + RESUME_MARGIN < duration;
+        let mut entries = self.entries.lock().unwrap();
+        let existing = entries.iter().position( |b| b.is_resume_point() && b.path == path );
+        match ( existing, worth_resuming ) {
+            ( Some( i ), true ) => {
+                // Only worth a write once it's moved on by a second or more
+                if entries[ i ].position.abs_diff( position ) < Duration::from_secs( 1 ) {
+                    return;
+                }
+                entries[ i ].position = position;
+            }
+            ( None, true ) => entries.push( Bookmark { path: path.to_path_buf(), name: None, position } ),
+            ( Some( i ), false ) => {
+                entries.remove( i );
+            }
+            ( None, false ) => return,
+        }
+        self.save_logged( &entries );
+    }
+
+
+    /// Adds a named bookmark.
+    pub fn add( &self, path: &Path, name: &str, position: Duration ) -> Bookmark {
+        // Tabs and line breaks would break the file format
+        let name: String = name.chars().map( |c| if c.is_control() { ' ' } else { c } ).collect();
+        let bookmark = Bookmark { path: path.to_path_buf(), name: Some( name.trim().to_string() ), position };
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.push( bookmark.clone() );
+        self.save_logged( &entries );
+        bookmark
+    }
+
+
+    /// Removes a bookmark or resume point, returning true if it was found.
+    pub fn remove( &self, bookmark: &Bookmark ) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let Some( i ) = entries.iter().position( |b| b == bookmark ) else {
+            return false;
+        };
+        entries.remove( i );
+        self.save_logged( &entries );
+        true
+    }
+
+
+    /// Lists the resume points followed by the named bookmarks.
+    pub fn list( &self ) -> Vec<Bookmark> {
+        let entries = self.entries.lock().unwrap();
+        let ( mut list, named ): ( Vec<_>, Vec<_> ) = entries.iter().cloned().partition( Bookmark::is_resume_point );
+        list.extend( named );
+        list
+    }
+
+
+    fn save_logged( &self, entries: &[Bookmark] ) {
+        if let Err( e ) = self.save( entries ) {
+            tracing::warn!( "Failed to save bookmarks: {}", e );
+        }
+    }
+
+
+    fn save( &self, entries: &[Bookmark] ) -> std::io::Result<()> {
+        let Some( ref file ) = self.file else {
+            return Ok(());
+        };
+
+        if let Some( parent ) = file.parent() {
+            fs::create_dir_all( parent )?;
+        }
+
+        let mut out = File::create( file )?;
+        for bookmark in entries {
+            let secs = bookmark.position.as_secs_f64();
+            match bookmark.name {
+                Some( ref name ) => writeln!( out, "mark\t{}\t{}\t{}", secs, name, bookmark.path.display() )?,
+                None => writeln!( out, "resume\t{}\t{}", secs, bookmark.path.display() )?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    const HOUR: Duration = Duration::from_secs( 3600 );
+
+
+    #[test]
+    fn test_resume_points_follow_threshold_and_margins() {
+        let bookmarks = Bookmarks::open( None );
+        let book = Path::new( "/books/long.m4b" );
+        let threshold = Some( DEFAULT_RESUME_THRESHOLD );
+
+        // Short tracks are never remembered
+        bookmarks.remember( Path::new( "/music/song.mp3" ), Duration::from_secs( 60 ), Some( Duration::from_secs( 200 ) ), threshold );
+        assert_eq!( bookmarks.resume_point( Path::new( "/music/song.mp3" ) ), None );
+
+        bookmarks.remember( book, Duration::from_secs( 600 ), Some( HOUR ), threshold );
+        bookmarks.remember( book, Duration::from_secs( 900 ), Some( HOUR ), threshold );
+        assert_eq!( bookmarks.resume_point( book ), Some( Duration::from_secs( 900 ) ) );
+        assert_eq!( bookmarks.list().len(), 1 );
+
+        // Finishing the track forgets where it was
+        bookmarks.remember( book, HOUR - Duration::from_secs( 2 ), Some( HOUR ), threshold );
+        assert_eq!( bookmarks.resume_point( book ), None );
+
+        // Disabled altogether
+        bookmarks.remember( book, Duration::from_secs( 600 ), Some( HOUR ), None );
+        assert_eq!( bookmarks.resume_point( book ), None );
+    }
+
+
+    #[test]
+    fn test_bookmarks_survive_reload() {
+        let file = std::env::temp_dir().join( format!( "oxidio-bookmarks-{}", std::process::id() ) );
+        let _ = fs::remove_file( &file );
+        let book = Path::new( "/books/long.m4b" );
+
+        let bookmarks = Bookmarks::open( Some( file.clone() ) );
+        let intro = bookmarks.add( book, "the\tintro", Duration::from_millis( 12_500 ) );
+        bookmarks.add( book, "chapter 2", Duration::from_secs( 1800 ) );
+        bookmarks.remember( book, Duration::from_secs( 2000 ), Some( HOUR ), Some( DEFAULT_RESUME_THRESHOLD ) );
+
+        let reloaded = Bookmarks::open( Some( file.clone() ) );
+        let list = reloaded.list();
+        assert_eq!( list.len(), 3 );
+        assert!( list[ 0 ].is_resume_point() );
+        assert_eq!( list[ 1 ], intro );
+        assert_eq!( intro.name.as_deref(), Some( "the intro" ) );
+
+        assert!( reloaded.remove( &intro ) );
+        assert_eq!( Bookmarks::open( Some( file.clone() ) ).list().len(), 2 );
+        let _ = fs::remove_file( &file );
+    }
+
+
+    #[test]
+    fn test_read_entries_skips_bad_positions() {
+        let file = std::env::temp_dir().join( format!( "oxidio-bad-bookmarks-{}", std::process::id() ) );
+        fs::write( &file, "resume\t-1\t/x\nresume\tNaN\t/y\nmark\tinf\tend\t/z\nresume\t90\t/book.m4b\n" ).unwrap();
+
+        let entries = Bookmarks::read_entries( &file ).unwrap();
+        assert_eq!( entries.len(), 1 );
+        assert_eq!( entries[ 0 ].path, Path::new( "/book.m4b" ) );
+        assert_eq!( entries[ 0 ].position, Duration::from_secs( 90 ) );
+
+        let _ = fs::remove_file( &file );
+    }
+}
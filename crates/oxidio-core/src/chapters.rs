@@ -0,0 +1,677 @@
+//! Chapter extraction
+//!
+//! Symphonia doesn't read chapters, so they're parsed here straight from the
+//! container: MP4/M4B (Nero `chpl` atoms and QuickTime chapter tracks), Matroska
+//! and ID3v2 CHAP frames.
+
+use std::fs::File;
+use std::io::{ self, BufReader, Read, Seek, SeekFrom };
+use std::path::Path;
+use std::time::Duration;
+
+
+/// ID3v2 frames larger than this are assumed to be corrupt.
+const MAX_TAG_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Matroska element IDs (with their length marker bits).
+const EBML_HEADER: u32 = 0x1A45_DFA3;
+const MKV_SEGMENT: u32 = 0x1853_8067;
+const MKV_SEEK_HEAD: u32 = 0x114D_9B74;
+const MKV_SEEK: u32 = 0x4DBB;
+const MKV_SEEK_ID: u32 = 0x53AB;
+const MKV_SEEK_POSITION: u32 = 0x53AC;
+const MKV_CLUSTER: u32 = 0x1F43_B675;
+const MKV_CHAPTERS: u32 = 0x1043_A770;
+const MKV_EDITION_ENTRY: u32 = 0x45B9;
+const MKV_CHAPTER_ATOM: u32 = 0xB6;
+const MKV_CHAPTER_TIME_START: u32 = 0x91;
+const MKV_CHAPTER_FLAG_HIDDEN: u32 = 0x98;
+const MKV_CHAPTER_DISPLAY: u32 = 0x80;
+const MKV_CHAP_STRING: u32 = 0x85;
+
+
+/// A named point in a track.
+#[derive( Debug, Clone, PartialEq )]
+pub struct Chapter {
+    pub title: Option<String>,
+    /// Where the chapter starts; it runs until the next one starts
+    pub start: Duration,
+}
+
+
+/// Reads the chapters of an audio file, in order.
+///
+/// Files without chapters, and files whose chapters can't be read, have none.
+pub fn read( path: &Path ) -> Vec<Chapter> {
+    let result = File::open( path ).and_then( |file| {
+        let mut reader = BufReader::new( file );
+        let mut magic = [ 0u8; 8 ];
+        reader.read_exact( &mut magic )?;
+        reader.rewind()?;
+
+        if magic.starts_with( b"ID3" ) {
+            read_id3( &mut reader )
+        } else if &magic[ 4.. ] == b"ftyp" {
+            read_mp4( &mut reader )
+        } else if u32::from_be_bytes( [ magic[ 0 ], magic[ 1 ], magic[ 2 ], magic[ 3 ] ] ) == EBML_HEADER {
+            read_matroska( &mut reader )
+        } else {
+            Ok( Vec::new() )
+        }
+    });
+
+    match result {
+        Ok( mut chapters ) => {
+            chapters.sort_by_key( |c| c.start );
+            chapters.dedup_by_key( |c| c.start );
+            chapters
+        }
+        Err( e ) => {
+            tracing::debug!( "No chapters read from {:?}: {}", path, e );
+            Vec::new()
+        }
+    }
+}
+
+
+fn invalid( message: &str ) -> io::Error {
+    io::Error::new( io::ErrorKind::InvalidData, message.to_string() )
+}
+
+
+fn read_bytes<R: Read>( reader: &mut R, len: u64 ) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.take( len ).read_to_end( &mut data )?;
+    if ( data.len() as u64 ) < len {
+        return Err( io::ErrorKind::UnexpectedEof.into() );
+    }
+    Ok( data )
+}
+
+
+fn be_u32( data: &[u8], at: usize ) -> Option<u32> {
+    Some( u32::from_be_bytes( data.get( at..at + 4 )?.try_into().ok()? ) )
+}
+
+
+fn be_u64( data: &[u8], at: usize ) -> Option<u64> {
+    Some( u64::from_be_bytes( data.get( at..at + 8 )?.try_into().ok()? ) )
+}
+
+
+/// Turns a time in `units` per second into a Duration.
+fn from_units( value: u64, units: u64 ) -> Duration {
+    Duration::from_secs( value / units ) + Duration::from_nanos( ( value % units ) * 1_000_000_000 / units )
+}
+
+
+/// Reads the CHAP frames of the ID3v2 tag at the start of the file.
+fn read_id3<R: Read>( reader: &mut R ) -> io::Result<Vec<Chapter>> {
+    let mut header = [ 0u8; 10 ];
+    reader.read_exact( &mut header )?;
+    let version = header[ 3 ];
+    let flags = header[ 5 ];
+    let size = syncsafe( &header[ 6..10 ] ) as u64;
+    if !( 3..=4 ).contains( &version ) || size > MAX_TAG_SIZE {
+        // ID3v2.2 has no chapters
+        return Ok( Vec::new() );
+    }
+
+    let mut tag = read_bytes( reader, size )?;
+    if version == 3 && flags & 0x80 != 0 {
+        tag = unsynchronise( &tag );
+    }
+
+    let mut at = 0;
+    if flags & 0x40 != 0 {
+        // Skip the extended header
+        let len = be_u32( &tag, 0 ).ok_or_else( || invalid( "truncated extended header" ) )?;
+        at = if version == 4 { syncsafe( &tag[ ..4 ] ) as usize } else { len as usize + 4 };
+    }
+
+    Ok( id3_frames( &tag[ at.min( tag.len() ).. ], version )
+        .filter( |( id, _ )| id == b"CHAP" )
+        .filter_map( |( _, body )| id3_chapter( body, version ) )
+        .collect() )
+}
+
+
+/// Decodes a 28-bit syncsafe integer.
+fn syncsafe( bytes: &[u8] ) -> u32 {
+    bytes.iter().fold( 0, |n, &b| ( n << 7 ) | ( b & 0x7F ) as u32 )
+}
+
+
+/// Undoes ID3 unsynchronisation (`FF 00` stands for `FF`).
+fn unsynchronise( data: &[u8] ) -> Vec<u8> {
+    let mut out = Vec::with_capacity( data.len() );
+    for ( i, &b ) in data.iter().enumerate() {
+        if !( b == 0 && i > 0 && data[ i - 1 ] == 0xFF ) {
+            out.push( b );
+        }
+    }
+    out
+}
+
+
+/// Iterates over the (ID, body) of the frames in `data`, stopping at the padding.
+fn id3_frames( data: &[u8], version: u8 ) -> impl Iterator<Item = ( [u8; 4], &[u8] )> {
+    let mut at = 0;
+    std::iter::from_fn( move || {
+        let header = data.get( at..at + 10 )?;
+        let id: [u8; 4] = header[ ..4 ].try_into().ok()?;
+        if id[ 0 ] == 0 {
+            return None;
+        }
+        let size = if version == 4 { syncsafe( &header[ 4..8 ] ) } else { be_u32( header, 4 )? } as usize;
+        let body = data.get( at + 10..at + 10 + size )?;
+        at += 10 + size;
+        Some(( id, body ))
+    })
+}
+
+
+/// Parses a CHAP frame: element ID, start and end times, byte offsets, then sub-frames.
+fn id3_chapter( body: &[u8], version: u8 ) -> Option<Chapter> {
+    let id_end = body.iter().position( |&b| b == 0 )?;
+    let start_ms = be_u32( body, id_end + 1 )?;
+    let sub_frames = body.get( id_end + 17.. ).unwrap_or( &[] );
+    let title = id3_frames( sub_frames, version )
+        .find( |( id, _ )| id == b"TIT2" )
+        .and_then( |( _, text )| id3_text( text ) );
+    Some( Chapter { title, start: Duration::from_millis( start_ms as u64 ) } )
+}
+
+
+/// Decodes a text frame body: an encoding byte followed by the text.
+fn id3_text( body: &[u8] ) -> Option<String> {
+    let ( &encoding, text ) = body.split_first()?;
+    let text = match encoding {
+        0 => text.iter().map( |&b| b as char ).collect(),
+        1 | 2 => {
+            let mut units: Vec<u16> = text.chunks_exact( 2 ).map( |c| u16::from_be_bytes( [ c[ 0 ], c[ 1 ] ] ) ).collect();
+            match units.first() {
+                Some( 0xFFFE ) => {
+                    units.remove( 0 );
+                    units.iter_mut().for_each( |u| *u = u.swap_bytes() );
+                }
+                Some( 0xFEFF ) => {
+                    units.remove( 0 );
+                }
+                _ => {}
+            }
+            String::from_utf16_lossy( &units )
+        }
+        _ => String::from_utf8_lossy( text ).into_owned(),
+    };
+    let text = text.trim_end_matches( '\0' ).to_string();
+    ( !text.is_empty() ).then_some( text )
+}
+
+
+/// Iterates over the (type, body) of the atoms in `data`.
+fn atoms( data: &[u8] ) -> impl Iterator<Item = ( [u8; 4], &[u8] )> {
+    let mut at = 0;
+    std::iter::from_fn( move || {
+        let size = be_u32( data, at )? as usize;
+        let kind: [u8; 4] = data.get( at + 4..at + 8 )?.try_into().ok()?;
+        let ( header, size ) = match size {
+            0 => ( 8, data.len() - at ),
+            1 => ( 16, be_u64( data, at + 8 )? as usize ),
+            size => ( 8, size ),
+        };
+        let body = data.get( at + header..at.checked_add( size )? )?;
+        at += size;
+        Some(( kind, body ))
+    })
+}
+
+
+/// Finds the first atom along `path`.
+fn atom<'a>( data: &'a [u8], path: &[&[u8; 4]] ) -> Option<&'a [u8]> {
+    path.iter().try_fold( data, |data, kind| atoms( data ).find( |( k, _ )| k == *kind ).map( |( _, body )| body ) )
+}
+
+
+/// Reads the chapters of an MP4 file, preferring a QuickTime chapter track to a Nero `chpl` atom.
+fn read_mp4<R: Read + Seek>( reader: &mut R ) -> io::Result<Vec<Chapter>> {
+    let moov = loop {
+        let mut header = [ 0u8; 8 ];
+        reader.read_exact( &mut header )?;
+        let size = be_u32( &header, 0 ).unwrap() as u64;
+        let ( header_len, size ) = match size {
+            0 => return Err( invalid( "no moov atom" ) ),
+            1 => {
+                let mut large = [ 0u8; 8 ];
+                reader.read_exact( &mut large )?;
+                ( 16, u64::from_be_bytes( large ) )
+            }
+            size => ( 8, size ),
+        };
+        let body_len = size.checked_sub( header_len ).ok_or_else( || invalid( "bad atom size" ) )?;
+        if &header[ 4..8 ] == b"moov" {
+            break read_bytes( reader, body_len )?;
+        }
+        reader.seek( SeekFrom::Current( body_len as i64 ) )?;
+    };
+
+    let chapters = quicktime_chapters( reader, &moov )?;
+    if !chapters.is_empty() {
+        return Ok( chapters );
+    }
+    Ok( atom( &moov, &[ b"udta", b"chpl" ] ).map( nero_chapters ).unwrap_or_default() )
+}
+
+
+/// Parses a Nero `chpl` atom: start times in 100 ns units with short titles.
+fn nero_chapters( chpl: &[u8] ) -> Vec<Chapter> {
+    // Version 1 has four reserved bytes after the version and flags
+    let mut at = if chpl.first() == Some( &1 ) { 8 } else { 4 };
+    let count = chpl.get( at ).copied().unwrap_or( 0 );
+    at += 1;
+
+    let mut chapters = Vec::new();
+    for _ in 0..count {
+        let Some( start ) = be_u64( chpl, at ) else { break };
+        let Some( &len ) = chpl.get( at + 8 ) else { break };
+        let Some( title ) = chpl.get( at + 9..at + 9 + len as usize ) else { break };
+        at += 9 + len as usize;
+
+        let title = String::from_utf8_lossy( title ).into_owned();
+        chapters.push( Chapter {
+            title: ( !title.is_empty() ).then_some( title ),
+            start: from_units( start, 10_000_000 ),
+        });
+    }
+    chapters
+}
+
+
+/// Reads the text samples of the track the audio track names in its `tref/chap` atom.
+fn quicktime_chapters<R: Read + Seek>( reader: &mut R, moov: &[u8] ) -> io::Result<Vec<Chapter>> {
+    let traks: Vec<&[u8]> = atoms( moov ).filter( |( k, _ )| k == b"trak" ).map( |( _, body )| body ).collect();
+    let chapter_ids: Vec<u32> = traks.iter()
+        .filter_map( |trak| atom( trak, &[ b"tref", b"chap" ] ) )
+        .flat_map( |chap| chap.chunks_exact( 4 ).map( |id| u32::from_be_bytes( id.try_into().unwrap() ) ) )
+        .collect();
+    let Some( trak ) = traks.iter().find( |trak| track_id( trak ).is_some_and( |id| chapter_ids.contains( &id ) ) ) else {
+        return Ok( Vec::new() );
+    };
+
+    let file_len = reader.seek( SeekFrom::End( 0 ) )?;
+    let Some(( timescale, samples )) = sample_table( trak, file_len ) else {
+        return Ok( Vec::new() );
+    };
+
+    let mut chapters = Vec::new();
+    for ( time, offset, size ) in samples {
+        reader.seek( SeekFrom::Start( offset ) )?;
+        let sample = read_bytes( reader, size as u64 )?;
+        // Each sample is a 16-bit length and the text, possibly followed by style atoms
+        let len = sample.get( ..2 ).map_or( 0, |l| u16::from_be_bytes( [ l[ 0 ], l[ 1 ] ] ) as usize );
+        let text = sample.get( 2..2 + len ).unwrap_or( &[] );
+        let title = if text.starts_with( &[ 0xFE, 0xFF ] ) || text.starts_with( &[ 0xFF, 0xFE ] ) {
+            id3_text( &[ &[ 1 ], text ].concat() )
+        } else {
+            Some( String::from_utf8_lossy( text ).into_owned() ).filter( |t| !t.is_empty() )
+        };
+        chapters.push( Chapter { title, start: from_units( time, timescale as u64 ) } );
+    }
+    Ok( chapters )
+}
+
+
+/// Reads the track ID from a `trak`'s `tkhd` atom.
+fn track_id( trak: &[u8] ) -> Option<u32> {
+    let tkhd = atom( trak, &[ b"tkhd" ] )?;
+    be_u32( tkhd, if tkhd.first() == Some( &1 ) { 20 } else { 12 } )
+}
+
+
+/// The timescale of a track and the (start time, file offset, size) of each sample.
+type SampleTable = ( u32, Vec<( u64, u64, u32 )> );
+
+
+/// Works out the start time, file offset and size of every sample of a track.
+///
+/// Sample counts are checked against the table sizes and `file_len` before anything
+/// is allocated for them, so a corrupt table is rejected rather than exhausting memory.
+fn sample_table( trak: &[u8], file_len: u64 ) -> Option<SampleTable> {
+    let mdia = atom( trak, &[ b"mdia" ] )?;
+    let mdhd = atom( mdia, &[ b"mdhd" ] )?;
+    let timescale = be_u32( mdhd, if mdhd.first() == Some( &1 ) { 20 } else { 12 } ).filter( |&t| t > 0 )?;
+    let stbl = atom( mdia, &[ b"minf", b"stbl" ] )?;
+
+    let entries = |kind: &[u8; 4], width: usize| -> Option<Vec<&[u8]>> {
+        let table = atom( stbl, &[ kind ] )?;
+        let count = be_u32( table, 4 )? as usize;
+        let data = table.get( 8..8 + count.checked_mul( width )? )?;
+        Some( data.chunks_exact( width ).collect() )
+    };
+
+    let stsz = atom( stbl, &[ b"stsz" ] )?;
+    let fixed_size = be_u32( stsz, 4 )?;
+    let count = be_u32( stsz, 8 )? as usize;
+    let sizes: Vec<u32> = match fixed_size {
+        0 => {
+            let table = stsz.get( 12..12 + count.checked_mul( 4 )? )?;
+            table.chunks_exact( 4 ).map( |size| be_u32( size, 0 ) ).collect::<Option<_>>()?
+        }
+        // The samples all have to fit in the file
+        size if ( count as u64 ).checked_mul( size as u64 )? > file_len => return None,
+        size => vec![ size; count ],
+    };
+
+    // Start time of each sample, ignoring any the sizes don't cover
+    let mut times = Vec::with_capacity( sizes.len() );
+    let mut time = 0u64;
+    for entry in entries( b"stts", 8 )? {
+        let repeat = ( be_u32( entry, 0 )? as usize ).min( sizes.len() - times.len() );
+        let delta = be_u32( entry, 4 )? as u64;
+        for _ in 0..repeat {
+            times.push( time );
+            time += delta;
+        }
+    }
+
+    let chunk_offsets: Vec<u64> = match entries( b"co64", 8 ) {
+        Some( entries ) => entries.iter().map( |e| be_u64( e, 0 ) ).collect::<Option<_>>()?,
+        None => entries( b"stco", 4 )?.iter().map( |e| be_u32( e, 0 ).map( u64::from ) ).collect::<Option<_>>()?,
+    };
+
+    // Samples are laid out back to back within each chunk
+    let stsc = entries( b"stsc", 12 )?;
+    let mut samples = Vec::with_capacity( sizes.len() );
+    let mut sample = 0;
+    for ( chunk, &chunk_offset ) in chunk_offsets.iter().enumerate() {
+        let chunk_number = chunk as u32 + 1;
+        let per_chunk = stsc.iter()
+            .take_while( |e| be_u32( e, 0 ).is_some_and( |first| first <= chunk_number ) )
+            .last()
+            .and_then( |e| be_u32( e, 4 ) )?;
+        let mut offset = chunk_offset;
+        for _ in 0..per_chunk {
+            let ( Some( &time ), Some( &size ) ) = ( times.get( sample ), sizes.get( sample ) ) else {
+                return Some(( timescale, samples ));
+            };
+            samples.push(( time, offset, size ));
+            offset += size as u64;
+            sample += 1;
+        }
+    }
+    Some(( timescale, samples ))
+}
+
+
+/// Reads an EBML variable-length integer, returning it and its length in bytes.
+///
+/// IDs keep their length marker; sizes have it stripped.
+fn read_vint<R: Read>( reader: &mut R, keep_marker: bool ) -> io::Result<( u64, u32 )> {
+    let mut first = [ 0u8; 1 ];
+    reader.read_exact( &mut first )?;
+    let len = first[ 0 ].leading_zeros() + 1;
+    if len > 8 {
+        return Err( invalid( "bad EBML integer" ) );
+    }
+    let mut value = if keep_marker { first[ 0 ] as u64 } else { ( first[ 0 ] as u64 ) & ( 0xFF >> len ) };
+    for _ in 1..len {
+        reader.read_exact( &mut first )?;
+        value = ( value << 8 ) | first[ 0 ] as u64;
+    }
+    Ok(( value, len ))
+}
+
+
+/// Reads an element header, returning its ID and size (None when unknown).
+fn read_element<R: Read>( reader: &mut R ) -> io::Result<( u32, Option<u64> )> {
+    let ( id, _ ) = read_vint( reader, true )?;
+    let ( size, len ) = read_vint( reader, false )?;
+    let unknown = size == ( 1 << ( 7 * len ) ) - 1;
+    Ok(( id as u32, ( !unknown ).then_some( size ) ))
+}
+
+
+/// Iterates over the (ID, body) of the elements in `data`.
+fn elements( data: &[u8] ) -> impl Iterator<Item = ( u32, &[u8] )> {
+    let mut at = 0;
+    std::iter::from_fn( move || {
+        let mut reader = data.get( at.. )?;
+        let before = reader.len();
+        let ( id, size ) = read_element( &mut reader ).ok()?;
+        let start = at + before - reader.len();
+        let body = data.get( start..start + size? as usize )?;
+        at = start + body.len();
+        Some(( id, body ))
+    })
+}
+
+
+fn element_uint( data: &[u8] ) -> u64 {
+    data.iter().take( 8 ).fold( 0, |n, &b| ( n << 8 ) | b as u64 )
+}
+
+
+/// Finds the Chapters element of a Matroska file and reads its first edition.
+fn read_matroska<R: Read + Seek>( reader: &mut R ) -> io::Result<Vec<Chapter>> {
+    // Skip the EBML header to reach the segment
+    let ( _, size ) = read_element( reader )?;
+    reader.seek( SeekFrom::Current( size.ok_or_else( || invalid( "bad EBML header" ) )? as i64 ) )?;
+    let ( id, _ ) = read_element( reader )?;
+    if id != MKV_SEGMENT {
+        return Err( invalid( "no segment" ) );
+    }
+    let segment_start = reader.stream_position()?;
+
+    // Chapters usually come before the clusters; if not, the seek head says where they are
+    let mut chapters_at = None;
+    loop {
+        let ( id, size ) = match read_element( reader ) {
+            Ok( element ) => element,
+            Err( e ) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok( Vec::new() ),
+            Err( e ) => return Err( e ),
+        };
+        match ( id, size ) {
+            ( MKV_CHAPTERS, Some( size ) ) => return Ok( matroska_chapters( &read_bytes( reader, size )? ) ),
+            ( MKV_SEEK_HEAD, Some( size ) ) => {
+                chapters_at = chapters_at.or( seek_position( &read_bytes( reader, size )?, MKV_CHAPTERS ) );
+            }
+            ( MKV_CLUSTER, _ ) | ( _, None ) => break,
+            ( _, Some( size ) ) => {
+                reader.seek( SeekFrom::Current( size as i64 ) )?;
+            }
+        }
+    }
+
+    let Some( position ) = chapters_at else {
+        return Ok( Vec::new() );
+    };
+    reader.seek( SeekFrom::Start( segment_start + position ) )?;
+    match read_element( reader )? {
+        ( MKV_CHAPTERS, Some( size ) ) => Ok( matroska_chapters( &read_bytes( reader, size )? ) ),
+        _ => Err( invalid( "seek head points away from chapters" ) ),
+    }
+}
+
+
+/// Finds where the seek head says the element `target` is, relative to the segment.
+fn seek_position( seek_head: &[u8], target: u32 ) -> Option<u64> {
+    elements( seek_head )
+        .filter( |( id, _ )| *id == MKV_SEEK )
+        .find_map( |( _, seek )| {
+            let id = elements( seek ).find( |( id, _ )| *id == MKV_SEEK_ID )?.1;
+            let position = elements( seek ).find( |( id, _ )| *id == MKV_SEEK_POSITION )?.1;
+            ( element_uint( id ) == target as u64 ).then( || element_uint( position ) )
+        })
+}
+
+
+/// Reads the visible top-level chapters of the first edition.
+fn matroska_chapters( data: &[u8] ) -> Vec<Chapter> {
+    let Some(( _, edition )) = elements( data ).find( |( id, _ )| *id == MKV_EDITION_ENTRY ) else {
+        return Vec::new();
+    };
+
+    elements( edition )
+        .filter( |( id, _ )| *id == MKV_CHAPTER_ATOM )
+        .filter_map( |( _, atom )| {
+            let field = |target: u32| elements( atom ).find( |( id, _ )| *id == target ).map( |( _, body )| body );
+            if field( MKV_CHAPTER_FLAG_HIDDEN ).is_some_and( |hidden| element_uint( hidden ) != 0 ) {
+                return None;
+            }
+            let start = element_uint( field( MKV_CHAPTER_TIME_START )? );
+            let title = field( MKV_CHAPTER_DISPLAY )
+                .and_then( |display| elements( display ).find( |( id, _ )| *id == MKV_CHAP_STRING ) )
+                .map( |( _, text )| String::from_utf8_lossy( text ).trim_end_matches( '\0' ).to_string() )
+                .filter( |t| !t.is_empty() );
+            Some( Chapter { title, start: Duration::from_nanos( start ) } )
+        })
+        .collect()
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+
+    fn mp4_atom( kind: &[u8; 4], body: &[u8] ) -> Vec<u8> {
+        [ &( body.len() as u32 + 8 ).to_be_bytes()[ .. ], kind, body ].concat()
+    }
+
+
+    fn ebml( id: u32, body: &[u8] ) -> Vec<u8> {
+        let id = id.to_be_bytes();
+        let id = &id[ id.iter().position( |&b| b != 0 ).unwrap().. ];
+        // Sizes are always written as 8-byte integers
+        let size = ( body.len() as u64 | 1 << 56 ).to_be_bytes();
+        [ id, &size[ .. ], body ].concat()
+    }
+
+
+    fn id3_frame( id: &[u8; 4], body: &[u8] ) -> Vec<u8> {
+        [ id, &( body.len() as u32 ).to_be_bytes()[ .. ], &[ 0, 0 ], body ].concat()
+    }
+
+
+    fn chapter( title: &str, secs: f64 ) -> Chapter {
+        Chapter { title: Some( title.into() ), start: Duration::from_secs_f64( secs ) }
+    }
+
+
+    #[test]
+    fn test_id3_chap_frames() {
+        let chap = |id: &[u8], start: u32, title: &str| {
+            let title = id3_frame( b"TIT2", &[ &[ 3 ], title.as_bytes() ].concat() );
+            let body = [ id, &[ 0 ], &start.to_be_bytes(), &0u32.to_be_bytes(), &[ 0xFF; 8 ], &title ].concat();
+            id3_frame( b"CHAP", &body )
+        };
+        let frames = [ chap( b"ch1", 90_500, "Two" ), chap( b"ch0", 0, "One" ), vec![ 0; 16 ] ].concat();
+        let size = frames.len() as u32;
+        let syncsafe = [ ( size >> 21 ) as u8 & 0x7F, ( size >> 14 ) as u8 & 0x7F, ( size >> 7 ) as u8 & 0x7F, size as u8 & 0x7F ];
+        let tag = [ &b"ID3\x03\x00\x00"[ .. ], &syncsafe, &frames ].concat();
+
+        let mut chapters = read_id3( &mut Cursor::new( tag ) ).unwrap();
+        chapters.sort_by_key( |c| c.start );
+        assert_eq!( chapters, vec![ chapter( "One", 0.0 ), chapter( "Two", 90.5 ) ] );
+    }
+
+
+    #[test]
+    fn test_mp4_chapter_track_and_nero_atom() {
+        let chpl = [
+            &[ 1, 0, 0, 0, 0, 0, 0, 0, 1 ][ .. ],
+            &15_000_000u64.to_be_bytes(), &[ 4 ], b"Nero",
+        ].concat();
+        let nero = mp4_atom( b"moov", &mp4_atom( b"udta", &mp4_atom( b"chpl", &chpl ) ) );
+        let file = [ mp4_atom( b"ftyp", b"M4B " ), nero ].concat();
+        assert_eq!( read_mp4( &mut Cursor::new( file ) ).unwrap(), vec![ chapter( "Nero", 1.5 ) ] );
+
+        // A text track with two samples, referenced by the audio track
+        let samples = [ &[ 0, 5 ][ .. ], b"Intro", &[ 0, 6 ], b"Part 2" ].concat();
+        let ftyp = mp4_atom( b"ftyp", b"M4B " );
+        let mdat_offset = ftyp.len() as u32 + 8;
+        let mdat = mp4_atom( b"mdat", &samples );
+        let full_box = |fields: &[u32]| -> Vec<u8> {
+            [ &[ 0u8; 4 ][ .. ], &fields.iter().flat_map( |f| f.to_be_bytes() ).collect::<Vec<_>>() ].concat()
+        };
+        let tkhd = |id: u32| mp4_atom( b"tkhd", &full_box( &[ 0, 0, id ] ) );
+        let stbl = [
+            mp4_atom( b"stts", &full_box( &[ 2, 1, 1000, 1, 500 ] ) ),
+            mp4_atom( b"stsz", &full_box( &[ 0, 2, 7, 8 ] ) ),
+            mp4_atom( b"stsc", &full_box( &[ 1, 1, 2, 1 ] ) ),
+            mp4_atom( b"stco", &full_box( &[ 1, mdat_offset ] ) ),
+        ].concat();
+        let mdia = [
+            mp4_atom( b"mdhd", &full_box( &[ 0, 0, 100 ] ) ),
+            mp4_atom( b"minf", &mp4_atom( b"stbl", &stbl ) ),
+        ].concat();
+        let audio = mp4_atom( b"trak", &[ tkhd( 1 ), mp4_atom( b"tref", &mp4_atom( b"chap", &2u32.to_be_bytes() ) ) ].concat() );
+        let text = mp4_atom( b"trak", &[ tkhd( 2 ), mp4_atom( b"mdia", &mdia ) ].concat() );
+        let file = [ ftyp, mdat, mp4_atom( b"moov", &[ audio, text ].concat() ) ].concat();
+
+        assert_eq!(
+            read_mp4( &mut Cursor::new( file ) ).unwrap(),
+            vec![ chapter( "Intro", 0.0 ), chapter( "Part 2", 10.0 ) ],
+        );
+    }
+
+
+    #[test]
+    fn test_mp4_sample_table_rejects_impossible_counts() {
+        let full_box = |fields: &[u32]| -> Vec<u8> {
+            [ &[ 0u8; 4 ][ .. ], &fields.iter().flat_map( |f| f.to_be_bytes() ).collect::<Vec<_>>() ].concat()
+        };
+        let trak = |stts: &[u32], stsz: &[u32]| {
+            let stbl = [
+                mp4_atom( b"stts", &full_box( stts ) ),
+                mp4_atom( b"stsz", &full_box( stsz ) ),
+                mp4_atom( b"stsc", &full_box( &[ 1, 1, 1, 1 ] ) ),
+                mp4_atom( b"stco", &full_box( &[ 1, 0 ] ) ),
+            ].concat();
+            let mdia = [
+                mp4_atom( b"mdhd", &full_box( &[ 0, 0, 100 ] ) ),
+                mp4_atom( b"minf", &mp4_atom( b"stbl", &stbl ) ),
+            ].concat();
+            mp4_atom( b"mdia", &mdia )
+        };
+
+        // Four billion fixed-size samples can't fit in a small file
+        assert_eq!( sample_table( &trak( &[ 1, u32::MAX, 10 ], &[ 8, u32::MAX ] ), 4096 ), None );
+        // Nor can a size table that claims more entries than it holds
+        assert_eq!( sample_table( &trak( &[ 1, 1, 10 ], &[ 0, u32::MAX, 8 ] ), 4096 ), None );
+        // A huge time-to-sample run is cut to the samples there are
+        assert_eq!( sample_table( &trak( &[ 1, u32::MAX, 10 ], &[ 8, 1 ] ), 4096 ), Some(( 100, vec![ ( 0, 0, 8 ) ] )) );
+    }
+
+
+    #[test]
+    fn test_matroska_chapters_after_clusters() {
+        let atom = |start_ns: u64, title: &str, hidden: u8| ebml( MKV_CHAPTER_ATOM, &[
+            ebml( MKV_CHAPTER_TIME_START, &start_ns.to_be_bytes() ),
+            ebml( MKV_CHAPTER_FLAG_HIDDEN, &[ hidden ] ),
+            ebml( MKV_CHAPTER_DISPLAY, &ebml( MKV_CHAP_STRING, title.as_bytes() ) ),
+        ].concat() );
+        let chapters = ebml( MKV_CHAPTERS, &ebml( MKV_EDITION_ENTRY, &[
+            atom( 0, "Start", 0 ),
+            atom( 5_000_000_000, "Hidden", 1 ),
+            atom( 62_250_000_000, "Later", 0 ),
+        ].concat() ) );
+
+        // The seek head points past a cluster to the chapters
+        let cluster = ebml( MKV_CLUSTER, &[ 0; 32 ] );
+        let seek = |position: u64| ebml( MKV_SEEK_HEAD, &ebml( MKV_SEEK, &[
+            ebml( MKV_SEEK_ID, &MKV_CHAPTERS.to_be_bytes() ),
+            ebml( MKV_SEEK_POSITION, &position.to_be_bytes() ),
+        ].concat() ) );
+        let head_len = seek( 0 ).len() as u64;
+        let segment = [ seek( head_len + cluster.len() as u64 ), cluster, chapters ].concat();
+        let file = [ ebml( EBML_HEADER, b"matroska" ), ebml( MKV_SEGMENT, &segment ) ].concat();
+
+        assert_eq!(
+            read_matroska( &mut Cursor::new( file ) ).unwrap(),
+            vec![ chapter( "Start", 0.0 ), chapter( "Later", 62.25 ) ],
+        );
+    }
+}
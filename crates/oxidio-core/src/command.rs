@@ -10,6 +10,15 @@ use std::time::Duration;
 
 use thiserror::Error;
 
+use crate::crossfade::CrossfadeCurve;
+use crate::replaygain::ReplayGainMode;
+use crate::timestretch::{ MAX_SPEED, MIN_SPEED };
+use crate::volume::{ db_to_percent, percent_to_db, MAX_VOLUME_DB, MIN_VOLUME_DB };
+
+
+/// Longest crossfade `/crossfade` accepts, in seconds.
+pub const MAX_CROSSFADE_SECS: f32 = 30.0;
+
 
 /// Errors that can occur during command parsing or execution.
 #[derive( Debug, Error )]
@@ -53,10 +62,24 @@ pub enum Command {
     Next,
     Prev,
     Seek { position: Duration },
+    Crossfade { seconds: Option<f32>, curve: Option<CrossfadeCurve> },
+    ReplayGain {
+        mode: Option<ReplayGainMode>,
+        preamp_db: Option<f32>,
+        prevent_clipping: Option<bool>,
+        target_lufs: Option<f32>,
+    },
+    Eq { arg: Option<EqArg> },
+    Speed { factor: Option<f32> },
+    Loop { arg: LoopArg },
+    Chapter { arg: ChapterArg },
+    Bookmark { arg: BookmarkArg },
+    /// Switch to audio stream n (counting from 1), or the next one if None
+    Stream { number: Option<usize> },
 
     // UI commands
     Vis,
-    Volume { level: Option<u32> },
+    Volume { db: Option<f32> },
     Help,
     Quit,
 }
@@ -71,6 +94,49 @@ pub enum RepeatModeArg {
 }
 
 
+/// A-B loop argument for parsing.
+#[derive( Debug, Clone, Copy, PartialEq, Eq )]
+pub enum LoopArg {
+    /// Mark the start of the loop at the current position
+    A,
+    /// Mark the end of the loop at the current position, starting the loop
+    B,
+    Off,
+}
+
+
+/// Chapter argument for parsing.
+#[derive( Debug, Clone, Copy, PartialEq, Eq )]
+pub enum ChapterArg {
+    Next,
+    Prev,
+    /// Jump to the chapter with this number (counting from 1)
+    Number( usize ),
+}
+
+
+/// Bookmark argument for parsing.
+#[derive( Debug, Clone, PartialEq, Eq )]
+pub enum BookmarkArg {
+    /// Bookmark the current position, optionally named
+    Add( Option<String> ),
+    /// Show the saved bookmarks
+    List,
+}
+
+
+/// Equalizer argument for parsing.
+#[derive( Debug, Clone, PartialEq, Eq )]
+pub enum EqArg {
+    On,
+    Off,
+    /// Apply the named preset
+    Preset( String ),
+    /// Save the current bands as a user preset
+    Save( String ),
+}
+
+
 impl FromStr for RepeatModeArg {
     type Err = CommandError;
 
@@ -88,6 +154,42 @@ impl FromStr for RepeatModeArg {
 }
 
 
+impl FromStr for ChapterArg {
+    type Err = CommandError;
+
+
+    fn from_str( s: &str ) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "next" | "n" | "+" => Ok( ChapterArg::Next ),
+            "prev" | "previous" | "p" | "-" => Ok( ChapterArg::Prev ),
+            n => match n.parse() {
+                Ok( n ) if n > 0 => Ok( ChapterArg::Number( n ) ),
+                _ => Err( CommandError::InvalidArgument(
+                    format!( "Invalid chapter: '{}'. Use 'next', 'prev', or a chapter number", s )
+                )),
+            },
+        }
+    }
+}
+
+
+impl FromStr for LoopArg {
+    type Err = CommandError;
+
+
+    fn from_str( s: &str ) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "a" | "start" => Ok( LoopArg::A ),
+            "b" | "end" => Ok( LoopArg::B ),
+            "off" | "clear" => Ok( LoopArg::Off ),
+            _ => Err( CommandError::InvalidArgument(
+                format!( "Invalid loop point: '{}'. Use 'a', 'b', or 'off'", s )
+            )),
+        }
+    }
+}
+
+
 impl Command {
     /// Parses a command string (without the leading `/`).
     ///
@@ -151,12 +253,150 @@ impl Command {
                 let position = parse_time( time_str )?;
                 Ok( Command::Seek { position } )
             }
+            "crossfade" | "xf" => {
+                let mut seconds = None;
+                let mut curve = None;
+                for arg in args.unwrap_or( "" ).split_whitespace() {
+                    if arg.eq_ignore_ascii_case( "off" ) {
+                        seconds = Some( 0.0 );
+                    } else if let Ok( secs ) = arg.trim_end_matches( 's' ).parse::<f32>() {
+                        if !( 0.0..=MAX_CROSSFADE_SECS ).contains( &secs ) {
+                            return Err( CommandError::InvalidArgument(
+                                format!( "Crossfade must be between 0 and {} seconds, got {}", MAX_CROSSFADE_SECS, secs )
+                            ));
+                        }
+                        seconds = Some( secs );
+                    } else {
+                        curve = Some( arg.parse()? );
+                    }
+                }
+                Ok( Command::Crossfade { seconds, curve } )
+            }
+            "replaygain" | "rg" => {
+                let mut mode = None;
+                let mut preamp_db = None;
+                let mut prevent_clipping = None;
+                let mut target_lufs = None;
+                for arg in args.unwrap_or( "" ).split_whitespace() {
+                    match arg.to_lowercase().as_str() {
+                        "peak" => prevent_clipping = Some( true ),
+                        "nopeak" => prevent_clipping = Some( false ),
+                        lower if lower.ends_with( "lufs" ) => {
+                            let lufs: f32 = lower.trim_end_matches( "lufs" ).parse()
+                                .map_err( |_| CommandError::InvalidArgument( format!( "Invalid loudness target: {}", arg ) ) )?;
+                            if !( -30.0..=-5.0 ).contains( &lufs ) {
+                                return Err( CommandError::InvalidArgument(
+                                    format!( "Loudness target must be between -30 and -5 LUFS, got {}", lufs )
+                                ));
+                            }
+                            target_lufs = Some( lufs );
+                        }
+                        lower => {
+                            if let Ok( db ) = lower.trim_end_matches( "db" ).parse::<f32>() {
+                                if !( -15.0..=15.0 ).contains( &db ) {
+                                    return Err( CommandError::InvalidArgument(
+                                        format!( "Preamp must be between -15 and +15 dB, got {}", db )
+                                    ));
+                                }
+                                preamp_db = Some( db );
+                            } else {
+                                mode = Some( arg.parse()? );
+                            }
+                        }
+                    }
+                }
+                Ok( Command::ReplayGain { mode, preamp_db, prevent_clipping, target_lufs } )
+            }
+            "speed" | "sp" => {
+                let factor = match args.filter( |s| !s.is_empty() ) {
+                    None => None,
+                    Some( s ) if s.eq_ignore_ascii_case( "normal" ) => Some( 1.0 ),
+                    Some( s ) => {
+                        let factor: f32 = s.trim_end_matches( ['x', 'X'] ).parse()
+                            .map_err( |_| CommandError::InvalidArgument( format!( "Invalid speed: {}", s ) ) )?;
+                        if !( MIN_SPEED..=MAX_SPEED ).contains( &factor ) {
+                            return Err( CommandError::InvalidArgument(
+                                format!( "Speed must be between {}x and {}x, got {}", MIN_SPEED, MAX_SPEED, factor )
+                            ));
+                        }
+                        Some( factor )
+                    }
+                };
+                Ok( Command::Speed { factor } )
+            }
+            "loop" | "ab" => {
+                let arg = args
+                    .filter( |s| !s.is_empty() )
+                    .ok_or_else( || CommandError::MissingArgument( "loop point (a, b or off)".into() ) )?
+                    .parse()?;
+                Ok( Command::Loop { arg } )
+            }
+            "chapter" | "ch" => {
+                let arg = args
+                    .filter( |s| !s.is_empty() )
+                    .ok_or_else( || CommandError::MissingArgument( "chapter (next, prev or a number)".into() ) )?
+                    .parse()?;
+                Ok( Command::Chapter { arg } )
+            }
+            "bookmark" | "bookmarks" | "bm" => {
+                let mut words = args.unwrap_or( "" ).splitn( 2, ' ' );
+                let first = words.next().unwrap_or( "" );
+                // Names can be quoted, e.g. /bookmark add "the intro"
+                let rest = words.next()
+                    .map( |r| r.trim().trim_matches( '"' ).trim() )
+                    .filter( |r| !r.is_empty() );
+                let arg = match ( first.to_lowercase().as_str(), rest ) {
+                    ( "" | "list" | "ls", None ) => BookmarkArg::List,
+                    ( "add", name ) => BookmarkArg::Add( name.map( String::from ) ),
+                    _ => return Err( CommandError::InvalidArgument(
+                        format!( "Invalid bookmark command: '{}'. Use 'add [name]' or 'list'", args.unwrap_or( "" ) )
+                    )),
+                };
+                Ok( Command::Bookmark { arg } )
+            }
+            "stream" | "audio" => {
+                let number = match args.filter( |s| !s.is_empty() ) {
+                    None => None,
+                    Some( s ) if s.eq_ignore_ascii_case( "next" ) => None,
+                    Some( s ) => match s.parse::<usize>() {
+                        Ok( n ) if n > 0 => Some( n ),
+                        _ => return Err( CommandError::InvalidArgument(
+                            format!( "Invalid audio stream: '{}'. Use 'next' or a stream number", s )
+                        )),
+                    },
+                };
+                Ok( Command::Stream { number } )
+            }
+            "eq" | "equalizer" => {
+                let arg = match args.filter( |s| !s.is_empty() ) {
+                    None => None,
+                    Some( s ) => {
+                        let mut words = s.splitn( 2, ' ' );
+                        let first = words.next().unwrap_or( "" );
+                        let rest = words.next().map( |r| r.trim() ).filter( |r| !r.is_empty() );
+                        match ( first.to_lowercase().as_str(), rest ) {
+                            ( "on", None ) => Some( EqArg::On ),
+                            ( "off", None ) => Some( EqArg::Off ),
+                            ( "save", Some( name ) ) => Some( EqArg::Save( name.to_string() ) ),
+                            ( "save", None ) => {
+                                return Err( CommandError::MissingArgument( "preset name".into() ) );
+                            }
+                            _ => Some( EqArg::Preset( s.to_string() ) ),
+                        }
+                    }
+                };
+                Ok( Command::Eq { arg } )
+            }
 
             // UI commands
             "vis" | "visualizer" => Ok( Command::Vis ),
             "vol" | "volume" => {
-                let level = args.and_then( |s| s.parse().ok() );
-                Ok( Command::Volume { level } )
+                let db = match args.filter( |s| !s.is_empty() ) {
+                    None => None,
+                    Some( s ) if s.eq_ignore_ascii_case( "mute" ) => Some( MIN_VOLUME_DB ),
+                    Some( s ) => Some( parse_volume( s )? ),
+                };
+                Ok( Command::Volume { db } )
             }
             "help" | "h" => Ok( Command::Help ),
             "quit" | "q" | "exit" => Ok( Command::Quit ),
@@ -187,8 +427,16 @@ impl Command {
             Command::Next => "Next track",
             Command::Prev => "Previous track",
             Command::Seek { .. } => "Seek to position",
+            Command::Crossfade { .. } => "Set crossfade length and curve",
+            Command::ReplayGain { .. } => "Set ReplayGain mode and preamp",
+            Command::Eq { .. } => "Set equalizer preset",
+            Command::Speed { .. } => "Set playback speed",
+            Command::Loop { .. } => "Set A-B loop points",
+            Command::Chapter { .. } => "Jump to a chapter",
+            Command::Bookmark { .. } => "Add or list bookmarks",
+            Command::Stream { .. } => "Switch audio stream",
             Command::Vis => "Toggle visualizer",
-            Command::Volume { .. } => "Set volume (0-100)",
+            Command::Volume { .. } => "Set volume (dB or %)",
             Command::Help => "Show help",
             Command::Quit => "Quit application",
         }
@@ -218,6 +466,34 @@ fn parse_time( s: &str ) -> Result<Duration, CommandError> {
 }
 
 
+/// Parses a volume like "-6dB" or "+3 dB", or a percentage of loudness like "50"
+/// (see `volume::percent_to_db`), into dB.
+fn parse_volume( s: &str ) -> Result<f32, CommandError> {
+    let lower = s.trim().to_lowercase();
+    let invalid = || CommandError::InvalidArgument( format!( "Invalid volume: {}", s ) );
+
+    let db = match lower.strip_suffix( "db" ) {
+        Some( db ) => db.trim().parse::<f32>().map_err( |_| invalid() )?,
+        None => {
+            let percent: f32 = lower.trim_end_matches( '%' ).parse().map_err( |_| invalid() )?;
+            let max_percent = db_to_percent( MAX_VOLUME_DB ).floor();
+            if !( 0.0..=max_percent ).contains( &percent ) {
+                return Err( CommandError::InvalidArgument(
+                    format!( "Volume must be between 0% and {}%, got {}", max_percent, percent )
+                ));
+            }
+            percent_to_db( percent )
+        }
+    };
+    if db > MAX_VOLUME_DB || db.is_nan() {
+        return Err( CommandError::InvalidArgument(
+            format!( "Volume can be boosted by at most +{} dB, got {}", MAX_VOLUME_DB, s )
+        ));
+    }
+    Ok( db.max( MIN_VOLUME_DB ) )
+}
+
+
 /// Returns help text listing all available commands.
 pub fn help_text() -> &'static str {
     r#"Playlist Commands:
@@ -240,10 +516,29 @@ Playback Commands:
   /next           Next track
   /prev           Previous track
   /seek <time>    Seek to position (e.g., 1:30)
+  /crossfade <s>  Crossfade seconds, 0/off to disable
+                  optional curve: linear or equal
+  /replaygain <m> ReplayGain off/track/album/auto
+                  optional preamp (e.g. +3dB), peak/nopeak,
+                  loudness target for untagged (e.g. -18lufs)
+  /speed <x>      Playback speed 0.5-3.0, keeps pitch
+                  [ and ] step down/up by 0.1
+  /loop <a|b|off> Mark loop start/end at the current
+                  position, or stop looping
+  /chapter <n>    Jump to chapter n, or next/prev
+  /bookmark add [name]
+                  Bookmark the current position
+  /bookmark       List bookmarks and resume points [b]
+  /stream [n]     Switch to audio stream n, or the next
+                  (listed in track info)
+  /eq <preset>    Apply equalizer preset (e.g. rock)
+                  on/off, or save <name> for current bands
 
 Other Commands:
   /vis            Toggle visualizer      [v]
-  /vol [0-100]    Set volume             [+/-]
+  /vol <level>    Set volume             [+/-]
+                  in dB (e.g. -6dB), % of loudness
+                  (50 = -10dB) or mute
   /help           Show this help         [?]
   /quit           Exit oxidio            [q]"#
 }
@@ -282,6 +577,103 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_parse_crossfade() {
+        let cmd = Command::parse( "crossfade 4 linear" ).unwrap();
+        assert_eq!( cmd, Command::Crossfade { seconds: Some( 4.0 ), curve: Some( CrossfadeCurve::Linear ) } );
+    }
+
+
+    #[test]
+    fn test_parse_replaygain() {
+        let cmd = Command::parse( "rg album -2.5dB nopeak -16LUFS" ).unwrap();
+        assert_eq!( cmd, Command::ReplayGain {
+            mode: Some( ReplayGainMode::Album ),
+            preamp_db: Some( -2.5 ),
+            prevent_clipping: Some( false ),
+            target_lufs: Some( -16.0 ),
+        });
+    }
+
+
+    #[test]
+    fn test_parse_eq() {
+        assert_eq!( Command::parse( "eq bass boost" ).unwrap(), Command::Eq { arg: Some( EqArg::Preset( "bass boost".into() ) ) } );
+        assert_eq!( Command::parse( "eq OFF" ).unwrap(), Command::Eq { arg: Some( EqArg::Off ) } );
+        assert_eq!( Command::parse( "eq save My Mix" ).unwrap(), Command::Eq { arg: Some( EqArg::Save( "My Mix".into() ) ) } );
+        assert!( matches!( Command::parse( "eq save" ), Err( CommandError::MissingArgument( _ ) ) ) );
+    }
+
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!( Command::parse( "speed 1.5x" ).unwrap(), Command::Speed { factor: Some( 1.5 ) } );
+        assert_eq!( Command::parse( "sp normal" ).unwrap(), Command::Speed { factor: Some( 1.0 ) } );
+        assert_eq!( Command::parse( "speed" ).unwrap(), Command::Speed { factor: None } );
+        assert!( matches!( Command::parse( "speed 4" ), Err( CommandError::InvalidArgument( _ ) ) ) );
+    }
+
+
+    #[test]
+    fn test_parse_loop() {
+        assert_eq!( Command::parse( "loop a" ).unwrap(), Command::Loop { arg: LoopArg::A } );
+        assert_eq!( Command::parse( "loop B" ).unwrap(), Command::Loop { arg: LoopArg::B } );
+        assert_eq!( Command::parse( "ab off" ).unwrap(), Command::Loop { arg: LoopArg::Off } );
+        assert!( matches!( Command::parse( "loop" ), Err( CommandError::MissingArgument( _ ) ) ) );
+        assert!( matches!( Command::parse( "loop c" ), Err( CommandError::InvalidArgument( _ ) ) ) );
+    }
+
+
+    #[test]
+    fn test_parse_chapter() {
+        assert_eq!( Command::parse( "chapter next" ).unwrap(), Command::Chapter { arg: ChapterArg::Next } );
+        assert_eq!( Command::parse( "ch prev" ).unwrap(), Command::Chapter { arg: ChapterArg::Prev } );
+        assert_eq!( Command::parse( "chapter 12" ).unwrap(), Command::Chapter { arg: ChapterArg::Number( 12 ) } );
+        assert!( matches!( Command::parse( "chapter" ), Err( CommandError::MissingArgument( _ ) ) ) );
+        assert!( matches!( Command::parse( "chapter 0" ), Err( CommandError::InvalidArgument( _ ) ) ) );
+    }
+
+
+    #[test]
+    fn test_parse_bookmark() {
+        assert_eq!( Command::parse( "bookmark" ).unwrap(), Command::Bookmark { arg: BookmarkArg::List } );
+        assert_eq!( Command::parse( "bm add" ).unwrap(), Command::Bookmark { arg: BookmarkArg::Add( None ) } );
+        assert_eq!(
+            Command::parse( "bookmark add \"the intro\"" ).unwrap(),
+            Command::Bookmark { arg: BookmarkArg::Add( Some( "the intro".into() ) ) },
+        );
+        assert!( matches!( Command::parse( "bookmark drop" ), Err( CommandError::InvalidArgument( _ ) ) ) );
+    }
+
+
+    #[test]
+    fn test_parse_stream() {
+        assert_eq!( Command::parse( "stream" ).unwrap(), Command::Stream { number: None } );
+        assert_eq!( Command::parse( "audio next" ).unwrap(), Command::Stream { number: None } );
+        assert_eq!( Command::parse( "stream 2" ).unwrap(), Command::Stream { number: Some( 2 ) } );
+        assert!( matches!( Command::parse( "stream 0" ), Err( CommandError::InvalidArgument( _ ) ) ) );
+    }
+
+
+    #[test]
+    fn test_parse_volume() {
+        assert_eq!( Command::parse( "vol -6dB" ).unwrap(), Command::Volume { db: Some( -6.0 ) } );
+        assert_eq!( Command::parse( "volume +3 db" ).unwrap(), Command::Volume { db: Some( 3.0 ) } );
+        assert_eq!( Command::parse( "vol 50" ).unwrap(), Command::Volume { db: Some( -10.0 ) } );
+        assert_eq!( Command::parse( "vol mute" ).unwrap(), Command::Volume { db: Some( MIN_VOLUME_DB ) } );
+        assert_eq!( Command::parse( "vol" ).unwrap(), Command::Volume { db: None } );
+        assert!( matches!( Command::parse( "vol +20dB" ), Err( CommandError::InvalidArgument( _ ) ) ) );
+        assert!( matches!( Command::parse( "vol loud" ), Err( CommandError::InvalidArgument( _ ) ) ) );
+    }
+
+
+    #[test]
+    fn test_parse_crossfade_off() {
+        let cmd = Command::parse( "xf off" ).unwrap();
+        assert_eq!( cmd, Command::Crossfade { seconds: Some( 0.0 ), curve: None } );
+    }
+
+
     #[test]
     fn test_parse_repeat_with_mode() {
         let cmd = Command::parse( "repeat all" ).unwrap();
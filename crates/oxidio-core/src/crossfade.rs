@@ -0,0 +1,101 @@
+//! Crossfade configuration
+//!
+//! Describes how consecutive tracks are blended into each other.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::command::CommandError;
+
+
+/// Gain curve used while crossfading between two tracks.
+#[derive( Debug, Clone, Copy, PartialEq, Eq, Default )]
+#[cfg_attr( feature = "serde", derive( serde::Serialize, serde::Deserialize ) )]
+#[cfg_attr( feature = "serde", serde( rename_all = "kebab-case" ) )]
+pub enum CrossfadeCurve {
+    /// Straight-line gains. Dips in loudness around the midpoint.
+    Linear,
+
+    /// Sine/cosine gains that keep the combined power constant (default)
+    #[default]
+    EqualPower,
+}
+
+
+impl CrossfadeCurve {
+    /// Returns the (outgoing, incoming) gains at `progress` through the fade (0.0 to 1.0).
+    pub fn gains( self, progress: f32 ) -> ( f32, f32 ) {
+        let t = progress.clamp( 0.0, 1.0 );
+        match self {
+            CrossfadeCurve::Linear => ( 1.0 - t, t ),
+            CrossfadeCurve::EqualPower => {
+                let angle = t * std::f32::consts::FRAC_PI_2;
+                ( angle.cos(), angle.sin() )
+            }
+        }
+    }
+
+
+    /// Returns the display name of the curve.
+    pub fn name( &self ) -> &'static str {
+        match self {
+            CrossfadeCurve::Linear => "linear",
+            CrossfadeCurve::EqualPower => "equal power",
+        }
+    }
+}
+
+
+impl FromStr for CrossfadeCurve {
+    type Err = CommandError;
+
+
+    fn from_str( s: &str ) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "linear" | "lin" => Ok( CrossfadeCurve::Linear ),
+            "equal" | "equal-power" | "power" | "eq" => Ok( CrossfadeCurve::EqualPower ),
+            _ => Err( CommandError::InvalidArgument(
+                format!( "Invalid crossfade curve: '{}'. Use 'linear' or 'equal'", s )
+            )),
+        }
+    }
+}
+
+
+/// Crossfade settings applied to track transitions.
+#[derive( Debug, Clone, Copy, PartialEq, Default )]
+pub struct Crossfade {
+    /// Length of the overlap between tracks (zero disables crossfading)
+    pub duration: Duration,
+    pub curve: CrossfadeCurve,
+}
+
+
+impl Crossfade {
+    /// Returns true if transitions should be crossfaded.
+    pub fn is_enabled( &self ) -> bool {
+        !self.duration.is_zero()
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_equal_power_keeps_power_constant() {
+        for step in 0..=10 {
+            let ( out, inc ) = CrossfadeCurve::EqualPower.gains( step as f32 / 10.0 );
+            assert!(( out * out + inc * inc - 1.0 ).abs() < 1e-5 );
+        }
+    }
+
+
+    #[test]
+    fn test_linear_endpoints() {
+        assert_eq!( CrossfadeCurve::Linear.gains( 0.0 ), ( 1.0, 0.0 ) );
+        assert_eq!( CrossfadeCurve::Linear.gains( 1.0 ), ( 0.0, 1.0 ) );
+    }
+}
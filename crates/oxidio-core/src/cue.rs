@@ -0,0 +1,394 @@
+//! CUE sheet support
+//!
+//! Splits single-file album rips into their tracks. Each cue TRACK becomes a virtual
+//! track addressed by a path like `Album.cue#3`, which playlists store and the player
+//! plays like any other file.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{ Path, PathBuf };
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::library::SUPPORTED_EXTENSIONS;
+use crate::metadata::AudioMetadata;
+
+
+/// CD frames per second, the resolution of cue sheet timestamps.
+const FRAMES_PER_SECOND: u64 = 75;
+
+
+/// Errors that can occur reading a cue sheet.
+#[derive( Debug, Error )]
+pub enum CueError {
+    #[error( "IO error: {0}" )]
+    Io( #[from] std::io::Error ),
+
+    #[error( "Invalid cue sheet, line {line}: {message}" )]
+    Invalid { line: usize, message: String },
+
+    #[error( "Cue sheet has no track {0}" )]
+    NoSuchTrack( u32 ),
+}
+
+
+/// One track of a cue sheet: a stretch of an audio file.
+#[derive( Debug, Clone, PartialEq )]
+pub struct CueTrack {
+    pub number: u32,
+    /// Audio file the track is part of
+    pub file: PathBuf,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// Album-wide title, performer, genre and year from the sheet
+    pub album: Option<String>,
+    pub album_performer: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
+    /// Offset of the track (INDEX 01) within the file
+    pub start: Duration,
+    /// Where the next track in the same file starts, or None to play to the end
+    pub end: Option<Duration>,
+}
+
+
+impl CueTrack {
+    /// Length of the track, if it doesn't run to the end of its file.
+    pub fn duration( &self ) -> Option<Duration> {
+        self.end.map( |end| end.saturating_sub( self.start ) )
+    }
+
+
+    /// Overrides the tags read from the audio file with what the sheet says about
+    /// this track.
+    pub fn apply_to( &self, metadata: &mut AudioMetadata ) {
+        metadata.title = self.title.clone().or( metadata.title.take() );
+        metadata.artist = self.performer.clone().or( self.album_performer.clone() ).or( metadata.artist.take() );
+        metadata.album = self.album.clone().or( metadata.album.take() );
+        metadata.album_artist = self.album_performer.clone().or( metadata.album_artist.take() );
+        metadata.track_number = Some( self.number );
+        metadata.genre = metadata.genre.take().or( self.genre.clone() );
+        metadata.year = metadata.year.or( self.year );
+    }
+}
+
+
+/// A parsed cue sheet.
+#[derive( Debug, Clone )]
+pub struct CueSheet {
+    /// Path of the `.cue` file
+    pub path: PathBuf,
+    /// Audio tracks in sheet order
+    pub tracks: Vec<CueTrack>,
+}
+
+
+impl CueSheet {
+    /// Reads and parses a cue sheet. Audio files are looked up next to it.
+    pub fn load( path: &Path ) -> Result<Self, CueError> {
+        let bytes = fs::read( path )?;
+
+        // Cue sheets predate UTF-8, so fall back to Latin-1
+        let text = String::from_utf8( bytes )
+            .unwrap_or_else( |e| e.into_bytes().iter().map( |&b| b as char ).collect() );
+
+        let dir = path.parent().unwrap_or( Path::new( "" ) );
+        Self::parse( path, &text, dir )
+    }
+
+
+    /// Parses the text of the cue sheet at `path`, resolving audio files against `dir`.
+    pub fn parse( path: &Path, text: &str, dir: &Path ) -> Result<Self, CueError> {
+        let mut album = None;
+        let mut album_performer = None;
+        let mut genre = None;
+        let mut year = None;
+        let mut file: Option<PathBuf> = None;
+        let mut tracks: Vec<CueTrack> = Vec::new();
+        // Whether the track being read is an audio track, with its INDEX 01 line
+        let mut current: Option<( bool, usize )> = None;
+
+        let text = text.trim_start_matches( '\u{feff}' );
+        for ( i, line ) in text.lines().enumerate() {
+            let number = i + 1;
+            let invalid = |message: &str| CueError::Invalid { line: number, message: message.into() };
+            let line = line.trim();
+            let ( command, rest ) = line.split_once( char::is_whitespace ).unwrap_or(( line, "" ));
+            let rest = rest.trim();
+            let in_track = current.is_some_and( |( audio, _ )| audio );
+
+            match command.to_uppercase().as_str() {
+                "REM" => {
+                    let ( key, value ) = rest.split_once( char::is_whitespace ).unwrap_or(( rest, "" ));
+                    match key.to_uppercase().as_str() {
+                        "GENRE" => genre = Some( unquote( value ) ),
+                        "DATE" => year = value.trim().get( ..4 ).and_then( |y| y.parse().ok() ),
+                        _ => {}
+                    }
+                }
+                "TITLE" if in_track => tracks.last_mut().unwrap().title = Some( unquote( rest ) ),
+                "TITLE" if current.is_none() => album = Some( unquote( rest ) ),
+                "PERFORMER" if in_track => tracks.last_mut().unwrap().performer = Some( unquote( rest ) ),
+                "PERFORMER" if current.is_none() => album_performer = Some( unquote( rest ) ),
+                "FILE" => {
+                    finish_track( &tracks, current, number )?;
+                    current = None;
+                    // The name is quoted or runs up to the file type
+                    let name = if rest.starts_with( '"' ) {
+                        unquote( rest )
+                    } else {
+                        rest.rsplit_once( char::is_whitespace ).map_or( rest, |( name, _ )| name ).to_string()
+                    };
+                    file = Some( locate( dir, &name ) );
+                }
+                "TRACK" => {
+                    finish_track( &tracks, current, number )?;
+                    let mut words = rest.split_whitespace();
+                    let track_number = words.next().and_then( |n| n.parse().ok() )
+                        .ok_or_else( || invalid( "bad track number" ) )?;
+                    let audio = words.next().is_some_and( |kind| kind.eq_ignore_ascii_case( "AUDIO" ) );
+                    let file = file.clone().ok_or_else( || invalid( "TRACK before FILE" ) )?;
+                    if audio {
+                        tracks.push( CueTrack {
+                            number: track_number,
+                            file,
+                            title: None,
+                            performer: None,
+                            album: None,
+                            album_performer: None,
+                            genre: None,
+                            year: None,
+                            start: Duration::ZERO,
+                            end: None,
+                        });
+                    }
+                    current = Some(( audio, 0 ));
+                }
+                "INDEX" if in_track => {
+                    let mut words = rest.split_whitespace();
+                    let index: u32 = words.next().and_then( |n| n.parse().ok() )
+                        .ok_or_else( || invalid( "bad index number" ) )?;
+                    let time = words.next().and_then( parse_time ).ok_or_else( || invalid( "bad index time" ) )?;
+                    // INDEX 00 marks the pregap, which is heard as the end of the previous track
+                    if index == 1 {
+                        tracks.last_mut().unwrap().start = time;
+                        current = Some(( true, number ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        finish_track( &tracks, current, text.lines().count() )?;
+
+        // Each track runs until the next one in the same file starts
+        for i in 0..tracks.len() {
+            let next = tracks.get( i + 1 ).filter( |next| next.file == tracks[ i ].file ).map( |next| next.start );
+            let track = &mut tracks[ i ];
+            track.end = next;
+            track.album = album.clone();
+            track.album_performer = album_performer.clone();
+            track.genre = genre.clone();
+            track.year = year;
+        }
+
+        Ok( Self { path: path.to_path_buf(), tracks } )
+    }
+
+
+    /// Finds a track by its number.
+    pub fn track( &self, number: u32 ) -> Option<&CueTrack> {
+        self.tracks.iter().find( |t| t.number == number )
+    }
+
+
+    /// Returns the virtual paths of every track, in order.
+    pub fn track_paths( &self ) -> Vec<PathBuf> {
+        self.tracks.iter().map( |t| track_path( &self.path, t.number ) ).collect()
+    }
+
+
+    /// Returns true if the sheet splits up `file`.
+    pub fn covers( &self, file: &Path ) -> bool {
+        self.tracks.iter().any( |t| t.file == file )
+    }
+}
+
+
+/// Checks that the track before `line` had an INDEX 01.
+fn finish_track( tracks: &[CueTrack], current: Option<( bool, usize )>, line: usize ) -> Result<(), CueError> {
+    match current {
+        Some(( true, 0 )) => Err( CueError::Invalid {
+            line,
+            message: format!( "track {} has no INDEX 01", tracks.last().map_or( 0, |t| t.number ) ),
+        }),
+        _ => Ok(()),
+    }
+}
+
+
+/// Strips the quotes around a value, if it has them.
+fn unquote( value: &str ) -> String {
+    let value = value.trim();
+    match value.strip_prefix( '"' ) {
+        Some( rest ) => rest.split( '"' ).next().unwrap_or( "" ).to_string(),
+        None => value.to_string(),
+    }
+}
+
+
+/// Parses an `MM:SS:FF` timestamp (FF in 1/75ths of a second).
+fn parse_time( s: &str ) -> Option<Duration> {
+    let mut parts = s.split( ':' ).map( |p| p.parse::<u64>().ok() );
+    let ( minutes, seconds, frames ) = ( parts.next()??, parts.next()??, parts.next()?? );
+    if parts.next().is_some() || seconds >= 60 || frames >= FRAMES_PER_SECOND {
+        return None;
+    }
+    let frames = ( minutes * 60 + seconds ) * FRAMES_PER_SECOND + frames;
+    Some( Duration::from_nanos( frames * 1_000_000_000 / FRAMES_PER_SECOND ) )
+}
+
+
+/// Resolves a FILE entry against the sheet's folder.
+///
+/// Rips are often re-encoded without updating the sheet, so if the named file is
+/// missing, an audio file with the same stem is used instead.
+fn locate( dir: &Path, name: &str ) -> PathBuf {
+    let path = dir.join( name );
+    if path.exists() {
+        return path;
+    }
+    SUPPORTED_EXTENSIONS.iter()
+        .map( |ext| path.with_extension( ext ) )
+        .find( |candidate| candidate.exists() )
+        .unwrap_or( path )
+}
+
+
+/// Returns the virtual path of track `number` of the sheet at `cue`, e.g. `Album.cue#3`.
+pub fn track_path( cue: &Path, number: u32 ) -> PathBuf {
+    let mut path = OsString::from( cue.as_os_str() );
+    path.push( format!( "#{}", number ) );
+    PathBuf::from( path )
+}
+
+
+/// Splits a virtual track path into the cue sheet and track number.
+///
+/// Returns None for ordinary files.
+pub fn parse_track_path( path: &Path ) -> Option<( PathBuf, u32 )> {
+    let ( cue, number ) = path.to_str()?.rsplit_once( '#' )?;
+    if !is_cue_file( Path::new( cue ) ) || !number.bytes().all( |b| b.is_ascii_digit() ) {
+        return None;
+    }
+    Some(( PathBuf::from( cue ), number.parse().ok()? ))
+}
+
+
+/// Looks up the track a virtual path refers to.
+///
+/// Returns Ok(None) for ordinary files.
+pub fn resolve( path: &Path ) -> Result<Option<CueTrack>, CueError> {
+    let Some(( cue, number )) = parse_track_path( path ) else {
+        return Ok( None );
+    };
+    let sheet = CueSheet::load( &cue )?;
+    sheet.track( number ).cloned().map( Some ).ok_or( CueError::NoSuchTrack( number ) )
+}
+
+
+/// Returns true if `path` names a `.cue` file.
+pub fn is_cue_file( path: &Path ) -> bool {
+    path.extension().is_some_and( |e| e.eq_ignore_ascii_case( "cue" ) )
+}
+
+
+/// Expands a file being added to a playlist into the tracks it holds.
+///
+/// A cue sheet becomes its tracks, as does an audio file with a sheet next to it
+/// (`Album.cue` or `Album.flac.cue`) that splits it up. Anything else is returned
+/// as is.
+pub fn expand( path: &Path ) -> Vec<PathBuf> {
+    let sheet = if is_cue_file( path ) {
+        CueSheet::load( path ).map_err( |e| tracing::warn!( "Failed to read {:?}: {}", path, e ) ).ok()
+    } else {
+        let mut beside = path.as_os_str().to_owned();
+        beside.push( ".cue" );
+        [ path.with_extension( "cue" ), PathBuf::from( beside ) ].into_iter()
+            .filter( |cue| cue.exists() )
+            .filter_map( |cue| CueSheet::load( &cue ).ok() )
+            .find( |sheet| sheet.covers( path ) )
+    };
+
+    match sheet {
+        Some( sheet ) if !sheet.tracks.is_empty() => sheet.track_paths(),
+        _ => vec![ path.to_path_buf() ],
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    const SHEET: &str = r#"REM GENRE Jazz
+REM DATE 1959
+PERFORMER "Miles Davis"
+TITLE "Kind of Blue"
+FILE "Kind of Blue.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "So What"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Freddie Freeloader"
+    PERFORMER "Miles Davis Sextet"
+    INDEX 00 09:20:50
+    INDEX 01 09:22:37
+  TRACK 03 AUDIO
+    TITLE "Blue in Green"
+    INDEX 01 19:01:00
+"#;
+
+
+    #[test]
+    fn test_parse_tracks_and_offsets() {
+        let sheet = CueSheet::parse( Path::new( "/music/kob.cue" ), SHEET, Path::new( "/music" ) ).unwrap();
+        assert_eq!( sheet.tracks.len(), 3 );
+
+        let second = sheet.track( 2 ).unwrap();
+        assert_eq!( second.file, PathBuf::from( "/music/Kind of Blue.wav" ) );
+        assert_eq!( second.title.as_deref(), Some( "Freddie Freeloader" ) );
+        assert_eq!( second.performer.as_deref(), Some( "Miles Davis Sextet" ) );
+        assert_eq!( second.album.as_deref(), Some( "Kind of Blue" ) );
+        assert_eq!( second.year, Some( 1959 ) );
+
+        // 37 frames of 1/75s, and the pregap belongs to the track before
+        assert_eq!( second.start, Duration::from_secs( 562 ) + Duration::from_nanos( 37 * 1_000_000_000 / 75 ) );
+        assert_eq!( sheet.tracks[ 0 ].end, Some( second.start ) );
+        assert_eq!( sheet.tracks[ 2 ].end, None );
+
+        // The first track has no performer of its own, so gets the album's
+        let mut metadata = AudioMetadata::default();
+        sheet.tracks[ 0 ].apply_to( &mut metadata );
+        assert_eq!( metadata.artist.as_deref(), Some( "Miles Davis" ) );
+        assert_eq!( metadata.track_number, Some( 1 ) );
+    }
+
+
+    #[test]
+    fn test_track_paths_round_trip() {
+        let path = track_path( Path::new( "/music/Album #2.cue" ), 7 );
+        assert_eq!( path, PathBuf::from( "/music/Album #2.cue#7" ) );
+        assert_eq!( parse_track_path( &path ), Some(( PathBuf::from( "/music/Album #2.cue" ), 7 )) );
+        assert_eq!( parse_track_path( Path::new( "/music/Song #7.flac" ) ), None );
+    }
+
+
+    #[test]
+    fn test_rejects_track_without_start() {
+        let sheet = "FILE \"a.flac\" WAVE\n  TRACK 01 AUDIO\n  TRACK 02 AUDIO\n    INDEX 01 00:10:00\n";
+        let result = CueSheet::parse( Path::new( "a.cue" ), sheet, Path::new( "" ) );
+        assert!( matches!( result, Err( CueError::Invalid { line: 3, .. } ) ) );
+    }
+}
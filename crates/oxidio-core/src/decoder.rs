@@ -2,34 +2,37 @@
 //!
 //! Handles decoding of various audio formats into raw PCM samples.
 
+use std::cell::OnceCell;
 use std::fs::File;
-use std::path::Path;
+use std::path::{ Path, PathBuf };
+use std::time::Duration;
 
 use symphonia::core::audio::{ SampleBuffer, SignalSpec };
-use symphonia::core::codecs::{ Decoder as SymphoniaDecoder, DecoderOptions, CODEC_TYPE_NULL };
+use symphonia::core::codecs::{ CodecParameters, CodecType, Decoder as SymphoniaDecoder, DecoderOptions, CODEC_TYPE_NULL };
 use symphonia::core::formats::{ FormatOptions, FormatReader, SeekMode, SeekTo };
 use symphonia::core::io::{ MediaSourceStream, MediaSourceStreamOptions };
-use symphonia::core::meta::{ MetadataOptions, StandardTagKey };
+use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::ProbedMetadata;
 use symphonia::core::probe::Hint;
-use symphonia::core::units::Time;
+use symphonia::core::units::{ Time, TimeBase };
 use thiserror::Error;
 
+use crate::artwork::Artwork;
+use crate::chapters::{ self, Chapter };
+use crate::metadata::AudioMetadata;
+use crate::remix::ChannelLayout;
 
-/// Audio metadata extracted from the file.
-#[derive( Debug, Clone, Default )]
-pub struct AudioMetadata {
-    pub title: Option<String>,
-    pub artist: Option<String>,
-    pub album: Option<String>,
-    pub album_artist: Option<String>,
-    pub track_number: Option<u32>,
-    pub genre: Option<String>,
-    pub year: Option<u32>,
-    pub codec: Option<String>,
-    pub bitrate: Option<u32>,
+
+/// One of the audio streams in a file, e.g. an alternate language or a commentary track.
+#[derive( Debug, Clone, PartialEq )]
+pub struct AudioStream {
+    /// Track ID within the container
+    pub id: u32,
+    pub codec: String,
+    /// Language code (e.g. "eng"), where the container gives one
+    pub language: Option<String>,
     pub sample_rate: Option<u32>,
-    pub channels: Option<u32>,
+    pub channels: Option<usize>,
 }
 
 
@@ -45,6 +48,9 @@ pub enum DecoderError {
     #[error( "No audio tracks found" )]
     NoAudioTrack,
 
+    #[error( "No audio stream {0}" )]
+    NoSuchStream( usize ),
+
     #[error( "Decoder creation failed: {0}" )]
     DecoderCreation( String ),
 
@@ -62,11 +68,21 @@ pub struct Decoder {
     decoder: Box<dyn SymphoniaDecoder>,
     track_id: u32,
     sample_rate: u32,
+    /// Time base of the track's timestamps, used to report where seeks land
+    time_base: Option<TimeBase>,
     channels: usize,
+    /// Speaker of each channel, from the file where it says so
+    channel_layout: ChannelLayout,
     sample_buf: Option<SampleBuffer<f32>>,
     duration: Option<f64>,
     /// Metadata from probe result (ID3 tags, etc.)
     probe_metadata: ProbedMetadata,
+    path: PathBuf,
+    /// Chapters read from the container, which Symphonia doesn't expose. Read when
+    /// first asked for, as it means going through the tags a second time.
+    chapters: OnceCell<Vec<Chapter>>,
+    /// Every audio stream in the file, in container order
+    streams: Vec<AudioStream>,
 }
 
 
@@ -104,23 +120,31 @@ impl Decoder {
         let probe_metadata = probed.metadata;
         let format_reader = probed.format;
 
-        // Find the first audio track
+        let streams: Vec<AudioStream> = format_reader
+            .tracks()
+            .iter()
+            .filter( |t| t.codec_params.codec != CODEC_TYPE_NULL )
+            .map( |t| AudioStream {
+                id: t.id,
+                codec: codec_name( t.codec_params.codec ),
+                // Matroska marks untagged tracks as undetermined
+                language: t.language.clone().filter( |l| !l.is_empty() && l != "und" ),
+                sample_rate: t.codec_params.sample_rate,
+                channels: t.codec_params.channels.map( |c| c.count() ),
+            })
+            .collect();
+
+        // Play the first audio track until told otherwise
         let track = format_reader
             .tracks()
             .iter()
-            .find( |t| t.codec_params.codec != CODEC_TYPE_NULL )
+            .find( |t| Some( t.id ) == streams.first().map( |s| s.id ) )
             .ok_or( DecoderError::NoAudioTrack )?;
 
         let track_id = track.id;
         let codec_params = &track.codec_params;
-
-        let sample_rate = codec_params.sample_rate.unwrap_or( 44100 );
-        let channels = codec_params.channels.map( |c| c.count() ).unwrap_or( 2 );
-
-        // Calculate duration if available
-        let duration = codec_params.n_frames.map( |frames| {
-            frames as f64 / sample_rate as f64
-        });
+        let time_base = codec_params.time_base;
+        let ( sample_rate, channels, channel_layout, duration ) = stream_format( codec_params );
 
         tracing::info!(
             "Opened audio: {} Hz, {} channels, duration: {:?}s",
@@ -128,22 +152,26 @@ impl Decoder {
             channels,
             duration
         );
+        if streams.len() > 1 {
+            tracing::info!( "Found {} audio streams", streams.len() );
+        }
 
-        // Create the decoder
-        let decoder_opts = DecoderOptions::default();
-        let decoder = symphonia::default::get_codecs()
-            .make( codec_params, &decoder_opts )
-            .map_err( |e| DecoderError::DecoderCreation( e.to_string() ) )?;
+        let decoder = make_decoder( codec_params )?;
 
         Ok( Self {
             format_reader,
             decoder,
             track_id,
             sample_rate,
+            time_base,
             channels,
+            channel_layout,
             sample_buf: None,
             duration,
             probe_metadata,
+            path: path.to_path_buf(),
+            chapters: OnceCell::new(),
+            streams,
         })
     }
 
@@ -160,87 +188,99 @@ impl Decoder {
     }
 
 
+    /// Returns which speaker each channel is meant for.
+    pub fn channel_layout( &self ) -> &ChannelLayout {
+        &self.channel_layout
+    }
+
+
     /// Returns the duration in seconds, if known.
     pub fn duration( &self ) -> Option<f64> {
         self.duration
     }
 
 
+    /// Returns the file's chapters, in order, reading them on the first call.
+    pub fn chapters( &self ) -> &[Chapter] {
+        self.chapters.get_or_init( || {
+            let chapters = chapters::read( &self.path );
+            if !chapters.is_empty() {
+                tracing::info!( "Found {} chapters", chapters.len() );
+            }
+            chapters
+        })
+    }
+
+
+    /// Hands over chapters already read from the same file, so they aren't read again.
+    pub( crate ) fn reuse_chapters( &mut self, chapters: &[Chapter] ) {
+        let _ = self.chapters.set( chapters.to_vec() );
+    }
+
+
+    /// Returns the file's audio streams, in container order.
+    pub fn streams( &self ) -> &[AudioStream] {
+        &self.streams
+    }
+
+
+    /// Returns the index in `streams()` of the stream being decoded.
+    pub fn stream( &self ) -> usize {
+        self.streams.iter().position( |s| s.id == self.track_id ).unwrap_or( 0 )
+    }
+
+
+    /// Switches decoding to stream `index` of `streams()`.
+    ///
+    /// The reader stays where it is, so callers will usually want to seek afterwards
+    /// to line the new stream up.
+    pub fn select_stream( &mut self, index: usize ) -> Result<(), DecoderError> {
+        let id = self.streams.get( index ).ok_or( DecoderError::NoSuchStream( index ) )?.id;
+        let track = self.format_reader
+            .tracks()
+            .iter()
+            .find( |t| t.id == id )
+            .ok_or( DecoderError::NoSuchStream( index ) )?;
+
+        let codec_params = &track.codec_params;
+        self.decoder = make_decoder( codec_params )?;
+        self.time_base = codec_params.time_base;
+        ( self.sample_rate, self.channels, self.channel_layout, self.duration ) = stream_format( codec_params );
+        self.track_id = id;
+        self.sample_buf = None;
+
+        tracing::info!( "Switched to audio stream {} ({} Hz, {} channels)", index, self.sample_rate, self.channels );
+        Ok(())
+    }
+
+
     /// Extracts metadata from the audio file.
     pub fn metadata( &mut self ) -> AudioMetadata {
         let mut meta = AudioMetadata::default();
 
-        // Helper to extract tags from a metadata revision
-        let extract_tags = |meta: &mut AudioMetadata, tags: &[symphonia::core::meta::Tag]| {
-            for tag in tags {
-                if let Some( std_key ) = tag.std_key {
-                    let value = tag.value.to_string();
-                    match std_key {
-                        StandardTagKey::TrackTitle => {
-                            if meta.title.is_none() {
-                                meta.title = Some( value );
-                            }
-                        }
-                        StandardTagKey::Artist => {
-                            if meta.artist.is_none() {
-                                meta.artist = Some( value );
-                            }
-                        }
-                        StandardTagKey::Album => {
-                            if meta.album.is_none() {
-                                meta.album = Some( value );
-                            }
-                        }
-                        StandardTagKey::AlbumArtist => {
-                            if meta.album_artist.is_none() {
-                                meta.album_artist = Some( value );
-                            }
-                        }
-                        StandardTagKey::TrackNumber => {
-                            if meta.track_number.is_none() {
-                                meta.track_number = value.parse().ok();
-                            }
-                        }
-                        StandardTagKey::Genre => {
-                            if meta.genre.is_none() {
-                                meta.genre = Some( value );
-                            }
-                        }
-                        StandardTagKey::Date | StandardTagKey::ReleaseDate => {
-                            if meta.year.is_none() {
-                                // Extract year from date string (e.g., "2023" or "2023-01-15")
-                                if let Some( year_str ) = value.split( '-' ).next() {
-                                    meta.year = year_str.parse().ok();
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        };
-
         // First check probe metadata (ID3 tags, etc.)
         if let Some( metadata_log ) = self.probe_metadata.get() {
             if let Some( metadata_rev ) = metadata_log.current() {
-                extract_tags( &mut meta, metadata_rev.tags() );
+                metadata_rev.tags().iter().for_each( |tag| meta.add_tag( tag ) );
+                meta.artwork.extend( metadata_rev.visuals().iter().filter_map( Artwork::from_visual ) );
             }
         }
 
         // Then check format reader metadata (may have additional tags)
         if let Some( metadata_rev ) = self.format_reader.metadata().current() {
-            extract_tags( &mut meta, metadata_rev.tags() );
+            metadata_rev.tags().iter().for_each( |tag| meta.add_tag( tag ) );
+            meta.artwork.extend( metadata_rev.visuals().iter().filter_map( Artwork::from_visual ) );
         }
 
         // Add audio format information
         meta.sample_rate = Some( self.sample_rate );
         meta.channels = Some( self.channels as u32 );
+        meta.duration = self.duration.map( Duration::from_secs_f64 );
 
         // Get codec and bitrate from track info
         if let Some( track ) = self.format_reader.tracks().iter().find( |t| t.id == self.track_id ) {
             // Get codec name
-            let codec_type = track.codec_params.codec;
-            meta.codec = Some( format!( "{:?}", codec_type ).replace( "CODEC_TYPE_", "" ) );
+            meta.codec = Some( codec_name( track.codec_params.codec ) );
 
             // Get bitrate if available
             if let Some( bit_rate ) = track.codec_params.bits_per_sample {
@@ -313,19 +353,57 @@ impl Decoder {
 
 
     /// Seeks to a position in seconds.
-    pub fn seek( &mut self, position_secs: f64 ) -> Result<(), DecoderError> {
+    ///
+    /// Returns the position the reader actually landed on, which can be slightly
+    /// before the requested one depending on the format.
+    pub fn seek( &mut self, position_secs: f64 ) -> Result<f64, DecoderError> {
         let seek_to = SeekTo::Time {
             time: Time::from( position_secs ),
             track_id: Some( self.track_id ),
         };
 
-        self.format_reader
+        let seeked = self.format_reader
             .seek( SeekMode::Accurate, seek_to )
             .map_err( |e| DecoderError::Seek( e.to_string() ) )?;
 
         // Reset decoder state after seek
         self.decoder.reset();
 
-        Ok(())
+        let landed = match self.time_base {
+            Some( time_base ) => {
+                let time = time_base.calc_time( seeked.actual_ts );
+                time.seconds as f64 + time.frac
+            }
+            None => seeked.actual_ts as f64 / self.sample_rate as f64,
+        };
+
+        Ok( landed )
     }
 }
+
+
+/// Returns the (sample rate, channel count, channel layout, duration in seconds) of a stream.
+fn stream_format( codec_params: &CodecParameters ) -> ( u32, usize, ChannelLayout, Option<f64> ) {
+    let sample_rate = codec_params.sample_rate.unwrap_or( 44100 );
+    let channels = codec_params.channels.map( |c| c.count() ).unwrap_or( 2 );
+    let channel_layout = codec_params.channels
+        .map( ChannelLayout::from )
+        .unwrap_or_else( || ChannelLayout::default_for( channels ) );
+    let duration = codec_params.n_frames.map( |frames| frames as f64 / sample_rate as f64 );
+    ( sample_rate, channels, channel_layout, duration )
+}
+
+
+fn make_decoder( codec_params: &CodecParameters ) -> Result<Box<dyn SymphoniaDecoder>, DecoderError> {
+    symphonia::default::get_codecs()
+        .make( codec_params, &DecoderOptions::default() )
+        .map_err( |e| DecoderError::DecoderCreation( e.to_string() ) )
+}
+
+
+/// Returns the short name of a codec, e.g. "AAC" or "FLAC".
+fn codec_name( codec: CodecType ) -> String {
+    symphonia::default::get_codecs()
+        .get_codec( codec )
+        .map_or_else( || format!( "{:?}", codec ), |c| c.short_name.to_uppercase() )
+}
@@ -0,0 +1,417 @@
+//! Audio processing chain
+//!
+//! Defines the `AudioProcessor` trait and the ordered chain of stages that
+//! decoded samples pass through on their way to the output.
+
+use std::sync::{ Arc, RwLock };
+use std::time::Duration;
+
+use crate::remix::{ ChannelLayout, MixMatrix };
+
+
+/// A single processing stage working on interleaved f32 samples.
+pub trait AudioProcessor: Send {
+    /// Short name identifying the stage within a chain.
+    fn name( &self ) -> &'static str;
+
+
+    /// Processes a chunk of interleaved samples in place.
+    ///
+    /// `channels` and `sample_rate` describe the samples as they arrive. Stages that
+    /// change the amount of audio (resampling, remixing) may resize `samples`.
+    fn process( &mut self, samples: &mut Vec<f32>, channels: usize, sample_rate: u32 );
+
+
+    /// Number of channels this stage produces for `channels` input channels.
+    fn output_channels( &self, channels: usize ) -> usize {
+        channels
+    }
+
+
+    /// Sample rate this stage produces for input at `sample_rate`.
+    fn output_rate( &self, sample_rate: u32 ) -> u32 {
+        sample_rate
+    }
+
+
+    /// Returns any samples held back internally, at the end of a stream.
+    fn flush( &mut self, _channels: usize, _sample_rate: u32 ) -> Vec<f32> {
+        Vec::new()
+    }
+
+
+    /// Drops internal state such as filter history, e.g. after a seek.
+    fn reset( &mut self ) {}
+}
+
+
+/// Second order IIR filter section (transposed direct form II).
+#[derive( Debug, Clone, Copy )]
+pub(crate) struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+
+impl Biquad {
+    /// Creates a filter from its numerator (`b`) and denominator (`a`) coefficients.
+    pub(crate) fn new( b: [f64; 3], a: [f64; 3] ) -> Self {
+        let mut filter = Self { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0, z1: 0.0, z2: 0.0 };
+        filter.set_coefficients( b, a );
+        filter
+    }
+
+
+    /// Changes the response without clearing the filter history.
+    pub(crate) fn set_coefficients( &mut self, b: [f64; 3], a: [f64; 3] ) {
+        self.b0 = b[ 0 ] / a[ 0 ];
+        self.b1 = b[ 1 ] / a[ 0 ];
+        self.b2 = b[ 2 ] / a[ 0 ];
+        self.a1 = a[ 1 ] / a[ 0 ];
+        self.a2 = a[ 2 ] / a[ 0 ];
+    }
+
+
+    pub(crate) fn process( &mut self, x: f64 ) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+
+
+    pub(crate) fn reset( &mut self ) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+
+/// An ordered list of processing stages.
+///
+/// Stages can be added, replaced and removed at any time; the change applies
+/// from the next chunk of audio.
+#[derive( Default )]
+pub struct DspChain {
+    stages: Vec<Box<dyn AudioProcessor>>,
+}
+
+
+impl DspChain {
+    /// Creates an empty chain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+
+    /// Appends a stage to the end of the chain.
+    pub fn push( &mut self, stage: Box<dyn AudioProcessor> ) {
+        self.stages.push( stage );
+    }
+
+
+    /// Inserts a stage at `index`, clamped to the end of the chain.
+    pub fn insert( &mut self, index: usize, stage: Box<dyn AudioProcessor> ) {
+        let index = index.min( self.stages.len() );
+        self.stages.insert( index, stage );
+    }
+
+
+    /// Removes the first stage called `name`, returning it.
+    pub fn remove( &mut self, name: &str ) -> Option<Box<dyn AudioProcessor>> {
+        let index = self.position( name )?;
+        Some( self.stages.remove( index ) )
+    }
+
+
+    /// Replaces the stage with the same name in place, or appends it if there is none.
+    pub fn replace( &mut self, stage: Box<dyn AudioProcessor> ) {
+        match self.position( stage.name() ) {
+            Some( index ) => self.stages[ index ] = stage,
+            None => self.stages.push( stage ),
+        }
+    }
+
+
+    /// Returns the index of the first stage called `name`.
+    pub fn position( &self, name: &str ) -> Option<usize> {
+        self.stages.iter().position( |s| s.name() == name )
+    }
+
+
+    /// Returns true if the chain has a stage called `name`.
+    pub fn contains( &self, name: &str ) -> bool {
+        self.position( name ).is_some()
+    }
+
+
+    /// Names of the stages, in processing order.
+    pub fn names( &self ) -> Vec<&'static str> {
+        self.stages.iter().map( |s| s.name() ).collect()
+    }
+
+
+    /// Returns the number of stages.
+    pub fn len( &self ) -> usize {
+        self.stages.len()
+    }
+
+
+    /// Returns true if the chain has no stages.
+    pub fn is_empty( &self ) -> bool {
+        self.stages.is_empty()
+    }
+
+
+    /// Runs `samples` through every stage in order.
+    pub fn process( &mut self, samples: &mut Vec<f32>, channels: usize, sample_rate: u32 ) {
+        let ( mut channels, mut sample_rate ) = ( channels, sample_rate );
+        for stage in &mut self.stages {
+            stage.process( samples, channels, sample_rate );
+            channels = stage.output_channels( channels );
+            sample_rate = stage.output_rate( sample_rate );
+        }
+    }
+
+
+    /// Flushes every stage, passing each stage's leftovers through the stages after it.
+    pub fn flush( &mut self, channels: usize, sample_rate: u32 ) -> Vec<f32> {
+        let ( mut channels, mut sample_rate ) = ( channels, sample_rate );
+        let mut pending = Vec::new();
+        for stage in &mut self.stages {
+            if !pending.is_empty() {
+                stage.process( &mut pending, channels, sample_rate );
+            }
+            pending.extend( stage.flush( channels, sample_rate ) );
+            channels = stage.output_channels( channels );
+            sample_rate = stage.output_rate( sample_rate );
+        }
+        pending
+    }
+
+
+    /// Resets the state of every stage.
+    pub fn reset( &mut self ) {
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+    }
+
+
+    /// Channel count after the whole chain, for `channels` input channels.
+    pub fn output_channels( &self, channels: usize ) -> usize {
+        self.stages.iter().fold( channels, |ch, s| s.output_channels( ch ) )
+    }
+
+
+    /// Sample rate after the whole chain, for input at `sample_rate`.
+    pub fn output_rate( &self, sample_rate: u32 ) -> u32 {
+        self.stages.iter().fold( sample_rate, |rate, s| s.output_rate( rate ) )
+    }
+}
+
+
+/// Applies the user volume (0.0 = mute, 1.0 = normal, >1.0 = boost).
+pub struct Volume {
+    level: Arc<RwLock<f32>>,
+    /// How long to take moving to a new level (None = switch instantly)
+    fade: Option<Arc<RwLock<Duration>>>,
+    /// Gain being applied
+    current: Option<f32>,
+    /// Level being ramped to, and how much the gain moves towards it per frame
+    ramp: Option<( f32, f32 )>,
+}
+
+
+impl Volume {
+    /// Creates a volume stage that follows a shared level.
+    pub fn new( level: Arc<RwLock<f32>> ) -> Self {
+        Self { level, fade: None, current: None, ramp: None }
+    }
+
+
+    /// Creates a volume stage that ramps to a new level over the shared `fade` length,
+    /// so volume changes don't click.
+    pub fn with_fade( level: Arc<RwLock<f32>>, fade: Arc<RwLock<Duration>> ) -> Self {
+        Self { level, fade: Some( fade ), current: None, ramp: None }
+    }
+}
+
+
+impl AudioProcessor for Volume {
+    fn name( &self ) -> &'static str {
+        "volume"
+    }
+
+
+    fn process( &mut self, samples: &mut Vec<f32>, channels: usize, sample_rate: u32 ) {
+        let level = *self.level.read().unwrap();
+        let mut current = self.current.unwrap_or( level );
+
+        if current != level {
+            // Start a new ramp whenever the level changes, from wherever the gain has got to
+            let step = match self.ramp {
+                Some( ( target, step ) ) if target == level => step,
+                _ => {
+                    let fade = self.fade.as_ref().map_or( Duration::ZERO, |fade| *fade.read().unwrap() );
+                    let frames = ( fade.as_secs_f32() * sample_rate as f32 ).max( 1.0 );
+                    let step = ( level - current ).abs() / frames;
+                    self.ramp = Some( ( level, step ) );
+                    step
+                }
+            };
+            for frame in samples.chunks_mut( channels.max( 1 ) ) {
+                current = if level > current {
+                    ( current + step ).min( level )
+                } else {
+                    ( current - step ).max( level )
+                };
+                frame.iter_mut().for_each( |s| *s *= current );
+            }
+        } else if level != 1.0 {
+            for sample in samples.iter_mut() {
+                *sample *= level;
+            }
+        }
+
+        if current == level {
+            self.ramp = None;
+        }
+        self.current = Some( current );
+    }
+
+
+    fn reset( &mut self ) {
+        self.current = None;
+        self.ramp = None;
+    }
+}
+
+
+/// Mixes audio from the source's speaker layout into the output's (see `MixMatrix`).
+pub struct Remix {
+    channels: usize,
+    /// Layout of the incoming audio. None assumes the usual layout for its channel count.
+    source: Option<ChannelLayout>,
+    /// Whether to scale the mix down so it can't clip
+    normalize: Arc<RwLock<bool>>,
+    /// Matrix for the current input channel count and normalize setting
+    matrix: Option<( usize, bool, MixMatrix )>,
+}
+
+
+impl Remix {
+    /// Creates a stage producing `channels` output channels from audio in the usual
+    /// layout for its channel count.
+    pub fn new( channels: usize ) -> Self {
+        Self {
+            channels,
+            source: None,
+            normalize: Arc::new( RwLock::new( false ) ),
+            matrix: None,
+        }
+    }
+
+
+    /// Creates a stage mixing audio laid out as `source` into `channels` output
+    /// channels, normalizing the mix while the shared flag is set.
+    pub fn with_layout( source: ChannelLayout, channels: usize, normalize: Arc<RwLock<bool>> ) -> Self {
+        Self {
+            channels,
+            source: Some( source ),
+            normalize,
+            matrix: None,
+        }
+    }
+}
+
+
+impl AudioProcessor for Remix {
+    fn name( &self ) -> &'static str {
+        "remix"
+    }
+
+
+    fn process( &mut self, samples: &mut Vec<f32>, channels: usize, _sample_rate: u32 ) {
+        if channels == self.channels || channels == 0 {
+            return;
+        }
+
+        let normalize = *self.normalize.read().unwrap();
+        let stale = self.matrix.as_ref().is_none_or( |( c, n, _ )| *c != channels || *n != normalize );
+        if stale {
+            let source = self.source.clone()
+                .filter( |layout| layout.channels() == channels )
+                .unwrap_or_else( || ChannelLayout::default_for( channels ) );
+            let output = ChannelLayout::default_for( self.channels );
+            self.matrix = Some(( channels, normalize, MixMatrix::new( &source, &output, normalize ) ));
+        }
+
+        if let Some(( _, _, matrix )) = &self.matrix {
+            *samples = matrix.apply( samples );
+        }
+    }
+
+
+    fn output_channels( &self, _channels: usize ) -> usize {
+        self.channels
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_chain_runs_stages_in_order() {
+        let mut chain = DspChain::new();
+        chain.push( Box::new( Remix::new( 2 ) ) );
+        chain.push( Box::new( Volume::new( Arc::new( RwLock::new( 0.5 ) ) ) ) );
+        assert_eq!( chain.names(), vec![ "remix", "volume" ] );
+        assert_eq!( chain.output_channels( 1 ), 2 );
+
+        let mut samples = vec![ 1.0, 0.5 ];
+        chain.process( &mut samples, 1, 44100 );
+        assert_eq!( samples, vec![ 0.5, 0.5, 0.25, 0.25 ] );
+    }
+
+
+    #[test]
+    fn test_replace_keeps_position() {
+        let mut chain = DspChain::new();
+        chain.push( Box::new( Volume::new( Arc::new( RwLock::new( 1.0 ) ) ) ) );
+        chain.push( Box::new( Remix::new( 2 ) ) );
+        chain.replace( Box::new( Volume::new( Arc::new( RwLock::new( 0.0 ) ) ) ) );
+        assert_eq!( chain.names(), vec![ "volume", "remix" ] );
+
+        let mut samples = vec![ 1.0, 1.0 ];
+        chain.process( &mut samples, 2, 44100 );
+        assert_eq!( samples, vec![ 0.0, 0.0 ] );
+        assert!( chain.remove( "volume" ).is_some() );
+        assert!( !chain.contains( "volume" ) );
+    }
+
+
+    #[test]
+    fn test_volume_ramps_to_new_level() {
+        let level = Arc::new( RwLock::new( 1.0 ) );
+        let mut volume = Volume::with_fade( Arc::clone( &level ), Arc::new( RwLock::new( Duration::from_millis( 4 ) ) ) );
+
+        let mut samples = vec![ 1.0; 8 ];
+        volume.process( &mut samples, 2, 1000 );
+        assert_eq!( samples, vec![ 1.0; 8 ] );
+
+        // Falls by a quarter per frame over the 4ms fade, then holds
+        *level.write().unwrap() = 0.0;
+        let mut samples = vec![ 1.0; 12 ];
+        volume.process( &mut samples, 2, 1000 );
+        assert_eq!( samples, vec![ 0.75, 0.75, 0.5, 0.5, 0.25, 0.25, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0 ] );
+    }
+}
@@ -0,0 +1,319 @@
+//! Graphic / parametric equalizer
+//!
+//! Biquad peaking and shelf filters applied per channel, with built-in presets.
+
+use std::f64::consts::PI;
+use std::sync::{ Arc, RwLock };
+
+use crate::dsp::{ AudioProcessor, Biquad };
+
+
+/// Largest boost or cut a band can apply, in dB.
+pub const MAX_GAIN_DB: f32 = 12.0;
+
+/// Centre frequencies of the standard 10-band layout.
+pub const BAND_FREQUENCIES: [f32; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// Q giving each band of the 10-band layout roughly an octave of width.
+pub const DEFAULT_Q: f32 = 1.41;
+
+
+/// Shape of an equalizer band.
+#[derive( Debug, Clone, Copy, PartialEq, Eq, Default )]
+#[cfg_attr( feature = "serde", derive( serde::Serialize, serde::Deserialize ) )]
+#[cfg_attr( feature = "serde", serde( rename_all = "kebab-case" ) )]
+pub enum FilterKind {
+    /// Boosts or cuts everything below the frequency
+    LowShelf,
+
+    /// Boosts or cuts around the frequency (default)
+    #[default]
+    Peaking,
+
+    /// Boosts or cuts everything above the frequency
+    HighShelf,
+}
+
+
+impl FilterKind {
+    /// Returns the display name of the filter shape.
+    pub fn name( &self ) -> &'static str {
+        match self {
+            FilterKind::LowShelf => "low shelf",
+            FilterKind::Peaking => "peaking",
+            FilterKind::HighShelf => "high shelf",
+        }
+    }
+}
+
+
+/// A single equalizer band.
+#[derive( Debug, Clone, Copy, PartialEq )]
+#[cfg_attr( feature = "serde", derive( serde::Serialize, serde::Deserialize ) )]
+pub struct EqBand {
+    pub kind: FilterKind,
+    /// Centre (peaking) or corner (shelf) frequency in Hz
+    pub frequency: f32,
+    /// Boost or cut in dB
+    pub gain_db: f32,
+    /// Bandwidth; higher is narrower
+    pub q: f32,
+}
+
+
+impl EqBand {
+    /// Computes the RBJ cookbook coefficients (b, a) for this band at `sample_rate`.
+    fn coefficients( &self, sample_rate: u32 ) -> ( [f64; 3], [f64; 3] ) {
+        let rate = sample_rate as f64;
+        let frequency = ( self.frequency as f64 ).clamp( 10.0, rate * 0.49 );
+        let a = 10f64.powf( self.gain_db as f64 / 40.0 );
+        let w0 = 2.0 * PI * frequency / rate;
+        let ( sin, cos ) = w0.sin_cos();
+        let alpha = sin / ( 2.0 * ( self.q as f64 ).max( 0.1 ) );
+
+        match self.kind {
+            FilterKind::Peaking => (
+                [ 1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a ],
+                [ 1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a ],
+            ),
+            FilterKind::LowShelf => {
+                let k = 2.0 * a.sqrt() * alpha;
+                (
+                    [
+                        a * ( ( a + 1.0 ) - ( a - 1.0 ) * cos + k ),
+                        2.0 * a * ( ( a - 1.0 ) - ( a + 1.0 ) * cos ),
+                        a * ( ( a + 1.0 ) - ( a - 1.0 ) * cos - k ),
+                    ],
+                    [
+                        ( a + 1.0 ) + ( a - 1.0 ) * cos + k,
+                        -2.0 * ( ( a - 1.0 ) + ( a + 1.0 ) * cos ),
+                        ( a + 1.0 ) + ( a - 1.0 ) * cos - k,
+                    ],
+                )
+            }
+            FilterKind::HighShelf => {
+                let k = 2.0 * a.sqrt() * alpha;
+                (
+                    [
+                        a * ( ( a + 1.0 ) + ( a - 1.0 ) * cos + k ),
+                        -2.0 * a * ( ( a - 1.0 ) + ( a + 1.0 ) * cos ),
+                        a * ( ( a + 1.0 ) + ( a - 1.0 ) * cos - k ),
+                    ],
+                    [
+                        ( a + 1.0 ) - ( a - 1.0 ) * cos + k,
+                        2.0 * ( ( a - 1.0 ) - ( a + 1.0 ) * cos ),
+                        ( a + 1.0 ) - ( a - 1.0 ) * cos - k,
+                    ],
+                )
+            }
+        }
+    }
+}
+
+
+/// Builds the standard 10-band layout with the given gains.
+///
+/// The outer bands are shelves, the rest peaking filters.
+pub fn ten_band( gains: [f32; 10] ) -> Vec<EqBand> {
+    BAND_FREQUENCIES.iter().zip( gains ).enumerate().map( |( i, ( &frequency, gain_db ) )| {
+        let kind = match i {
+            0 => FilterKind::LowShelf,
+            9 => FilterKind::HighShelf,
+            _ => FilterKind::Peaking,
+        };
+        EqBand { kind, frequency, gain_db, q: DEFAULT_Q }
+    }).collect()
+}
+
+
+/// Equalizer configuration.
+#[derive( Debug, Clone, PartialEq )]
+#[cfg_attr( feature = "serde", derive( serde::Serialize, serde::Deserialize ) )]
+pub struct EqualizerSettings {
+    pub enabled: bool,
+    pub bands: Vec<EqBand>,
+}
+
+
+impl Default for EqualizerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bands: ten_band( [ 0.0; 10 ] ),
+        }
+    }
+}
+
+
+/// A named set of bands.
+#[derive( Debug, Clone, PartialEq )]
+#[cfg_attr( feature = "serde", derive( serde::Serialize, serde::Deserialize ) )]
+pub struct EqPreset {
+    pub name: String,
+    pub bands: Vec<EqBand>,
+}
+
+
+impl EqPreset {
+    /// Returns the presets that ship with oxidio.
+    pub fn builtin() -> Vec<EqPreset> {
+        let preset = |name: &str, gains| EqPreset { name: name.to_string(), bands: ten_band( gains ) };
+        vec![
+            preset( "Flat", [ 0.0; 10 ] ),
+            preset( "Rock", [ 5.0, 4.0, 3.0, 1.0, -1.0, -1.0, 1.0, 3.0, 4.0, 5.0 ] ),
+            preset( "Classical", [ 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -2.0, -3.0, -3.0, -4.0 ] ),
+            preset( "Bass Boost", [ 7.0, 6.0, 5.0, 3.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0 ] ),
+        ]
+    }
+
+
+    /// Finds a preset by name, ignoring case, spaces, dashes and underscores.
+    pub fn find<'a>( presets: &'a [EqPreset], name: &str ) -> Option<&'a EqPreset> {
+        let normalize = |s: &str| -> String {
+            s.chars()
+                .filter( |c| !matches!( c, ' ' | '-' | '_' ) )
+                .flat_map( char::to_lowercase )
+                .collect()
+        };
+        let wanted = normalize( name );
+        presets.iter().find( |p| normalize( &p.name ) == wanted )
+    }
+}
+
+
+/// Processing stage applying the shared equalizer settings.
+///
+/// Setting changes are picked up on the next chunk without resetting the filters,
+/// so bands can be adjusted while playing.
+pub struct Equalizer {
+    settings: Arc<RwLock<EqualizerSettings>>,
+    /// Settings, sample rate and channel count the filters were built for
+    applied: Option<( EqualizerSettings, u32, usize )>,
+    /// One filter per band, per channel
+    filters: Vec<Vec<Biquad>>,
+}
+
+
+impl Equalizer {
+    /// Creates an equalizer stage following shared settings.
+    pub fn new( settings: Arc<RwLock<EqualizerSettings>> ) -> Self {
+        Self {
+            settings,
+            applied: None,
+            filters: Vec::new(),
+        }
+    }
+
+
+    /// Rebuilds the filter coefficients if the settings or format changed.
+    fn update( &mut self, settings: &EqualizerSettings, channels: usize, sample_rate: u32 ) {
+        if let Some(( applied, rate, ch )) = &self.applied {
+            if applied == settings && *rate == sample_rate && *ch == channels {
+                return;
+            }
+        }
+
+        let coefficients: Vec<_> = settings.bands.iter().map( |b| b.coefficients( sample_rate ) ).collect();
+        let same_layout = self.filters.len() == channels
+            && self.filters.first().is_some_and( |f| f.len() == coefficients.len() );
+
+        if same_layout {
+            // Keep the filter history so adjustments don't click
+            for channel in &mut self.filters {
+                for ( filter, ( b, a ) ) in channel.iter_mut().zip( &coefficients ) {
+                    filter.set_coefficients( *b, *a );
+                }
+            }
+        } else {
+            self.filters = ( 0..channels )
+                .map( |_| coefficients.iter().map( |( b, a )| Biquad::new( *b, *a ) ).collect() )
+                .collect();
+        }
+
+        self.applied = Some(( settings.clone(), sample_rate, channels ));
+    }
+}
+
+
+impl AudioProcessor for Equalizer {
+    fn name( &self ) -> &'static str {
+        "equalizer"
+    }
+
+
+    fn process( &mut self, samples: &mut Vec<f32>, channels: usize, sample_rate: u32 ) {
+        let settings = Arc::clone( &self.settings );
+        let settings = settings.read().unwrap();
+        if !settings.enabled || channels == 0 {
+            // Start from a clean state when switched back on
+            self.applied = None;
+            self.filters.clear();
+            return;
+        }
+
+        self.update( &settings, channels, sample_rate );
+
+        for frame in samples.chunks_exact_mut( channels ) {
+            for ( sample, filters ) in frame.iter_mut().zip( &mut self.filters ) {
+                let mut value = *sample as f64;
+                for filter in filters.iter_mut() {
+                    value = filter.process( value );
+                }
+                *sample = value as f32;
+            }
+        }
+    }
+
+
+    fn reset( &mut self ) {
+        for filter in self.filters.iter_mut().flatten() {
+            filter.reset();
+        }
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    /// Runs a sine through the equalizer and returns its peak amplitude after settling.
+    fn sine_peak( settings: EqualizerSettings, frequency: f32 ) -> f32 {
+        let rate = 48000;
+        let mut samples: Vec<f32> = ( 0..rate )
+            .map( |i| ( 2.0 * std::f32::consts::PI * frequency * i as f32 / rate as f32 ).sin() )
+            .collect();
+
+        let mut eq = Equalizer::new( Arc::new( RwLock::new( settings ) ) );
+        eq.process( &mut samples, 1, rate );
+        samples[ rate as usize / 2.. ].iter().fold( 0.0f32, |m, s| m.max( s.abs() ) )
+    }
+
+
+    #[test]
+    fn test_flat_is_transparent() {
+        let settings = EqualizerSettings { enabled: true, bands: ten_band( [ 0.0; 10 ] ) };
+        assert!(( sine_peak( settings, 1000.0 ) - 1.0 ).abs() < 1e-3 );
+    }
+
+
+    #[test]
+    fn test_peaking_band_boosts_centre() {
+        let mut gains = [ 0.0; 10 ];
+        gains[ 5 ] = 6.0;
+        let settings = EqualizerSettings { enabled: true, bands: ten_band( gains ) };
+        let gain_db = 20.0 * sine_peak( settings, 1000.0 ).log10();
+        assert!(( gain_db - 6.0 ).abs() < 0.5, "{}", gain_db );
+    }
+
+
+    #[test]
+    fn test_find_preset_loosely() {
+        let presets = EqPreset::builtin();
+        assert_eq!( EqPreset::find( &presets, "bass-boost" ).map( |p| p.name.as_str() ), Some( "Bass Boost" ) );
+        assert!( EqPreset::find( &presets, "jazz" ).is_none() );
+    }
+}
@@ -3,15 +3,46 @@
 //! This crate provides the core functionality for audio playback,
 //! including decoding, output, playlist management, and library scanning.
 
+pub mod artwork;
+pub mod bookmarks;
+pub mod chapters;
 pub mod command;
+pub mod crossfade;
+pub mod cue;
 pub mod decoder;
+pub mod dsp;
+pub mod equalizer;
 pub mod library;
+pub mod loudness;
+pub mod metadata;
 pub mod output;
 pub mod player;
 pub mod playlist;
+pub mod remix;
+pub mod replaygain;
+pub mod resample;
+pub mod sink;
+pub mod spectrum;
+pub mod timestretch;
+pub mod volume;
 
+pub use artwork::{ Artwork, PictureType };
+pub use bookmarks::{ Bookmark, Bookmarks };
+pub use chapters::Chapter;
 pub use command::{ Command, CommandError };
-pub use decoder::AudioMetadata;
-pub use output::VIS_BARS;
+pub use crossfade::{ Crossfade, CrossfadeCurve };
+pub use cue::{ CueSheet, CueTrack };
+pub use decoder::AudioStream;
+pub use dsp::{ AudioProcessor, DspChain };
+pub use equalizer::{ EqBand, EqPreset, Equalizer, EqualizerSettings, FilterKind };
+pub use loudness::{ Loudness, LoudnessCache };
+pub use metadata::{ AudioMetadata, MusicBrainzIds };
+pub use output::{ list_output_devices, OutputDeviceId, OutputDeviceInfo };
 pub use player::Player;
 pub use playlist::{ Playlist, PlaylistError, RepeatMode, SessionState };
+pub use remix::{ ChannelLayout, MixMatrix, Speaker };
+pub use replaygain::{ ReplayGain, ReplayGainMode, ReplayGainSettings };
+pub use resample::{ ResampleQuality, ResampleSettings, SincWindow };
+pub use sink::{ AudioSink, NullSink, Pacing, WavSink };
+pub use spectrum::{ Spectrum, SpectrumSettings };
+pub use volume::{ Limiter, MAX_VOLUME_DB, MIN_VOLUME_DB };
@@ -7,9 +7,12 @@ use std::path::{ Path, PathBuf };
 
 use thiserror::Error;
 
+use crate::cue::{ self, CueSheet };
+use crate::metadata::AudioMetadata;
+
 
 /// Supported audio file extensions.
-const SUPPORTED_EXTENSIONS: &[&str] = &[
+pub(crate) const SUPPORTED_EXTENSIONS: &[&str] = &[
     "mp3", "flac", "ogg", "wav", "m4a", "aac", "opus", "wma", "aiff", "alac",
 ];
 
@@ -28,24 +31,11 @@ pub enum LibraryError {
 }
 
 
-/// Track metadata.
-#[derive( Debug, Clone, Default )]
-pub struct TrackMetadata {
-    pub title: Option<String>,
-    pub artist: Option<String>,
-    pub album: Option<String>,
-    pub track_number: Option<u32>,
-    pub duration_secs: Option<f64>,
-    pub year: Option<i32>,
-    pub genre: Option<String>,
-}
-
-
 /// A scanned audio file.
 #[derive( Debug, Clone )]
 pub struct ScannedTrack {
     pub path: PathBuf,
-    pub metadata: TrackMetadata,
+    pub metadata: AudioMetadata,
 }
 
 
@@ -130,6 +120,8 @@ impl LibraryScanner {
             Err( e ) => return Err( LibraryError::Io( e ) ),
         };
 
+        let mut files = Vec::new();
+        let mut sheets = Vec::new();
         for entry in entries.flatten() {
             let path = entry.path();
 
@@ -137,18 +129,45 @@ impl LibraryScanner {
                 // Recurse into subdirectories
                 self.scan_recursive( &path, tracks )?;
             } else if Self::is_audio_file( &path ) {
-                // Found an audio file
-                tracks.push( ScannedTrack {
-                    path,
-                    metadata: TrackMetadata::default(), // TODO: Read actual metadata
-                } );
+                files.push( path );
+            } else if cue::is_cue_file( &path ) {
+                match CueSheet::load( &path ) {
+                    Ok( sheet ) => sheets.push( sheet ),
+                    Err( e ) => tracing::warn!( "Skipping cue sheet {:?}: {}", path, e ),
+                }
             }
         }
 
+        // Files split up by a cue sheet are listed as the sheet's tracks instead
+        files.retain( |path| !sheets.iter().any( |sheet| sheet.covers( path ) ) );
+        for path in files {
+            tracks.push( ScannedTrack {
+                path,
+                metadata: AudioMetadata::default(), // TODO: Read actual metadata
+            } );
+        }
+        for sheet in &sheets {
+            tracks.extend( Self::cue_tracks( sheet ) );
+        }
+
         Ok(())
     }
 
 
+    /// Lists the virtual tracks of a cue sheet, with what the sheet says about them.
+    fn cue_tracks( sheet: &CueSheet ) -> impl Iterator<Item = ScannedTrack> + '_ {
+        sheet.tracks.iter().map( |track| {
+            let mut metadata = AudioMetadata {
+                track_total: Some( sheet.tracks.len() as u32 ),
+                duration: track.duration(),
+                ..AudioMetadata::default()
+            };
+            track.apply_to( &mut metadata );
+            ScannedTrack { path: cue::track_path( &sheet.path, track.number ), metadata }
+        } )
+    }
+
+
     /// Checks if a file has a supported audio extension.
     fn is_audio_file( path: &Path ) -> bool {
         path.extension()
@@ -0,0 +1,480 @@
+//! EBU R128 loudness measurement
+//!
+//! Measures the integrated loudness of tracks (ITU-R BS.1770) so that files without
+//! ReplayGain tags can still be normalized, and caches the results per file.
+
+use std::collections::{ HashMap, VecDeque };
+use std::fs::{ self, File };
+use std::io::{ BufRead, BufReader, BufWriter, Write };
+use std::path::{ Path, PathBuf };
+use std::sync::Mutex;
+use std::time::{ Duration, Instant, UNIX_EPOCH };
+
+use crate::cue::CueTrack;
+use crate::dsp::Biquad;
+
+
+/// Default normalization target, matching the ReplayGain 2.0 reference level.
+pub const DEFAULT_TARGET_LUFS: f32 = -18.0;
+
+/// Blocks quieter than this are ignored entirely (absolute gate).
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+
+/// Blocks more than this far below the ungated loudness are ignored (relative gate).
+const RELATIVE_GATE_LU: f64 = 10.0;
+
+/// How long new measurements may wait before the cache is written out.
+const SAVE_INTERVAL: Duration = Duration::from_secs( 300 );
+
+
+/// Measured loudness of a file.
+#[derive( Debug, Clone, Copy, PartialEq )]
+pub struct Loudness {
+    /// Integrated loudness in LUFS
+    pub integrated_lufs: f32,
+    /// Sample peak as a linear value (1.0 = full scale)
+    pub peak: f32,
+}
+
+
+impl Loudness {
+    /// Returns the gain in dB that brings the track to `target_lufs`.
+    pub fn gain_to( &self, target_lufs: f32 ) -> f32 {
+        target_lufs - self.integrated_lufs
+    }
+}
+
+
+/// Builds the two K-weighting stages (high shelf, then high-pass) for a sample rate.
+fn k_weighting( sample_rate: u32 ) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    // Stage 1: high shelf modelling the acoustic effect of the head
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = ( std::f64::consts::PI * f0 / rate ).tan();
+    let vh = 10f64.powf( gain_db / 20.0 );
+    let vb = vh.powf( 0.4996667741545416 );
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [ ( vh + vb * k / q + k * k ) / a0, 2.0 * ( k * k - vh ) / a0, ( vh - vb * k / q + k * k ) / a0 ],
+        [ 1.0, 2.0 * ( k * k - 1.0 ) / a0, ( 1.0 - k / q + k * k ) / a0 ],
+    );
+
+    // Stage 2: RLB high-pass
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = ( std::f64::consts::PI * f0 / rate ).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [ 1.0, -2.0, 1.0 ],
+        [ 1.0, 2.0 * ( k * k - 1.0 ) / a0, ( 1.0 - k / q + k * k ) / a0 ],
+    );
+
+    [ shelf, high_pass ]
+}
+
+
+/// Converts a mean square power to loudness in LUFS.
+fn power_to_lufs( power: f64 ) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+
+/// Streaming integrated loudness meter.
+///
+/// Feed it interleaved samples with `process`, then call `integrated` once the
+/// whole track has been seen.
+pub struct LoudnessMeter {
+    channels: usize,
+    filters: Vec<[Biquad; 2]>,
+    /// Per-channel weights (surround channels count more, LFE is ignored)
+    weights: Vec<f64>,
+    /// Frames in a 100ms step; gating blocks are four steps long
+    step_frames: usize,
+    /// Weighted sum of squared samples in the current step
+    step_sum: f64,
+    step_position: usize,
+    /// Mean power of the most recent steps
+    recent_steps: VecDeque<f64>,
+    /// Mean power of every 400ms block (75% overlap)
+    blocks: Vec<f64>,
+    peak: f32,
+}
+
+
+impl LoudnessMeter {
+    /// Creates a meter for audio with the given format.
+    pub fn new( sample_rate: u32, channels: usize ) -> Self {
+        let channels = channels.max( 1 );
+        let weights = ( 0..channels ).map( |ch| {
+            match ( channels >= 6, ch ) {
+                ( true, 3 ) => 0.0,
+                ( true, ch ) if ch >= 4 => 1.41,
+                _ => 1.0,
+            }
+        }).collect();
+
+        Self {
+            channels,
+            filters: vec![ k_weighting( sample_rate ); channels ],
+            weights,
+            step_frames: ( sample_rate as usize / 10 ).max( 1 ),
+            step_sum: 0.0,
+            step_position: 0,
+            recent_steps: VecDeque::with_capacity( 4 ),
+            blocks: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+
+    /// Feeds interleaved samples to the meter.
+    pub fn process( &mut self, samples: &[f32] ) {
+        for frame in samples.chunks_exact( self.channels ) {
+            for ( ch, &sample ) in frame.iter().enumerate() {
+                self.peak = self.peak.max( sample.abs() );
+
+                let [ shelf, high_pass ] = &mut self.filters[ ch ];
+                let weighted = high_pass.process( shelf.process( sample as f64 ) );
+                self.step_sum += self.weights[ ch ] * weighted * weighted;
+            }
+
+            self.step_position += 1;
+            if self.step_position == self.step_frames {
+                self.finish_step();
+            }
+        }
+    }
+
+
+    /// Closes the current 100ms step and records a block once four are available.
+    fn finish_step( &mut self ) {
+        let power = self.step_sum / self.step_frames as f64;
+        self.step_sum = 0.0;
+        self.step_position = 0;
+
+        if self.recent_steps.len() == 4 {
+            self.recent_steps.pop_front();
+        }
+        self.recent_steps.push_back( power );
+
+        if self.recent_steps.len() == 4 {
+            self.blocks.push( self.recent_steps.iter().sum::<f64>() / 4.0 );
+        }
+    }
+
+
+    /// Returns the gated integrated loudness of everything processed so far.
+    ///
+    /// Returns None if the audio was too short or entirely below the absolute gate.
+    pub fn integrated( &self ) -> Option<Loudness> {
+        let above_absolute: Vec<f64> = self.blocks.iter()
+            .copied()
+            .filter( |&p| p > 0.0 && power_to_lufs( p ) > ABSOLUTE_GATE_LUFS )
+            .collect();
+        if above_absolute.is_empty() {
+            return None;
+        }
+
+        let ungated = above_absolute.iter().sum::<f64>() / above_absolute.len() as f64;
+        let relative_gate = power_to_lufs( ungated ) - RELATIVE_GATE_LU;
+
+        let gated: Vec<f64> = above_absolute.into_iter()
+            .filter( |&p| power_to_lufs( p ) > relative_gate )
+            .collect();
+        if gated.is_empty() {
+            return None;
+        }
+
+        let power = gated.iter().sum::<f64>() / gated.len() as f64;
+        Some( Loudness {
+            integrated_lufs: power_to_lufs( power ) as f32,
+            peak: self.peak,
+        })
+    }
+}
+
+
+/// A cached measurement, valid as long as the file hasn't changed.
+struct CacheEntry {
+    modified: u64,
+    size: u64,
+    loudness: Loudness,
+}
+
+
+/// What a measurement covers: a whole file, or the stretch of it a cue sheet track plays.
+#[derive( Debug, Clone, PartialEq, Eq, Hash )]
+struct CacheKey {
+    file: PathBuf,
+    /// Start and end of the cue track in milliseconds (None = the whole file, no end = to the end)
+    range: Option<( u64, Option<u64> )>,
+}
+
+
+impl CacheKey {
+    fn new( path: &Path, cue: Option<&CueTrack> ) -> Self {
+        let ms = |d: Duration| d.as_millis() as u64;
+        match cue {
+            Some( track ) => Self {
+                file: track.file.clone(),
+                range: Some(( ms( track.start ), track.end.map( ms ) )),
+            },
+            None => Self { file: path.to_path_buf(), range: None },
+        }
+    }
+}
+
+
+/// Loudness measurements persisted across sessions, keyed by file path.
+///
+/// Entries are invalidated when the file's size or modification time changes, and
+/// dropped once the file is gone. New measurements are written out at most every
+/// few minutes, and when the cache is dropped.
+pub struct LoudnessCache {
+    file: Option<PathBuf>,
+    entries: Mutex<HashMap<CacheKey, CacheEntry>>,
+    /// When the oldest measurement not yet written out was made
+    unsaved_since: Mutex<Option<Instant>>,
+}
+
+
+impl LoudnessCache {
+    /// Gets the default cache file path.
+    pub fn cache_file() -> Option<PathBuf> {
+        dirs::data_local_dir().map( |d| d.join( "oxidio" ).join( "loudness.cache" ) )
+    }
+
+
+    /// Loads the cache from the default location, starting empty if it doesn't exist.
+    pub fn load() -> Self {
+        Self::open( Self::cache_file() )
+    }
+
+
+    /// Loads the cache from `file`, saving measurements back to it (nowhere with None).
+    pub fn open( file: Option<PathBuf> ) -> Self {
+        let entries = file.as_deref()
+            .and_then( |f| Self::read_entries( f ).ok() )
+            .unwrap_or_default();
+
+        Self {
+            file,
+            entries: Mutex::new( entries ),
+            unsaved_since: Mutex::new( None ),
+        }
+    }
+
+
+    /// Reads cache lines of the form `lufs<TAB>peak<TAB>modified<TAB>size<TAB>start<TAB>end<TAB>path`,
+    /// where start and end are "-" for a whole file.
+    fn read_entries( file: &Path ) -> std::io::Result<HashMap<CacheKey, CacheEntry>> {
+        let reader = BufReader::new( File::open( file )? );
+        let mut entries = HashMap::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let mut fields = line.splitn( 7, '\t' );
+            let parsed = ( || {
+                let integrated_lufs = fields.next()?.parse().ok()?;
+                let peak = fields.next()?.parse().ok()?;
+                let modified = fields.next()?.parse().ok()?;
+                let size = fields.next()?.parse().ok()?;
+                let start = fields.next()?;
+                let end = fields.next()?;
+                let range = match start {
+                    "-" => None,
+                    start => Some(( start.parse().ok()?, end.parse().ok() )),
+                };
+                let file = PathBuf::from( fields.next()? );
+                Some(( CacheKey { file, range }, CacheEntry { modified, size, loudness: Loudness { integrated_lufs, peak } } ))
+            })();
+
+            if let Some(( key, entry )) = parsed {
+                entries.insert( key, entry );
+            }
+        }
+
+        Ok( entries )
+    }
+
+
+    /// Returns the (modified, size) fingerprint of a file.
+    fn fingerprint( path: &Path ) -> Option<( u64, u64 )> {
+        let meta = fs::metadata( path ).ok()?;
+        let modified = meta.modified().ok()?.duration_since( UNIX_EPOCH ).ok()?.as_secs();
+        Some(( modified, meta.len() ))
+    }
+
+
+    /// Gets the cached loudness of a file, or of the cue sheet track `cue` within it,
+    /// if it was measured and the file hasn't changed since.
+    pub fn get( &self, path: &Path, cue: Option<&CueTrack> ) -> Option<Loudness> {
+        let key = CacheKey::new( path, cue );
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get( &key )?;
+        let ( modified, size ) = Self::fingerprint( &key.file )?;
+        ( entry.modified == modified && entry.size == size ).then_some( entry.loudness )
+    }
+
+
+    /// Stores a measurement of a file or cue sheet track, writing the cache to disk if
+    /// earlier measurements have waited long enough.
+    pub fn insert( &self, path: &Path, cue: Option<&CueTrack>, loudness: Loudness ) {
+        let key = CacheKey::new( path, cue );
+        let Some(( modified, size )) = Self::fingerprint( &key.file ) else {
+            return;
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert( key, CacheEntry { modified, size, loudness } );
+
+        let mut unsaved_since = self.unsaved_since.lock().unwrap();
+        if unsaved_since.get_or_insert_with( Instant::now ).elapsed() >= SAVE_INTERVAL {
+            *unsaved_since = None;
+            self.save_logged( &mut entries );
+        }
+    }
+
+
+    /// Writes measurements not yet on disk.
+    pub fn flush( &self ) {
+        let mut entries = self.entries.lock().unwrap();
+        if self.unsaved_since.lock().unwrap().take().is_some() {
+            self.save_logged( &mut entries );
+        }
+    }
+
+
+    fn save_logged( &self, entries: &mut HashMap<CacheKey, CacheEntry> ) {
+        if let Err( e ) = self.save( entries ) {
+            tracing::warn!( "Failed to save loudness cache: {}", e );
+        }
+    }
+
+
+    /// Writes the cache, leaving out files that no longer exist.
+    fn save( &self, entries: &mut HashMap<CacheKey, CacheEntry> ) -> std::io::Result<()> {
+        let Some( ref file ) = self.file else {
+            return Ok(());
+        };
+
+        entries.retain( |key, _| key.file.exists() );
+
+        if let Some( parent ) = file.parent() {
+            fs::create_dir_all( parent )?;
+        }
+
+        let mut out = BufWriter::new( File::create( file )? );
+        for ( key, entry ) in entries.iter() {
+            let ( start, end ) = match key.range {
+                Some(( start, end )) => ( start.to_string(), end.map_or( "-".to_string(), |end| end.to_string() ) ),
+                None => ( "-".to_string(), "-".to_string() ),
+            };
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                entry.loudness.integrated_lufs,
+                entry.loudness.peak,
+                entry.modified,
+                entry.size,
+                start,
+                end,
+                key.file.display()
+            )?;
+        }
+
+        out.flush()
+    }
+}
+
+
+impl Drop for LoudnessCache {
+    fn drop( &mut self ) {
+        self.flush();
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_sine_loudness() {
+        // A 997 Hz sine at -23 dBFS in both channels measures -23 LUFS (EBU Tech 3341)
+        let rate = 48000;
+        let amplitude = 10f32.powf( -23.0 / 20.0 );
+        let samples: Vec<f32> = ( 0..rate * 5 )
+            .flat_map( |i| {
+                let s = amplitude * ( 2.0 * std::f32::consts::PI * 997.0 * i as f32 / rate as f32 ).sin();
+                [ s, s ]
+            })
+            .collect();
+
+        let mut meter = LoudnessMeter::new( rate, 2 );
+        meter.process( &samples );
+        let loudness = meter.integrated().unwrap();
+        assert!(( loudness.integrated_lufs + 23.0 ).abs() < 0.1, "{}", loudness.integrated_lufs );
+    }
+
+
+    #[test]
+    fn test_silence_has_no_loudness() {
+        let mut meter = LoudnessMeter::new( 44100, 2 );
+        meter.process( &vec![ 0.0; 44100 * 2 ] );
+        assert!( meter.integrated().is_none() );
+    }
+
+
+    #[test]
+    fn test_cache_keys_cue_tracks_and_prunes_missing_files() {
+        let dir = std::env::temp_dir().join( format!( "oxidio-loudness-{}", std::process::id() ) );
+        let _ = fs::remove_dir_all( &dir );
+        fs::create_dir_all( &dir ).unwrap();
+        let album = dir.join( "album.flac" );
+        let single = dir.join( "single.flac" );
+        fs::write( &album, b"album" ).unwrap();
+        fs::write( &single, b"single" ).unwrap();
+
+        let track = |number, start, end: Option<u64>| CueTrack {
+            number,
+            file: album.clone(),
+            title: None,
+            performer: None,
+            album: None,
+            album_performer: None,
+            genre: None,
+            year: None,
+            start: Duration::from_secs( start ),
+            end: end.map( Duration::from_secs ),
+        };
+        let loudness = |lufs| Loudness { integrated_lufs: lufs, peak: 0.5 };
+
+        let file = dir.join( "loudness.cache" );
+        let cache = LoudnessCache::open( Some( file.clone() ) );
+        let cue = dir.join( "album.cue#1" );
+        cache.insert( &cue, Some( &track( 1, 0, Some( 200 ) ) ), loudness( -10.0 ) );
+        cache.insert( &cue, Some( &track( 2, 200, None ) ), loudness( -20.0 ) );
+        cache.insert( &single, None, loudness( -30.0 ) );
+        // Batched until flushed
+        assert!( !file.exists() );
+        drop( cache );
+
+        let cache = LoudnessCache::open( Some( file.clone() ) );
+        assert_eq!( cache.get( &cue, Some( &track( 1, 0, Some( 200 ) ) ) ), Some( loudness( -10.0 ) ) );
+        assert_eq!( cache.get( &cue, Some( &track( 2, 200, None ) ) ), Some( loudness( -20.0 ) ) );
+        assert_eq!( cache.get( &album, None ), None );
+        assert_eq!( cache.get( &single, None ), Some( loudness( -30.0 ) ) );
+
+        fs::remove_file( &single ).unwrap();
+        cache.insert( &album, None, loudness( -15.0 ) );
+        cache.flush();
+        assert_eq!( LoudnessCache::read_entries( &file ).unwrap().len(), 3 );
+
+        let _ = fs::remove_dir_all( &dir );
+    }
+}
@@ -0,0 +1,230 @@
+//! Track metadata
+//!
+//! One description of a track, filled in from its tags by the decoder and from cue
+//! sheets by the library scanner. Besides the well-known fields, every tag is kept
+//! as it appears in the file.
+
+use std::time::Duration;
+
+use symphonia::core::meta::{ StandardTagKey, Tag, Value };
+
+use crate::artwork::{ self, Artwork };
+use crate::loudness::Loudness;
+use crate::replaygain::{ parse_gain, parse_peak, ReplayGain };
+
+
+/// Metadata of a track: its tags, audio format and pictures.
+#[derive( Debug, Clone, Default )]
+pub struct AudioMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track_number: Option<u32>,
+    /// Number of tracks on the disc
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
+    pub composer: Option<String>,
+    pub conductor: Option<String>,
+    pub comment: Option<String>,
+    pub lyrics: Option<String>,
+    pub bpm: Option<u32>,
+    pub isrc: Option<String>,
+    pub musicbrainz: MusicBrainzIds,
+    pub codec: Option<String>,
+    pub bitrate: Option<u32>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+    pub duration: Option<Duration>,
+    pub replay_gain: ReplayGain,
+    /// Loudness measured by oxidio, when the file has been analyzed
+    pub loudness: Option<Loudness>,
+    /// Embedded pictures, or the folder image when there are none
+    pub artwork: Vec<Artwork>,
+    /// Every tag as (key, value), with keys as the container names them (e.g. "TIT2", "TITLE")
+    pub raw_tags: Vec<( String, String )>,
+}
+
+
+/// MusicBrainz identifiers, as written by Picard and other taggers.
+#[derive( Debug, Clone, Default, PartialEq )]
+pub struct MusicBrainzIds {
+    pub recording: Option<String>,
+    /// The track on a particular release
+    pub track: Option<String>,
+    /// The release (album)
+    pub album: Option<String>,
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub release_group: Option<String>,
+    pub work: Option<String>,
+}
+
+
+impl AudioMetadata {
+    /// Returns the picture to show for the track, preferring the front cover.
+    pub fn cover( &self ) -> Option<&Artwork> {
+        artwork::cover( &self.artwork )
+    }
+
+
+    /// Fills in fields from a tag. Fields already set are kept, so earlier tags win.
+    pub( crate ) fn add_tag( &mut self, tag: &Tag ) {
+        let value = match tag.value {
+            // Binary values (ID3 PRIV frames and the like) are only worth their size
+            Value::Binary( ref data ) => format!( "<{} bytes>", data.len() ),
+            ref value => value.to_string(),
+        };
+        self.raw_tags.push(( tag.key.clone(), value.clone() ));
+        if matches!( tag.value, Value::Binary( _ ) ) {
+            return;
+        }
+
+        let Some( std_key ) = tag.std_key else {
+            // Some containers (ID3 TXXX, MP4 freeform atoms) only expose the raw key
+            let key = tag.key.to_uppercase();
+            let gain = &mut self.replay_gain;
+            if key.ends_with( "REPLAYGAIN_TRACK_GAIN" ) {
+                gain.track_gain = gain.track_gain.or( parse_gain( &value ) );
+            } else if key.ends_with( "REPLAYGAIN_TRACK_PEAK" ) {
+                gain.track_peak = gain.track_peak.or( parse_peak( &value ) );
+            } else if key.ends_with( "REPLAYGAIN_ALBUM_GAIN" ) {
+                gain.album_gain = gain.album_gain.or( parse_gain( &value ) );
+            } else if key.ends_with( "REPLAYGAIN_ALBUM_PEAK" ) {
+                gain.album_peak = gain.album_peak.or( parse_peak( &value ) );
+            }
+            return;
+        };
+
+        let text = |field: &mut Option<String>| {
+            if field.is_none() && !value.trim().is_empty() {
+                *field = Some( value.clone() );
+            }
+        };
+        let mb = &mut self.musicbrainz;
+        match std_key {
+            StandardTagKey::TrackTitle => text( &mut self.title ),
+            StandardTagKey::Artist => text( &mut self.artist ),
+            StandardTagKey::Album => text( &mut self.album ),
+            StandardTagKey::AlbumArtist => text( &mut self.album_artist ),
+            StandardTagKey::Genre => text( &mut self.genre ),
+            StandardTagKey::Composer => text( &mut self.composer ),
+            StandardTagKey::Conductor => text( &mut self.conductor ),
+            StandardTagKey::Comment => text( &mut self.comment ),
+            StandardTagKey::Lyrics => text( &mut self.lyrics ),
+            StandardTagKey::IdentIsrc => text( &mut self.isrc ),
+            StandardTagKey::MusicBrainzRecordingId => text( &mut mb.recording ),
+            StandardTagKey::MusicBrainzReleaseTrackId | StandardTagKey::MusicBrainzTrackId => text( &mut mb.track ),
+            StandardTagKey::MusicBrainzAlbumId => text( &mut mb.album ),
+            StandardTagKey::MusicBrainzArtistId => text( &mut mb.artist ),
+            StandardTagKey::MusicBrainzAlbumArtistId => text( &mut mb.album_artist ),
+            StandardTagKey::MusicBrainzReleaseGroupId => text( &mut mb.release_group ),
+            StandardTagKey::MusicBrainzWorkId => text( &mut mb.work ),
+            // Numbers may come as "3/12" (ID3 TRCK/TPOS)
+            StandardTagKey::TrackNumber => {
+                let ( number, total ) = parse_number_pair( &value );
+                self.track_number = self.track_number.or( number );
+                self.track_total = self.track_total.or( total );
+            }
+            StandardTagKey::TrackTotal => {
+                self.track_total = self.track_total.or( parse_number_pair( &value ).0 );
+            }
+            StandardTagKey::DiscNumber => {
+                let ( number, total ) = parse_number_pair( &value );
+                self.disc_number = self.disc_number.or( number );
+                self.disc_total = self.disc_total.or( total );
+            }
+            StandardTagKey::DiscTotal => {
+                self.disc_total = self.disc_total.or( parse_number_pair( &value ).0 );
+            }
+            StandardTagKey::Bpm => {
+                let bpm = value.trim().parse::<f32>().ok().filter( |bpm| *bpm > 0.0 );
+                self.bpm = self.bpm.or( bpm.map( |bpm| bpm.round() as u32 ) );
+            }
+            StandardTagKey::Date | StandardTagKey::ReleaseDate => {
+                // Extract year from date string (e.g., "2023" or "2023-01-15")
+                let year = value.split( '-' ).next().and_then( |year| year.trim().parse().ok() );
+                self.year = self.year.or( year );
+            }
+            StandardTagKey::ReplayGainTrackGain => {
+                self.replay_gain.track_gain = self.replay_gain.track_gain.or( parse_gain( &value ) );
+            }
+            StandardTagKey::ReplayGainTrackPeak => {
+                self.replay_gain.track_peak = self.replay_gain.track_peak.or( parse_peak( &value ) );
+            }
+            StandardTagKey::ReplayGainAlbumGain => {
+                self.replay_gain.album_gain = self.replay_gain.album_gain.or( parse_gain( &value ) );
+            }
+            StandardTagKey::ReplayGainAlbumPeak => {
+                self.replay_gain.album_peak = self.replay_gain.album_peak.or( parse_peak( &value ) );
+            }
+            _ => {}
+        }
+    }
+}
+
+
+/// Parses "3", "3/12" or "/12" into (number, total).
+fn parse_number_pair( value: &str ) -> ( Option<u32>, Option<u32> ) {
+    let mut parts = value.splitn( 2, '/' );
+    let number = parts.next().and_then( |n| n.trim().parse().ok() );
+    let total = parts.next().and_then( |n| n.trim().parse().ok() );
+    ( number, total )
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    fn tag( std_key: Option<StandardTagKey>, key: &str, value: Value ) -> Tag {
+        Tag::new( std_key, key, value )
+    }
+
+
+    #[test]
+    fn test_tags_fill_fields_and_raw_list() {
+        let mut meta = AudioMetadata::default();
+        let tags = [
+            tag( Some( StandardTagKey::TrackTitle ), "TIT2", Value::from( "So What" ) ),
+            tag( Some( StandardTagKey::TrackNumber ), "TRCK", Value::from( "1/5" ) ),
+            tag( Some( StandardTagKey::DiscNumber ), "TPOS", Value::from( "2/2" ) ),
+            tag( Some( StandardTagKey::Composer ), "TCOM", Value::from( "Miles Davis" ) ),
+            tag( Some( StandardTagKey::Bpm ), "TBPM", Value::from( "136.4" ) ),
+            tag( Some( StandardTagKey::MusicBrainzRecordingId ), "TXXX:MusicBrainz Release Track Id", Value::from( "abc-123" ) ),
+            tag( None, "TXXX:REPLAYGAIN_TRACK_GAIN", Value::from( "-6.5 dB" ) ),
+            tag( None, "TXXX:MOOD", Value::from( "Cool" ) ),
+            tag( None, "PRIV", Value::from( &[ 0u8; 40 ][ .. ] ) ),
+            // Later duplicates don't override
+            tag( Some( StandardTagKey::TrackTitle ), "TITLE", Value::from( "Other" ) ),
+        ];
+        for tag in &tags {
+            meta.add_tag( tag );
+        }
+
+        assert_eq!( meta.title.as_deref(), Some( "So What" ) );
+        assert_eq!(( meta.track_number, meta.track_total ), ( Some( 1 ), Some( 5 ) ));
+        assert_eq!(( meta.disc_number, meta.disc_total ), ( Some( 2 ), Some( 2 ) ));
+        assert_eq!( meta.composer.as_deref(), Some( "Miles Davis" ) );
+        assert_eq!( meta.bpm, Some( 136 ) );
+        assert_eq!( meta.musicbrainz.recording.as_deref(), Some( "abc-123" ) );
+        assert_eq!( meta.replay_gain.track_gain, Some( -6.5 ) );
+
+        assert_eq!( meta.raw_tags.len(), tags.len() );
+        assert_eq!( meta.raw_tags[ 7 ], ( "TXXX:MOOD".to_string(), "Cool".to_string() ) );
+        assert_eq!( meta.raw_tags[ 8 ].1, "<40 bytes>" );
+    }
+
+
+    #[test]
+    fn test_parse_number_pair() {
+        assert_eq!( parse_number_pair( "7" ), ( Some( 7 ), None ) );
+        assert_eq!( parse_number_pair( " 3 / 12 " ), ( Some( 3 ), Some( 12 ) ) );
+        assert_eq!( parse_number_pair( "/12" ), ( None, Some( 12 ) ) );
+        assert_eq!( parse_number_pair( "" ), ( None, None ) );
+    }
+}
@@ -2,13 +2,17 @@
 //!
 //! Handles sending decoded PCM samples to the system audio device.
 
-use std::sync::{ Arc, Mutex };
-use std::sync::atomic::{ AtomicBool, AtomicU32, Ordering };
-use std::collections::VecDeque;
+use std::sync::{ mpsc, Arc };
+use std::thread;
+use std::time::{ Duration, Instant };
+use std::sync::atomic::{ AtomicBool, AtomicU32, AtomicU64, Ordering };
 
 use cpal::traits::{ DeviceTrait, HostTrait, StreamTrait };
 use thiserror::Error;
 
+use crate::sink::AudioSink;
+use crate::spectrum::FFT_SIZE;
+
 
 /// Errors that can occur with audio output.
 #[derive( Debug, Error )]
@@ -24,233 +28,476 @@ pub enum OutputError {
 
     #[error( "Failed to play stream: {0}" )]
     PlayStream( String ),
+
+    #[error( "Failed to write output file: {0}" )]
+    File( String ),
+}
+
+
+/// Identifies an output device by its host API and name.
+#[derive( Debug, Clone, PartialEq, Eq )]
+#[cfg_attr( feature = "serde", derive( serde::Serialize, serde::Deserialize ) )]
+pub struct OutputDeviceId {
+    /// Audio API the device belongs to, e.g. "ALSA" or "WASAPI"
+    pub host: String,
+    /// Device name as reported by the host
+    pub name: String,
+}
+
+
+/// A range of stream configurations an output device supports.
+#[derive( Debug, Clone, PartialEq, Eq )]
+pub struct SupportedOutputConfig {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    /// Sample format name, e.g. "f32" or "i16"
+    pub sample_format: String,
 }
 
 
-/// Number of visualization bars to display
-pub const VIS_BARS: usize = 32;
+/// An output device available on the system.
+#[derive( Debug, Clone )]
+pub struct OutputDeviceInfo {
+    pub id: OutputDeviceId,
+    /// Whether this is its host's default output device
+    pub is_default: bool,
+    pub configs: Vec<SupportedOutputConfig>,
+}
+
+
+/// Lists the output devices of every available host, with their supported configurations.
+pub fn list_output_devices() -> Vec<OutputDeviceInfo> {
+    let mut devices = Vec::new();
+
+    for host_id in cpal::available_hosts() {
+        let host = match cpal::host_from_id( host_id ) {
+            Ok( host ) => host,
+            Err( e ) => {
+                tracing::warn!( "Failed to open audio host {}: {}", host_id.name(), e );
+                continue;
+            }
+        };
+
+        let default_name = host.default_output_device().and_then( |d| d.name().ok() );
+        let outputs = match host.output_devices() {
+            Ok( outputs ) => outputs,
+            Err( e ) => {
+                tracing::warn!( "Failed to list {} output devices: {}", host_id.name(), e );
+                continue;
+            }
+        };
+
+        for device in outputs {
+            let Ok( name ) = device.name() else {
+                continue;
+            };
+            let configs = device.supported_output_configs()
+                .map( |configs| configs.map( |c| SupportedOutputConfig {
+                    channels: c.channels(),
+                    min_sample_rate: c.min_sample_rate().0,
+                    max_sample_rate: c.max_sample_rate().0,
+                    sample_format: c.sample_format().to_string(),
+                }).collect() )
+                .unwrap_or_default();
+
+            devices.push( OutputDeviceInfo {
+                is_default: default_name.as_deref() == Some( name.as_str() ),
+                id: OutputDeviceId { host: host_id.name().to_string(), name },
+                configs,
+            });
+        }
+    }
+
+    devices
+}
+
+
+/// Finds the device with the given host and name, if it's still present.
+fn find_output_device( id: &OutputDeviceId ) -> Option<cpal::Device> {
+    let host_id = cpal::available_hosts().into_iter().find( |h| h.name() == id.host )?;
+    let host = cpal::host_from_id( host_id ).ok()?;
+    let mut devices = host.output_devices().ok()?;
+    devices.find( |d| d.name().is_ok_and( |name| name == id.name ) )
+}
+
+
+/// Format of the samples the output device consumes.
+#[derive( Debug, Clone, Copy, PartialEq, Eq )]
+pub struct OutputFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
 
 
 /// Shared sample buffer between producer (decoder) and consumer (audio callback).
 /// This is Send + Sync and can be shared across threads.
-/// Handles channel conversion between source and output.
+/// Samples must already be in the output's channel layout (see `dsp::Remix`).
+///
+/// The buffer is a single-producer, single-consumer ring that never locks or
+/// allocates, so `pop` is safe to call from a real-time audio callback. Samples are
+/// stored as the bits of an `f32` in atomics so both sides can touch a slot at once
+/// without undefined behavior.
+///
+/// The consumer ramps the gain when pausing, resuming and around `fade_out` /
+/// `fade_in`, so playback never starts or stops mid-waveform with a click.
 pub struct SampleBuffer {
-    buffer: Mutex<VecDeque<f32>>,
+    ring: Box<[AtomicU32]>,
     capacity: usize,
     paused: AtomicBool,
-    /// Volume level stored as f32 bits (0.0 to 1.0+)
-    volume: AtomicU32,
-    source_channels: u16,
-    output_channels: u16,
-    /// Visualization data - RMS amplitudes for display
-    vis_data: Mutex<[f32; VIS_BARS]>,
+    channels: u16,
+    /// Length of gain ramps in frames (0 = switch instantly)
+    fade_frames: AtomicU32,
+    /// Gain applied to popped samples, as f32 bits. Only the consumer changes it.
+    gain: AtomicU32,
+    /// Set while the gain is heading for silence
+    silenced: AtomicBool,
+    /// Set while fading out before pausing, until the consumer pauses
+    pausing: AtomicBool,
+    /// Asks the consumer to jump straight back to full gain
+    unmute: AtomicBool,
+    /// Mono mixdown of the most recently played frames, as f32 bits, for visualization
+    played: Box<[AtomicU32]>,
+    /// Total number of frames ever recorded in `played`
+    played_frames: AtomicU64,
+    /// Total number of samples ever pushed into the buffer, the write position
+    pushed: AtomicU64,
+    /// Total number of samples ever read by the consumer, the read position
+    read: AtomicU64,
+    /// Write position at the last `clear()`. Everything before it is discarded.
+    discarded: AtomicU64,
+    /// Set while the producer is feeding a stream, so running dry counts as a dropout
+    streaming: AtomicBool,
+    /// Set once the consumer has run dry, until it can fill a request again
+    starved: AtomicBool,
+    /// Number of times the consumer ran dry mid-stream
+    underruns: AtomicU64,
+    /// Reference point for `last_pop_nanos`
+    epoch: Instant,
+    /// Read position at the start of the latest pop while playing
+    last_pop_position: AtomicU64,
+    /// When the latest pop while playing ran, in nanoseconds since `epoch`
+    last_pop_nanos: AtomicU64,
+    /// Time between a pop and its first sample being heard, in nanoseconds.
+    /// `u64::MAX` until the output reports it.
+    latency_nanos: AtomicU64,
 }
 
 
 impl SampleBuffer {
-    /// Creates a new sample buffer with the given capacity and channel configuration.
+    /// Creates a new sample buffer with the given capacity and channel count.
     ///
     /// - `capacity`: Maximum number of samples to buffer
-    /// - `source_channels`: Number of channels in the source audio (from decoder)
-    /// - `output_channels`: Number of channels expected by the output device
-    pub fn new( capacity: usize, source_channels: u16, output_channels: u16 ) -> Self {
+    /// - `channels`: Number of channels expected by the output device
+    pub fn new( capacity: usize, channels: u16 ) -> Self {
+        let capacity = capacity.max( 1 );
         Self {
-            buffer: Mutex::new( VecDeque::with_capacity( capacity ) ),
-            vis_data: Mutex::new( [0.0; VIS_BARS] ),
+            ring: ( 0..capacity ).map( |_| AtomicU32::new( 0 ) ).collect(),
+            played: ( 0..FFT_SIZE ).map( |_| AtomicU32::new( 0 ) ).collect(),
+            played_frames: AtomicU64::new( 0 ),
             capacity,
             paused: AtomicBool::new( false ),
-            volume: AtomicU32::new( 1.0_f32.to_bits() ),
-            source_channels,
-            output_channels,
+            channels,
+            fade_frames: AtomicU32::new( 0 ),
+            gain: AtomicU32::new( 1.0f32.to_bits() ),
+            silenced: AtomicBool::new( false ),
+            pausing: AtomicBool::new( false ),
+            unmute: AtomicBool::new( false ),
+            pushed: AtomicU64::new( 0 ),
+            read: AtomicU64::new( 0 ),
+            discarded: AtomicU64::new( 0 ),
+            streaming: AtomicBool::new( false ),
+            starved: AtomicBool::new( false ),
+            underruns: AtomicU64::new( 0 ),
+            epoch: Instant::now(),
+            last_pop_position: AtomicU64::new( 0 ),
+            last_pop_nanos: AtomicU64::new( 0 ),
+            latency_nanos: AtomicU64::new( u64::MAX ),
         }
     }
 
 
     /// Pushes samples to the buffer. Returns number of samples actually pushed.
-    /// Also updates visualization data with RMS values.
+    ///
+    /// Only one thread may push at a time.
     pub fn push( &self, samples: &[f32] ) -> usize {
-        let mut buf = self.buffer.lock().unwrap();
-        let available = self.capacity.saturating_sub( buf.len() );
-        let to_push = samples.len().min( available );
-        buf.extend( samples[ ..to_push ].iter().copied() );
-
-        // Update visualization data if we have enough samples
-        if to_push >= VIS_BARS {
-            let mut vis = self.vis_data.lock().unwrap();
-            let samples_per_bar = to_push / VIS_BARS;
-
-            for ( bar_idx, bar ) in vis.iter_mut().enumerate() {
-                let start = bar_idx * samples_per_bar;
-                let end = ( start + samples_per_bar ).min( to_push );
-
-                // Calculate RMS for this bar
-                let sum_sq: f32 = samples[ start..end ]
-                    .iter()
-                    .map( |s| s * s )
-                    .sum();
-                let rms = ( sum_sq / ( end - start ) as f32 ).sqrt();
-
-                // Smooth with previous value (decay)
-                *bar = ( *bar * 0.7 ) + ( rms * 0.3 );
-            }
+        let head = self.pushed.load( Ordering::Relaxed );
+        let queued = ( head - self.consumed() ) as usize;
+        let to_push = samples.len().min( self.capacity.saturating_sub( queued ) );
+
+        for ( i, sample ) in samples[ ..to_push ].iter().enumerate() {
+            self.slot( head + i as u64 ).store( sample.to_bits(), Ordering::Relaxed );
+        }
+        self.pushed.store( head + to_push as u64, Ordering::Release );
+        if to_push > 0 {
+            self.streaming.store( true, Ordering::Relaxed );
         }
 
         to_push
     }
 
 
-    /// Pops samples from the buffer into the output slice, handling channel conversion.
+    /// Pops samples from the buffer into the output slice.
     /// Returns the number of output samples actually written.
+    ///
+    /// Only one thread may pop at a time. Never blocks or allocates.
     pub fn pop( &self, output: &mut [f32] ) -> usize {
         // If paused, output silence
         if self.paused.load( Ordering::Relaxed ) {
-            for sample in output.iter_mut() {
-                *sample = 0.0;
-            }
+            output.fill( 0.0 );
+            self.record_played( output );
             return 0;
         }
 
-        let volume = f32::from_bits( self.volume.load( Ordering::Relaxed ) );
-        let mut buf = self.buffer.lock().unwrap();
-        let src_ch = self.source_channels as usize;
-        let out_ch = self.output_channels as usize;
+        let tail = self.consumed();
+        let head = self.pushed.load( Ordering::Acquire );
+        self.last_pop_position.store( tail, Ordering::Relaxed );
+        self.last_pop_nanos.store( self.epoch.elapsed().as_nanos() as u64, Ordering::Relaxed );
 
-        let written = if src_ch == out_ch {
-            // No conversion needed
-            let to_pop = output.len().min( buf.len() );
-            for i in 0..to_pop {
-                output[ i ] = buf.pop_front().unwrap();
-            }
-            // Fill remaining with silence
-            for i in to_pop..output.len() {
-                output[ i ] = 0.0;
-            }
-            to_pop
-        } else if src_ch == 1 && out_ch == 2 {
-            // Mono to stereo: duplicate each sample
-            let output_frames = output.len() / out_ch;
-            let available_frames = buf.len() / src_ch;
-            let frames_to_process = output_frames.min( available_frames );
-
-            for i in 0..frames_to_process {
-                let sample = buf.pop_front().unwrap();
-                output[ i * 2 ] = sample;
-                output[ i * 2 + 1 ] = sample;
-            }
-            // Fill remaining with silence
-            for i in ( frames_to_process * out_ch )..output.len() {
-                output[ i ] = 0.0;
-            }
-            frames_to_process * out_ch
-        } else if src_ch == 2 && out_ch == 1 {
-            // Stereo to mono: mix down
-            let output_frames = output.len() / out_ch;
-            let available_frames = buf.len() / src_ch;
-            let frames_to_process = output_frames.min( available_frames );
-
-            for i in 0..frames_to_process {
-                let left = buf.pop_front().unwrap();
-                let right = buf.pop_front().unwrap();
-                output[ i ] = ( left + right ) * 0.5;
-            }
-            // Fill remaining with silence
-            for i in frames_to_process..output.len() {
-                output[ i ] = 0.0;
-            }
-            frames_to_process
-        } else {
-            // General case: simple remix (duplicate first channel or mix all to fewer)
-            let output_frames = output.len() / out_ch;
-            let available_frames = buf.len() / src_ch;
-            let frames_to_process = output_frames.min( available_frames );
-
-            for frame in 0..frames_to_process {
-                // Read source frame
-                let mut src_samples = Vec::with_capacity( src_ch );
-                for _ in 0..src_ch {
-                    src_samples.push( buf.pop_front().unwrap() );
-                }
-
-                // Write output frame
-                for ch in 0..out_ch {
-                    if ch < src_ch {
-                        output[ frame * out_ch + ch ] = src_samples[ ch ];
-                    } else {
-                        // Duplicate last channel if output has more channels
-                        output[ frame * out_ch + ch ] = src_samples[ src_ch - 1 ];
-                    }
-                }
-            }
-            // Fill remaining with silence
-            for i in ( frames_to_process * out_ch )..output.len() {
-                output[ i ] = 0.0;
-            }
-            frames_to_process * out_ch
-        };
+        let written = output.len().min( ( head - tail ) as usize );
+        for ( i, out ) in output[ ..written ].iter_mut().enumerate() {
+            *out = f32::from_bits( self.slot( tail + i as u64 ).load( Ordering::Relaxed ) );
+        }
+        // Fill remaining with silence
+        output[ written.. ].fill( 0.0 );
+        self.read.store( tail + written as u64, Ordering::Release );
+
+        // Read after `pushed`, so requests made before pushing new audio apply to it
+        self.apply_gain( output );
+        self.record_played( output );
+
+        // Count each time playback runs dry, not every silent callback after it
+        if written == output.len() {
+            self.starved.store( false, Ordering::Relaxed );
+        } else if self.streaming.load( Ordering::Relaxed ) && !self.starved.swap( true, Ordering::Relaxed ) {
+            self.underruns.fetch_add( 1, Ordering::Relaxed );
+        }
+
+        written
+    }
 
-        // Apply volume to all output samples
-        if volume != 1.0 {
-            for sample in output[ ..written ].iter_mut() {
-                *sample *= volume;
+
+    /// Scales popped samples by the gain, moving it towards its target one step per frame.
+    fn apply_gain( &self, output: &mut [f32] ) {
+        let previous = f32::from_bits( self.gain.load( Ordering::Relaxed ) );
+        let mut gain = if self.unmute.swap( false, Ordering::Acquire ) { 1.0 } else { previous };
+        let target = if self.silenced.load( Ordering::Acquire ) { 0.0 } else { 1.0 };
+
+        if gain != target || target != 1.0 {
+            let fade_frames = self.fade_frames.load( Ordering::Relaxed );
+            let step = if fade_frames == 0 { 1.0 } else { 1.0 / fade_frames as f32 };
+            for frame in output.chunks_mut( self.channels.max( 1 ) as usize ) {
+                gain = if target > gain { ( gain + step ).min( target ) } else { ( gain - step ).max( target ) };
+                frame.iter_mut().for_each( |s| *s *= gain );
             }
         }
+        if gain != previous {
+            self.gain.store( gain.to_bits(), Ordering::Release );
+        }
 
-        written
+        // A pause takes effect once faded out
+        if gain == 0.0 && self.pausing.load( Ordering::Acquire ) {
+            self.paused.store( true, Ordering::Release );
+            self.pausing.store( false, Ordering::Release );
+        }
+    }
+
+
+    /// Keeps a mono mixdown of samples handed to the output for `played()`.
+    fn record_played( &self, output: &[f32] ) {
+        let channels = self.channels.max( 1 ) as usize;
+        let start = self.played_frames.load( Ordering::Relaxed );
+        let mut frames = 0;
+        for frame in output.chunks_exact( channels ) {
+            let mono = frame.iter().sum::<f32>() / channels as f32;
+            self.played[ ( ( start + frames ) % FFT_SIZE as u64 ) as usize ].store( mono.to_bits(), Ordering::Relaxed );
+            frames += 1;
+        }
+        self.played_frames.store( start + frames, Ordering::Release );
+    }
+
+
+    /// Gets the ring slot holding the sample at stream position `position`.
+    fn slot( &self, position: u64 ) -> &AtomicU32 {
+        &self.ring[ ( position % self.capacity as u64 ) as usize ]
     }
 
 
     /// Returns the number of samples currently in the buffer.
     pub fn len( &self ) -> usize {
-        self.buffer.lock().unwrap().len()
+        self.pushed.load( Ordering::Acquire ).saturating_sub( self.consumed() ) as usize
     }
 
 
     /// Returns true if the buffer is empty.
     pub fn is_empty( &self ) -> bool {
-        self.buffer.lock().unwrap().is_empty()
+        self.len() == 0
     }
 
 
     /// Clears the buffer.
+    ///
+    /// Safe to call from any thread. The consumer skips the discarded samples on its
+    /// next pop; one it's already in the middle of may still play a few of them.
     pub fn clear( &self ) {
-        self.buffer.lock().unwrap().clear();
+        self.discarded.fetch_max( self.pushed.load( Ordering::Acquire ), Ordering::AcqRel );
+        self.streaming.store( false, Ordering::Relaxed );
+    }
+
+
+    /// Marks the end of the stream, so the buffer running dry isn't counted as a dropout.
+    ///
+    /// The next push starts a new stream.
+    pub fn end_of_stream( &self ) {
+        self.streaming.store( false, Ordering::Relaxed );
+    }
+
+
+    /// Returns the total number of samples pushed since the buffer was created.
+    ///
+    /// Together with `consumed()` this lets the producer tell when a sample it
+    /// pushed has actually been handed to the output device.
+    pub fn pushed( &self ) -> u64 {
+        self.pushed.load( Ordering::Relaxed )
+    }
+
+
+    /// Returns the total number of samples removed from the buffer since it was created.
+    pub fn consumed( &self ) -> u64 {
+        self.read.load( Ordering::Acquire ).max( self.discarded.load( Ordering::Acquire ) )
+    }
+
+
+    /// Reports how long after a pop its first sample reaches the listener.
+    ///
+    /// Called by outputs that know their latency, just before each `pop`. Without it,
+    /// `heard()` assumes samples are heard as soon as they are consumed.
+    pub fn set_output_latency( &self, latency: Duration ) {
+        self.latency_nanos.store( latency.as_nanos().min( u64::MAX as u128 - 1 ) as u64, Ordering::Relaxed );
+    }
+
+
+    /// Estimates the position, in the same terms as `pushed()` and `consumed()`, of
+    /// the sample being heard right now.
+    ///
+    /// Starts from the latest pop and advances with the wall clock, held back by the
+    /// output latency and never past what has been consumed.
+    pub fn heard( &self, sample_rate: u32 ) -> u64 {
+        let consumed = self.consumed();
+        let latency = self.latency_nanos.load( Ordering::Relaxed );
+        if latency == u64::MAX {
+            return consumed;
+        }
+
+        let base = self.last_pop_position.load( Ordering::Relaxed ) as i128;
+        let popped_at = self.last_pop_nanos.load( Ordering::Relaxed ) as i128;
+        let since = self.epoch.elapsed().as_nanos() as i128 - popped_at - latency as i128;
+        let advanced = since * sample_rate as i128 * self.channels as i128 / 1_000_000_000;
+
+        // Whole frames only, so the position maps cleanly onto the track
+        let channels = self.channels.max( 1 ) as i128;
+        let heard = ( base + advanced ).clamp( 0, consumed as i128 );
+        ( heard - heard % channels ) as u64
+    }
+
+
+    /// Returns how many times the output ran out of samples in the middle of a stream.
+    pub fn underruns( &self ) -> u64 {
+        self.underruns.load( Ordering::Relaxed )
     }
 
 
-    /// Sets paused state.
+    /// Gets the number of interleaved channels in the buffer.
+    pub fn channels( &self ) -> u16 {
+        self.channels
+    }
+
+
+    /// Pauses or resumes the output.
+    ///
+    /// Pausing fades out first and resuming fades back in (see `set_fade_length`).
+    /// `fade_done()` tells when the change is complete.
     pub fn set_paused( &self, paused: bool ) {
-        self.paused.store( paused, Ordering::Relaxed );
+        if paused {
+            self.silenced.store( true, Ordering::Release );
+            if self.fade_frames.load( Ordering::Relaxed ) == 0 {
+                self.paused.store( true, Ordering::Release );
+            } else if !self.paused.load( Ordering::Acquire ) {
+                self.pausing.store( true, Ordering::Release );
+            }
+        } else {
+            self.pausing.store( false, Ordering::Release );
+            self.silenced.store( false, Ordering::Release );
+            self.paused.store( false, Ordering::Release );
+        }
     }
 
 
-    /// Gets paused state.
+    /// Returns true if the output is paused, or fading out to pause.
     pub fn is_paused( &self ) -> bool {
-        self.paused.load( Ordering::Relaxed )
+        self.paused.load( Ordering::Acquire ) || self.pausing.load( Ordering::Acquire )
+    }
+
+
+    /// Sets how many frames gain ramps take (0 switches instantly).
+    pub fn set_fade_length( &self, frames: u32 ) {
+        self.fade_frames.store( frames, Ordering::Relaxed );
     }
 
 
-    /// Gets the current visualization data (RMS amplitudes for each bar).
-    pub fn vis_data( &self ) -> [f32; VIS_BARS] {
-        *self.vis_data.lock().unwrap()
+    /// Fades to silence without pausing: samples keep being consumed, unheard.
+    pub fn fade_out( &self ) {
+        self.silenced.store( true, Ordering::Release );
     }
 
 
-    /// Sets the volume level (0.0 = mute, 1.0 = normal, >1.0 = boost).
-    pub fn set_volume( &self, volume: f32 ) {
-        self.volume.store( volume.to_bits(), Ordering::Relaxed );
+    /// Fades back in after `fade_out`, starting with the next samples popped.
+    pub fn fade_in( &self ) {
+        self.silenced.store( false, Ordering::Release );
     }
 
 
-    /// Gets the current volume level.
-    pub fn volume( &self ) -> f32 {
-        f32::from_bits( self.volume.load( Ordering::Relaxed ) )
+    /// Goes straight back to full gain after `fade_out`, e.g. at the start of a new track.
+    pub fn unmute( &self ) {
+        self.silenced.store( false, Ordering::Release );
+        self.unmute.store( true, Ordering::Release );
+    }
+
+
+    /// Returns true once the gain has reached where the last pause, resume or fade
+    /// was taking it.
+    pub fn fade_done( &self ) -> bool {
+        let gain = f32::from_bits( self.gain.load( Ordering::Acquire ) );
+        if self.silenced.load( Ordering::Acquire ) {
+            self.paused.load( Ordering::Acquire ) || ( gain == 0.0 && !self.pausing.load( Ordering::Acquire ) )
+        } else {
+            gain == 1.0 && !self.unmute.load( Ordering::Acquire )
+        }
+    }
+
+
+    /// Copies a mono mixdown of the most recently played audio into `out`, oldest first.
+    ///
+    /// Holds up to `FFT_SIZE` frames. Includes the silence played while paused or
+    /// starved, and is padded with silence at the start if less has been played.
+    pub fn played( &self, out: &mut [f32] ) {
+        let end = self.played_frames.load( Ordering::Acquire );
+        let available = end.min( FFT_SIZE as u64 ) as usize;
+        let count = out.len().min( available );
+        let padding = out.len() - count;
+
+        out[ ..padding ].fill( 0.0 );
+        for ( i, sample ) in out[ padding.. ].iter_mut().enumerate() {
+            let position = end - count as u64 + i as u64;
+            *sample = f32::from_bits( self.played[ ( position % FFT_SIZE as u64 ) as usize ].load( Ordering::Relaxed ) );
+        }
     }
 }
 
 
 /// Audio output handler.
 /// Note: This struct is NOT Send/Sync due to cpal::Stream.
-/// Keep it on the thread where it was created.
+/// Keep it on the thread where it was created (see `CpalSink`).
 pub struct AudioOutput {
     stream: cpal::Stream,
     sample_rate: u32,
@@ -259,65 +506,41 @@ pub struct AudioOutput {
 
 
 impl AudioOutput {
-    /// Creates a new audio output with the specified source sample rate and channels.
+    /// Opens the default output device at its default configuration.
     ///
     /// Returns both the AudioOutput and a shared SampleBuffer that the caller should
-    /// use to push decoded samples. The buffer handles channel conversion if needed.
-    pub fn new(
-        source_sample_rate: u32,
-        source_channels: u16,
-    ) -> Result<( Self, Arc<SampleBuffer> ), OutputError> {
-        let host = cpal::default_host();
+    /// use to push samples. Producers are expected to resample and remix to the
+    /// device's format, which stays fixed for the lifetime of the stream.
+    pub fn new() -> Result<( Self, Arc<SampleBuffer> ), OutputError> {
+        Self::open( None )
+    }
 
-        let device = host
-            .default_output_device()
-            .ok_or( OutputError::NoDevice )?;
+
+    /// Opens `device` at its default configuration, or the default device if None.
+    ///
+    /// Falls back to the default device if the requested one is no longer present.
+    pub fn open( device: Option<&OutputDeviceId> ) -> Result<( Self, Arc<SampleBuffer> ), OutputError> {
+        let preferred = device.and_then( |id| {
+            let found = find_output_device( id );
+            if found.is_none() {
+                tracing::warn!( "Output device {} ({}) not found, using default", id.name, id.host );
+            }
+            found
+        });
+
+        let device = match preferred {
+            Some( device ) => device,
+            None => cpal::default_host()
+                .default_output_device()
+                .ok_or( OutputError::NoDevice )?,
+        };
 
         tracing::info!( "Using output device: {:?}", device.name() );
 
-        // Try to get a config matching our requirements
-        // Priority: 1) exact match, 2) same sample rate any channels, 3) default with warning
-        let supported_configs: Vec<_> = device
-            .supported_output_configs()
+        let config = device
+            .default_output_config()
             .map_err( |e| OutputError::StreamConfig( e.to_string() ) )?
-            .collect();
-
-        // First try: exact match (channels + sample rate)
-        let config = if let Some( supported_config ) = supported_configs.iter().find( |c| {
-            c.channels() == source_channels
-                && c.min_sample_rate().0 <= source_sample_rate
-                && c.max_sample_rate().0 >= source_sample_rate
-        }) {
-            supported_config.clone()
-                .with_sample_rate( cpal::SampleRate( source_sample_rate ) )
-                .config()
-        }
-        // Second try: any config that supports our sample rate (we'll handle channel conversion)
-        else if let Some( supported_config ) = supported_configs.iter().find( |c| {
-            c.min_sample_rate().0 <= source_sample_rate
-                && c.max_sample_rate().0 >= source_sample_rate
-        }) {
-            tracing::info!(
-                "Channel conversion: file has {} channels, device using {} channels",
-                source_channels,
-                supported_config.channels()
-            );
-            supported_config.clone()
-                .with_sample_rate( cpal::SampleRate( source_sample_rate ) )
-                .config()
-        }
-        // Last resort: default config (may have wrong sample rate!)
-        else {
-            let default_config = device
-                .default_output_config()
-                .map_err( |e| OutputError::StreamConfig( e.to_string() ) )?;
-            tracing::warn!(
-                "Sample rate mismatch: file is {} Hz, device defaulting to {} Hz - playback speed may be incorrect!",
-                source_sample_rate,
-                default_config.sample_rate().0
-            );
-            default_config.config()
-        };
+            .config();
 
         tracing::info!(
             "Audio output config: {} Hz, {} channels",
@@ -325,20 +548,20 @@ impl AudioOutput {
             config.channels
         );
 
-        // Create shared sample buffer with channel conversion info
+        // Create shared sample buffer
         // Buffer size: ~500ms of audio
-        let buffer_capacity = ( source_sample_rate as usize ) * ( source_channels as usize ) / 2;
-        let sample_buffer = Arc::new( SampleBuffer::new(
-            buffer_capacity,
-            source_channels,
-            config.channels,
-        ));
+        let buffer_capacity = ( config.sample_rate.0 as usize ) * ( config.channels as usize ) / 2;
+        let sample_buffer = Arc::new( SampleBuffer::new( buffer_capacity, config.channels ) );
         let sample_buffer_clone = Arc::clone( &sample_buffer );
 
         let stream = device
             .build_output_stream(
                 &config,
-                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
+                    let timestamp = info.timestamp();
+                    if let Some( latency ) = timestamp.playback.duration_since( &timestamp.callback ) {
+                        sample_buffer_clone.set_output_latency( latency );
+                    }
                     sample_buffer_clone.pop( data );
                 },
                 |err| {
@@ -386,3 +609,231 @@ impl AudioOutput {
         self.channels
     }
 }
+
+
+/// Sink playing through a sound card, as a long-lived cpal stream on its own thread.
+///
+/// cpal streams can't move between threads on every platform, so the stream is
+/// created, played and dropped on a dedicated thread. Everything else talks to it
+/// through the shared SampleBuffer, which makes this handle Send + Sync.
+pub struct CpalSink {
+    sample_buffer: Arc<SampleBuffer>,
+    format: OutputFormat,
+    shutdown: mpsc::Sender<()>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+
+impl CpalSink {
+    /// Opens `device` (or the default device if None) and starts streaming from a
+    /// new sample buffer.
+    pub fn open( device: Option<OutputDeviceId> ) -> Result<Self, OutputError> {
+        let ( ready_tx, ready_rx ) = mpsc::channel();
+        let ( shutdown_tx, shutdown_rx ) = mpsc::channel::<()>();
+
+        let thread = thread::Builder::new()
+            .name( "oxidio-output".into() )
+            .spawn( move || {
+                let opened = AudioOutput::open( device.as_ref() ).and_then( |( output, buffer )| {
+                    output.play()?;
+                    Ok(( output, buffer ))
+                });
+
+                let output = match opened {
+                    Ok(( output, buffer )) => {
+                        let format = OutputFormat {
+                            sample_rate: output.sample_rate(),
+                            channels: output.channels(),
+                        };
+                        let _ = ready_tx.send( Ok(( buffer, format )) );
+                        output
+                    }
+                    Err( e ) => {
+                        let _ = ready_tx.send( Err( e ) );
+                        return;
+                    }
+                };
+
+                // Keep the stream alive until the owner goes away
+                let _ = shutdown_rx.recv();
+                drop( output );
+                tracing::debug!( "Output thread: exiting" );
+            })
+            .map_err( |e| OutputError::BuildStream( e.to_string() ) )?;
+
+        let ( sample_buffer, format ) = ready_rx
+            .recv()
+            .map_err( |_| OutputError::BuildStream( "output thread exited".into() ) )??;
+
+        Ok( Self {
+            sample_buffer,
+            format,
+            shutdown: shutdown_tx,
+            thread: Some( thread ),
+        })
+    }
+}
+
+
+impl AudioSink for CpalSink {
+    fn format( &self ) -> OutputFormat {
+        self.format
+    }
+
+
+    fn sample_buffer( &self ) -> Arc<SampleBuffer> {
+        Arc::clone( &self.sample_buffer )
+    }
+}
+
+
+impl Drop for CpalSink {
+    fn drop( &mut self ) {
+        let _ = self.shutdown.send(());
+        if let Some( thread ) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_sample_buffer_wraps_around() {
+        let buffer = SampleBuffer::new( 8, 1 );
+        let mut out = [ 0.0; 5 ];
+        let mut next = 0.0;
+        let mut expected = 0.0;
+
+        for _ in 0..10 {
+            let chunk: Vec<f32> = ( 0..5 ).map( |i| next + i as f32 ).collect();
+            assert_eq!( buffer.push( &chunk ), 5 );
+            next += 5.0;
+
+            assert_eq!( buffer.pop( &mut out ), 5 );
+            for sample in out {
+                assert_eq!( sample, expected );
+                expected += 1.0;
+            }
+        }
+        assert_eq!( buffer.pushed(), 50 );
+        assert_eq!( buffer.consumed(), 50 );
+    }
+
+
+    #[test]
+    fn test_sample_buffer_full_and_clear() {
+        let buffer = SampleBuffer::new( 4, 1 );
+        assert_eq!( buffer.push( &[ 1.0; 6 ] ), 4 );
+        assert_eq!( buffer.push( &[ 1.0 ] ), 0 );
+
+        buffer.clear();
+        assert!( buffer.is_empty() );
+        assert_eq!( buffer.consumed(), 4 );
+
+        // Room again, and the discarded samples are never played
+        assert_eq!( buffer.push( &[ 2.0, 3.0 ] ), 2 );
+        let mut out = [ 0.0; 4 ];
+        assert_eq!( buffer.pop( &mut out ), 2 );
+        assert_eq!( out, [ 2.0, 3.0, 0.0, 0.0 ] );
+    }
+
+
+    #[test]
+    fn test_sample_buffer_counts_underruns() {
+        let buffer = SampleBuffer::new( 16, 1 );
+        let mut out = [ 0.0; 4 ];
+
+        // Idle before anything is pushed
+        buffer.pop( &mut out );
+        assert_eq!( buffer.underruns(), 0 );
+
+        // Running dry mid-stream counts once, however long it lasts
+        buffer.push( &[ 0.5; 6 ] );
+        buffer.pop( &mut out );
+        buffer.pop( &mut out );
+        buffer.pop( &mut out );
+        assert_eq!( buffer.underruns(), 1 );
+
+        // Draining at the end of the stream doesn't count
+        buffer.push( &[ 0.5; 6 ] );
+        buffer.end_of_stream();
+        buffer.pop( &mut out );
+        buffer.pop( &mut out );
+        assert_eq!( buffer.underruns(), 1 );
+    }
+
+
+    #[test]
+    fn test_heard_lags_by_output_latency() {
+        let buffer = SampleBuffer::new( 1024, 2 );
+        let mut out = [ 0.0; 200 ];
+        buffer.push( &[ 0.1; 400 ] );
+
+        // Without a reported latency everything consumed counts as heard
+        buffer.pop( &mut out );
+        assert_eq!( buffer.heard( 100 ), 200 );
+
+        // A second of latency at 100Hz holds back 100 frames, so nothing new is heard yet
+        buffer.set_output_latency( Duration::from_secs( 1 ) );
+        buffer.pop( &mut out );
+        assert_eq!( buffer.consumed(), 400 );
+        assert!( buffer.heard( 100 ) <= 2, "heard {}", buffer.heard( 100 ) );
+
+        // Never beyond what was consumed
+        buffer.set_output_latency( Duration::ZERO );
+        std::thread::sleep( Duration::from_millis( 50 ) );
+        assert_eq!( buffer.heard( 100_000 ), 400 );
+    }
+
+
+    #[test]
+    fn test_pause_fades_out_then_holds() {
+        let buffer = SampleBuffer::new( 64, 2 );
+        buffer.set_fade_length( 4 );
+        buffer.push( &[ 1.0; 32 ] );
+
+        // Pausing ramps down over 4 frames before the output goes quiet
+        buffer.set_paused( true );
+        assert!( buffer.is_paused() );
+        assert!( !buffer.fade_done() );
+        let mut out = [ 0.0; 12 ];
+        assert_eq!( buffer.pop( &mut out ), 12 );
+        assert_eq!( out, [ 0.75, 0.75, 0.5, 0.5, 0.25, 0.25, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0 ] );
+        assert!( buffer.fade_done() );
+
+        // Paused now, so nothing more is consumed
+        assert_eq!( buffer.pop( &mut out ), 0 );
+        assert_eq!( buffer.consumed(), 12 );
+
+        // Resuming ramps back up
+        buffer.set_paused( false );
+        let mut out = [ 0.0; 8 ];
+        buffer.pop( &mut out );
+        assert_eq!( out, [ 0.25, 0.25, 0.5, 0.5, 0.75, 0.75, 1.0, 1.0 ] );
+        assert!( buffer.fade_done() );
+    }
+
+
+    #[test]
+    fn test_fade_out_keeps_consuming() {
+        let buffer = SampleBuffer::new( 64, 1 );
+        buffer.set_fade_length( 2 );
+        buffer.push( &[ 1.0; 8 ] );
+
+        buffer.fade_out();
+        let mut out = [ 0.0; 4 ];
+        assert_eq!( buffer.pop( &mut out ), 4 );
+        assert_eq!( out, [ 0.5, 0.0, 0.0, 0.0 ] );
+        assert!( buffer.fade_done() );
+
+        // Unmuting skips the ramp
+        buffer.unmute();
+        buffer.pop( &mut out );
+        assert_eq!( out, [ 1.0; 4 ] );
+    }
+}
@@ -2,36 +2,55 @@
 //!
 //! The Player struct orchestrates decoding, output, and playback control.
 
-use std::path::PathBuf;
-use std::sync::{ Arc, RwLock };
+use std::collections::VecDeque;
+use std::path::{ Path, PathBuf };
+use std::sync::{ mpsc, Arc, Mutex, RwLock };
 use std::sync::atomic::{ AtomicBool, AtomicU64, Ordering };
 use std::thread;
-use std::time::Duration;
+use std::time::{ Duration, Instant };
 
-use rubato::{ FastFixedOut, PolynomialDegree, Resampler };
 use thiserror::Error;
 
-use crate::decoder::{ AudioMetadata, Decoder };
-use crate::output::{ AudioOutput, SampleBuffer };
+use crate::artwork;
+use crate::bookmarks::{ Bookmark, Bookmarks, DEFAULT_RESUME_THRESHOLD };
+use crate::chapters::Chapter;
+use crate::crossfade::{ Crossfade, CrossfadeCurve };
+use crate::cue::{ self, CueTrack };
+use crate::decoder::{ AudioStream, Decoder, DecoderError };
+use crate::dsp::{ DspChain, Remix, Volume };
+use crate::equalizer::{ Equalizer, EqualizerSettings };
+use crate::loudness::{ LoudnessCache, LoudnessMeter };
+use crate::metadata::AudioMetadata;
+use crate::output::{ CpalSink, OutputDeviceId, OutputError, OutputFormat, SampleBuffer };
 use crate::playlist::Playlist;
+use crate::replaygain::{ ReplayGainSettings, ReplayGainStage };
+use crate::resample::{ Resample, ResampleQuality, ResampleSettings };
+use crate::sink::{ AudioSink, NullSink, Pacing };
+use crate::spectrum::{ Spectrum, SpectrumAnalyzer, SpectrumSettings, FFT_SIZE };
+use crate::timestretch::{ clamp_speed, TimeStretch };
+use crate::volume::{ volume_db, volume_gain, Limiter };
 
 
-/// Converts planar samples back to interleaved format.
-/// [[L0, L1, ...], [R0, R1, ...]] → [L0, R0, L1, R1, ...]
-fn interleave( channels: &[Vec<f32>] ) -> Vec<f32> {
-    if channels.is_empty() || channels[ 0 ].is_empty() {
-        return Vec::new();
-    }
-    let frames = channels[ 0 ].len();
-    let num_ch = channels.len();
-    let mut out = Vec::with_capacity( frames * num_ch );
-    for f in 0..frames {
-        for ch in channels {
-            out.push( ch[ f ] );
-        }
-    }
-    out
-}
+/// How long before the end of a track the next playlist entry is opened.
+/// Gives slow (e.g. SMB) sources time to probe before the current track runs out.
+const PRELOAD_AHEAD: Duration = Duration::from_secs( 5 );
+
+/// Default length of the fades applied on pause, resume, stop, seek and volume changes.
+pub const DEFAULT_FADE: Duration = Duration::from_millis( 20 );
+
+/// Longest fade allowed by `Player::set_fade`.
+pub const MAX_FADE: Duration = Duration::from_millis( 100 );
+
+/// How often the position in a long track is saved while it plays, in case oxidio
+/// doesn't get to save it on the way out.
+const RESUME_SAVE_INTERVAL: Duration = Duration::from_secs( 15 );
+
+/// Going to the previous chapter within this long of the current one's start skips
+/// back a chapter; any later, it restarts the current one.
+const CHAPTER_RESTART: Duration = Duration::from_secs( 3 );
+
+/// Format played (silently) when the system has no output device.
+const FALLBACK_FORMAT: OutputFormat = OutputFormat { sample_rate: 44100, channels: 2 };
 
 
 /// Errors that can occur during playback.
@@ -48,6 +67,12 @@ pub enum PlayerError {
 
     #[error( "No track loaded" )]
     NoTrack,
+
+    #[error( "No chapter {0}" )]
+    NoChapter( usize ),
+
+    #[error( "No audio stream {0}" )]
+    NoStream( usize ),
 }
 
 
@@ -71,37 +96,558 @@ pub enum PlayerEvent {
 }
 
 
-/// Wrapper around AudioOutput that allows it to be stored in shared state.
+/// Details of the track whose samples are currently reaching the output.
 ///
-/// SAFETY: AudioOutput must only be accessed from the thread where it was created.
-/// The Player ensures this by only accessing the output from the main thread.
-#[allow( dead_code )] // Field is kept alive for its Drop impl which stops the audio stream
-struct AudioOutputHandle( AudioOutput );
+/// Replaced by the decode thread when a gapless transition reaches the device.
+#[derive( Clone )]
+struct TrackInfo {
+    /// Number of frames (samples / channels) decoded so far.
+    /// Counted before time-stretching, so it tracks the position within the track.
+    frames_decoded: Arc<AtomicU64>,
+    /// Where the decoded frames ended up in the output, to tell which one is being heard
+    timeline: Arc<Mutex<Timeline>>,
+    /// Sample rate of the source file
+    sample_rate: u32,
+    /// Total duration of the track
+    duration: Option<Duration>,
+    /// Quality the track is resampled with, if it doesn't play at the output rate
+    resample_quality: Option<ResampleQuality>,
+    /// Metadata extracted from the audio file
+    metadata: AudioMetadata,
+    /// Chapters within the track, in order
+    chapters: Vec<Chapter>,
+    /// Audio streams in the file, and the index of the one playing
+    streams: Vec<AudioStream>,
+    stream: usize,
+}
+
+
+impl TrackInfo {
+    /// Starts the timeline over: the next frame decoded is queued at `position` in the output.
+    fn start_at( &self, position: u64 ) {
+        let frame = self.frames_decoded.load( Ordering::Relaxed );
+        self.timeline.lock().unwrap().restart( position, frame );
+    }
+
+
+    /// Records that everything decoded so far has been queued up to `position` in the output.
+    fn queued_to( &self, position: u64 ) {
+        let frame = self.frames_decoded.load( Ordering::Relaxed );
+        self.timeline.lock().unwrap().mark( position, frame );
+    }
+}
+
+
+/// Maps positions in the output stream back to frames of a track, so the reported
+/// position follows what is heard rather than what has been decoded.
+#[derive( Default )]
+struct Timeline {
+    /// (`SampleBuffer` position, track frame) pairs, oldest first.
+    /// Positions in between are interpolated.
+    anchors: VecDeque<( u64, u64 )>,
+    /// Frame a requested seek is heading to, reported until the decode thread carries it out
+    seeking: Option<u64>,
+}
+
+
+impl Timeline {
+    fn restart( &mut self, position: u64, frame: u64 ) {
+        self.anchors.clear();
+        self.anchors.push_back(( position, frame ));
+    }
+
+
+    /// Records that the frames queued from the last anchor on carry on from `frame`,
+    /// e.g. after looping back.
+    fn jump( &mut self, frame: u64 ) {
+        if let Some( &( position, _ ) ) = self.anchors.back() {
+            self.anchors.push_back(( position, frame ));
+        }
+    }
+
+
+    fn mark( &mut self, position: u64, frame: u64 ) {
+        // Stages still filling up produce nothing, so there's nothing to anchor yet
+        if self.anchors.back().is_some_and( |&( last, _ )| last >= position ) {
+            return;
+        }
+        self.anchors.push_back(( position, frame ));
+    }
 
-// SAFETY: We guarantee AudioOutput is only used from the main thread.
-// cpal::Stream's raw pointers are only accessed by the audio callback thread
-// which is managed internally by cpal.
-unsafe impl Send for AudioOutputHandle {}
-unsafe impl Sync for AudioOutputHandle {}
+
+    /// Drops anchors that lie wholly before `heard`.
+    fn prune( &mut self, heard: u64 ) {
+        while self.anchors.len() > 1 && self.anchors[ 1 ].0 <= heard {
+            self.anchors.pop_front();
+        }
+    }
+
+
+    /// Returns the track frame heard at output position `heard`.
+    fn frame_at( &self, heard: u64 ) -> u64 {
+        if let Some( frame ) = self.seeking {
+            return frame;
+        }
+
+        let after = self.anchors.partition_point( |&( position, _ )| position <= heard );
+        match ( after.checked_sub( 1 ).map( |i| self.anchors[ i ] ), self.anchors.get( after ) ) {
+            ( Some(( p0, f0 )), Some( &( p1, f1 ) ) ) => {
+                f0 + ( f1.saturating_sub( f0 ) as f64 * ( heard - p0 ) as f64 / ( p1 - p0 ) as f64 ) as u64
+            }
+            ( Some(( _, frame )), None ) | ( None, Some( &( _, frame ) ) ) => frame,
+            ( None, None ) => 0,
+        }
+    }
+}
+
+
+/// Requests sent from the Player to a running decode thread.
+enum DecodeCommand {
+    /// Fade the current track out while fading this one in
+    CrossfadeTo( Box<TrackSource> ),
+    /// Jump to a position in the track being heard
+    Seek( Duration ),
+    /// Reopen the track being heard (e.g. on another audio stream), carrying on from where it is
+    Reopen,
+}
 
 
 /// Shared playback state between main thread and decode thread.
 struct PlaybackHandle {
     stop_flag: Arc<AtomicBool>,
     sample_buffer: Arc<SampleBuffer>,
-    #[allow( dead_code )] // Kept alive for its Drop impl which stops the audio stream
-    output: AudioOutputHandle,
+    config: SourceConfig,
     thread: Option<thread::JoinHandle<()>>,
-    /// Number of frames (samples / channels) decoded so far
-    frames_played: Arc<AtomicU64>,
-    /// Sample rate of the source file
-    sample_rate: u32,
-    /// Total duration of the track
-    duration: Option<Duration>,
+    commands: mpsc::Sender<DecodeCommand>,
+    /// The track currently being heard
+    track: Arc<RwLock<TrackInfo>>,
     /// Flag set when track ends naturally (EOF reached)
     track_ended: Arc<AtomicBool>,
-    /// Metadata extracted from the audio file
-    metadata: AudioMetadata,
+}
+
+
+/// Everything the decode thread needs, bundled so it can be moved in one piece.
+struct DecodeContext {
+    sample_buffer: Arc<SampleBuffer>,
+    stop_flag: Arc<AtomicBool>,
+    state: Arc<RwLock<PlaybackState>>,
+    track_ended: Arc<AtomicBool>,
+    track: Arc<RwLock<TrackInfo>>,
+    current_track: Arc<RwLock<Option<PathBuf>>>,
+    playlist: Arc<RwLock<Playlist>>,
+    events: mpsc::Sender<PlayerEvent>,
+    commands: mpsc::Receiver<DecodeCommand>,
+    crossfade: Arc<RwLock<Crossfade>>,
+    dsp: Arc<Mutex<DspChain>>,
+    bookmarks: Arc<Bookmarks>,
+    resume_threshold: Arc<RwLock<Option<Duration>>>,
+    config: SourceConfig,
+}
+
+
+/// Everything a TrackSource needs to know about how its output will be played.
+#[derive( Clone )]
+struct SourceConfig {
+    /// Format the output device is running at
+    output: OutputFormat,
+    replay_gain: Arc<RwLock<ReplayGainSettings>>,
+    loudness_cache: Arc<LoudnessCache>,
+    /// Playback speed factor (1.0 = normal)
+    speed: Arc<RwLock<f32>>,
+    /// Whether channel downmixes are scaled down to avoid clipping
+    normalize_downmix: Arc<RwLock<bool>>,
+    /// How tracks at other sample rates are converted to the output rate
+    resample: Arc<RwLock<ResampleSettings>>,
+    /// A-B loop within the track being played, as (start, end)
+    ab_loop: Arc<RwLock<Option<( Duration, Duration )>>>,
+    /// Used to tell whether a track is played as part of its album
+    playlist: Arc<RwLock<Playlist>>,
+    /// Audio stream picked for a track, kept whenever that track is reopened
+    audio_stream: Arc<RwLock<Option<( PathBuf, usize )>>>,
+}
+
+
+/// A decoder together with the per-track stages that bring it to the output format.
+struct TrackSource {
+    path: PathBuf,
+    /// The cue sheet track being played, if any
+    cue: Option<CueTrack>,
+    decoder: Decoder,
+    /// Resampling, remixing, time-stretching and gain for this track
+    chain: DspChain,
+    /// Measures untagged tracks on their first full playback
+    loudness_meter: Option<LoudnessMeter>,
+    loudness_cache: Arc<LoudnessCache>,
+    speed: Arc<RwLock<f32>>,
+    ab_loop: Arc<RwLock<Option<( Duration, Duration )>>>,
+    /// Frames still to drop from the decoder's output to land exactly on the loop start
+    skip: u64,
+    /// Samples decoded past the end of the loop, played if it's turned off before looping back
+    overrun: Vec<f32>,
+    /// Frame of the file the track starts at (non-zero for cue sheet tracks)
+    offset: u64,
+    /// Frames in the track, when it ends before the file does
+    length: Option<u64>,
+    info: TrackInfo,
+    /// Set once the decoder reached EOF and the chain has been flushed
+    finished: bool,
+}
+
+
+impl TrackSource {
+    /// Wraps an opened decoder, building the stages that bring it to the output format.
+    ///
+    /// For a cue sheet track, the decoder is moved to the track's start and stops at its end.
+    fn new( path: PathBuf, mut decoder: Decoder, cue: Option<CueTrack>, config: &SourceConfig ) -> Result<Self, PlayerError> {
+        let stream = config.audio_stream.read().unwrap().as_ref()
+            .filter( |( stream_path, _ )| *stream_path == path )
+            .map( |( _, index )| *index );
+        if let Some( index ) = stream {
+            decoder.select_stream( index ).map_err( |e| PlayerError::Decode( e.to_string() ) )?;
+        }
+
+        let output_sample_rate = config.output.sample_rate;
+        let source_sample_rate = decoder.sample_rate();
+        let channels = decoder.channels();
+
+        let to_frames = |d: Duration| ( d.as_secs_f64() * source_sample_rate as f64 ).round() as u64;
+        let start = cue.as_ref().map_or( Duration::ZERO, |track| track.start );
+        let offset = to_frames( start );
+        let length = cue.as_ref().and_then( CueTrack::duration ).map( to_frames );
+        let duration = match length {
+            Some( length ) => Some( Duration::from_secs_f64( length as f64 / source_sample_rate as f64 ) ),
+            None => decoder.duration().map( |d| Duration::from_secs_f64( d ).saturating_sub( start ) ),
+        };
+
+        let mut metadata = decoder.metadata();
+        if let Some( ref track ) = cue {
+            track.apply_to( &mut metadata );
+        }
+        metadata.duration = duration;
+        metadata.loudness = config.loudness_cache.get( &path, cue.as_ref() );
+        if metadata.artwork.is_empty() {
+            let file = cue.as_ref().map_or( path.as_path(), |track| track.file.as_path() );
+            metadata.artwork.extend( artwork::folder_image( file ) );
+        }
+
+        // Files without ReplayGain tags are measured while they play
+        let loudness_meter = ( metadata.loudness.is_none() && !metadata.replay_gain.is_tagged() )
+            .then( || LoudnessMeter::new( source_sample_rate, channels ) );
+
+        let resample_settings = *config.resample.read().unwrap();
+        let resampled = source_sample_rate != output_sample_rate;
+
+        let info = TrackInfo {
+            frames_decoded: Arc::new( AtomicU64::new( 0 ) ),
+            timeline: Arc::new( Mutex::new( Timeline::default() ) ),
+            sample_rate: source_sample_rate,
+            duration,
+            resample_quality: resampled.then_some( resample_settings.quality ),
+            metadata,
+            chapters: track_chapters( decoder.chapters(), start, duration ),
+            streams: decoder.streams().to_vec(),
+            stream: decoder.stream(),
+        };
+
+        let in_album = config.playlist.read().unwrap().in_album_order( &path );
+
+        let mut chain = DspChain::new();
+        if resampled {
+            let resample = Resample::new( source_sample_rate, output_sample_rate, channels, resample_settings )
+                .map_err( |e| PlayerError::Output( format!( "Failed to create resampler: {}", e ) ) )?;
+            chain.push( Box::new( resample ) );
+        }
+        chain.push( Box::new( Remix::with_layout(
+            decoder.channel_layout().clone(),
+            config.output.channels as usize,
+            Arc::clone( &config.normalize_downmix ),
+        )));
+        chain.push( Box::new( TimeStretch::new( Arc::clone( &config.speed ) ) ) );
+        chain.push( Box::new( ReplayGainStage::new(
+            Arc::clone( &config.replay_gain ),
+            info.metadata.replay_gain,
+            info.metadata.loudness,
+            in_album,
+        )));
+
+        let mut source = Self {
+            path,
+            cue,
+            decoder,
+            chain,
+            loudness_meter,
+            loudness_cache: Arc::clone( &config.loudness_cache ),
+            speed: Arc::clone( &config.speed ),
+            ab_loop: Arc::clone( &config.ab_loop ),
+            skip: 0,
+            overrun: Vec::new(),
+            offset,
+            length,
+            info,
+            finished: false,
+        };
+        if offset > 0 {
+            source.seek_decoder( 0 )
+                .map_err( |e| PlayerError::Decode( format!( "Failed to seek to track start: {}", e ) ) )?;
+        }
+        Ok( source )
+    }
+
+
+    /// Number of channels in the decoded file.
+    fn channels( &self ) -> usize {
+        self.decoder.channels()
+    }
+
+
+    /// Moves the decoder to `frame` of the track.
+    ///
+    /// The decoder can land a little early, so the frames before the target are
+    /// dropped as they're decoded. Returns the frame playback continues from.
+    fn seek_decoder( &mut self, frame: u64 ) -> Result<u64, DecoderError> {
+        let rate = self.info.sample_rate as f64;
+        let target = self.offset + frame;
+        let landed = ( self.decoder.seek( target as f64 / rate )? * rate ).round() as u64;
+
+        self.skip = target.saturating_sub( landed );
+        self.overrun.clear();
+        let frame = landed.max( target ) - self.offset;
+        self.info.frames_decoded.store( frame, Ordering::Relaxed );
+        Ok( frame )
+    }
+
+
+    /// Seeks the decoder and drops any samples still held by the chain.
+    ///
+    /// Returns the position the decoder actually landed on.
+    fn seek( &mut self, position: Duration ) -> Result<Duration, DecoderError> {
+        let rate = self.info.sample_rate as f64;
+        let frame = self.seek_decoder( ( position.as_secs_f64() * rate ) as u64 )?;
+
+        self.chain.reset();
+        self.finished = false;
+
+        // A partial measurement would be wrong, so wait for a full playback
+        self.loudness_meter = None;
+
+        Ok( Duration::from_secs_f64( frame as f64 / rate ) )
+    }
+
+
+    /// Caches the loudness measured over a complete playback, for use on later plays.
+    fn store_loudness( &mut self ) {
+        let Some( meter ) = self.loudness_meter.take() else {
+            return;
+        };
+
+        if let Some( loudness ) = meter.integrated() {
+            tracing::info!( "Measured {:?} at {:.1} LUFS", self.path, loudness.integrated_lufs );
+            self.loudness_cache.insert( &self.path, self.cue.as_ref(), loudness );
+        }
+    }
+
+
+    /// Returns the A-B loop as (start, end) frames of the track, if one is set.
+    fn loop_frames( &self ) -> Option<( u64, u64 )> {
+        let rate = self.info.sample_rate as f64;
+        self.ab_loop.read().unwrap().map( |( start, end )| {
+            ( ( start.as_secs_f64() * rate ).round() as u64, ( end.as_secs_f64() * rate ).round() as u64 )
+        })
+    }
+
+
+    /// Seeks back to the start of the A-B loop.
+    ///
+    /// The chain keeps running, so the end of the loop flows straight into its start.
+    fn loop_back( &mut self, start: u64 ) -> Result<(), DecoderError> {
+        let start = self.seek_decoder( start )?;
+        self.loudness_meter = None;
+        self.info.timeline.lock().unwrap().jump( start );
+        Ok(())
+    }
+
+
+    /// Time left until the decoder reaches the end of the track, if the duration is known.
+    ///
+    /// This is playing time, so it shrinks or grows with the playback speed. A track
+    /// that is looping never runs out.
+    fn remaining( &self ) -> Option<Duration> {
+        if self.ab_loop.read().unwrap().is_some() {
+            return None;
+        }
+        let decoded = self.info.frames_decoded.load( Ordering::Relaxed ) as f64 / self.info.sample_rate as f64;
+        let speed = *self.speed.read().unwrap() as f64;
+        self.info.duration.map( |d| d.saturating_sub( Duration::from_secs_f64( decoded ) ).div_f64( speed ) )
+    }
+
+
+    /// Decodes the next chunk of interleaved samples in the output format.
+    ///
+    /// Returns None once the track is exhausted and the chain has been flushed.
+    fn next_chunk( &mut self ) -> Result<Option<Vec<f32>>, DecoderError> {
+        if self.finished {
+            return Ok( None );
+        }
+
+        let channels = self.channels();
+        let sample_rate = self.info.sample_rate;
+        let ab_loop = self.loop_frames();
+
+        let mut samples = loop {
+            if let Some( ( start, end ) ) = ab_loop {
+                if self.info.frames_decoded.load( Ordering::Relaxed ) >= end {
+                    self.loop_back( start )?;
+                }
+            }
+            if !self.overrun.is_empty() {
+                break std::mem::take( &mut self.overrun );
+            }
+            let decoded = self.info.frames_decoded.load( Ordering::Relaxed );
+
+            // A cue sheet track ends where the next one starts
+            let next = match self.length {
+                Some( length ) if decoded >= length => None,
+                _ => self.decoder.decode_next()?,
+            };
+            match next {
+                Some( mut samples ) => {
+                    let skipped = self.skip.min( ( samples.len() / channels ) as u64 );
+                    samples.drain( ..skipped as usize * channels );
+                    self.skip -= skipped;
+                    if !samples.is_empty() {
+                        break samples;
+                    }
+                }
+                // A loop ending past the end of the track wraps around at the end
+                None if ab_loop.is_some_and( |( start, _ )| decoded > start ) => {
+                    self.loop_back( ab_loop.unwrap().0 )?;
+                }
+                None => {
+                    self.finished = true;
+                    self.store_loudness();
+
+                    // EOF - flush anything the stages are still holding
+                    let tail = self.chain.flush( channels, sample_rate );
+                    return Ok( ( !tail.is_empty() ).then_some( tail ) );
+                }
+            }
+        };
+
+        if let Some( length ) = self.length {
+            let decoded = self.info.frames_decoded.load( Ordering::Relaxed );
+            samples.truncate( length.saturating_sub( decoded ) as usize * channels );
+        }
+
+        // Stop exactly at the end of the loop
+        if let Some( ( _, end ) ) = ab_loop {
+            let decoded = self.info.frames_decoded.load( Ordering::Relaxed );
+            let keep = end.saturating_sub( decoded ) as usize * channels;
+            if samples.len() > keep {
+                self.overrun = samples.split_off( keep );
+            }
+        }
+
+        if let Some( ref mut meter ) = self.loudness_meter {
+            meter.process( &samples );
+        }
+
+        // Track position based on source frames (before resampling)
+        let source_frames = samples.len() / channels;
+        self.info.frames_decoded.fetch_add( source_frames as u64, Ordering::Relaxed );
+
+        self.chain.process( &mut samples, channels, sample_rate );
+        Ok( Some( samples ) )
+    }
+}
+
+
+/// A track whose first sample has been queued but not yet heard.
+struct PendingTrackChange {
+    /// `SampleBuffer::pushed()` at the moment the track's first sample was queued
+    boundary: u64,
+    path: PathBuf,
+    info: TrackInfo,
+    /// Whether the playlist still has to be advanced to this track
+    advance_playlist: bool,
+}
+
+
+impl PendingTrackChange {
+    /// Queues `source` to be announced once the next sample pushed is heard.
+    fn starting( ctx: &DecodeContext, source: &TrackSource, advance_playlist: bool ) -> Self {
+        let boundary = ctx.sample_buffer.pushed();
+        source.info.start_at( boundary );
+        Self {
+            boundary,
+            path: source.path.clone(),
+            info: source.info.clone(),
+            advance_playlist,
+        }
+    }
+}
+
+
+/// A track being faded out underneath the one that replaced it.
+struct FadeOut {
+    source: TrackSource,
+    /// Samples decoded from the outgoing track but not yet mixed
+    pending: VecDeque<f32>,
+    /// Frames of the fade already mixed
+    position: usize,
+    /// Total fade length in frames
+    length: usize,
+    curve: CrossfadeCurve,
+}
+
+
+impl FadeOut {
+    /// Starts fading `source` out over `length` frames.
+    fn new( source: TrackSource, length: usize, curve: CrossfadeCurve ) -> Self {
+        Self {
+            source,
+            pending: VecDeque::new(),
+            position: 0,
+            length: length.max( 1 ),
+            curve,
+        }
+    }
+
+
+    /// Mixes the outgoing track into `samples` (the incoming track) in place.
+    ///
+    /// Returns false once the fade has completed and the outgoing track can be dropped.
+    fn mix( &mut self, samples: &mut [f32], channels: usize ) -> bool {
+        // Decode enough of the outgoing track to cover this chunk
+        while self.pending.len() < samples.len() {
+            match self.source.next_chunk() {
+                Ok( Some( chunk ) ) => self.pending.extend( chunk ),
+                Ok( None ) => break,
+                Err( e ) => {
+                    tracing::warn!( "Decode error in outgoing track: {}", e );
+                    break;
+                }
+            }
+        }
+
+        for ( i, frame ) in samples.chunks_mut( channels ).enumerate() {
+            let progress = ( self.position + i ) as f32 / self.length as f32;
+            let ( out_gain, in_gain ) = self.curve.gains( progress );
+            for sample in frame.iter_mut() {
+                let outgoing = self.pending.pop_front().unwrap_or( 0.0 );
+                *sample = *sample * in_gain + outgoing * out_gain;
+            }
+        }
+
+        self.position += samples.len() / channels;
+        self.position < self.length
+    }
+}
+
+
+/// The next playlist entry, being opened ahead of the end of the current track.
+struct Preload {
+    path: PathBuf,
+    thread: thread::JoinHandle<Result<( Decoder, Option<CueTrack> ), PlayerError>>,
 }
 
 
@@ -111,97 +657,212 @@ pub struct Player {
     current_track: Arc<RwLock<Option<PathBuf>>>,
     playlist: Arc<RwLock<Playlist>>,
     playback: Arc<RwLock<Option<PlaybackHandle>>>,
-    /// Volume level (0.0 to 1.5), persisted across track changes
+    /// Linear gain of the volume stage, persisted across track changes
     volume: Arc<RwLock<f32>>,
+    /// Output stream shared by every track, opened on first playback
+    output: Mutex<Option<Box<dyn AudioSink>>>,
+    /// Set when the sink was supplied by the caller, so it's never reopened on a device
+    fixed_output: bool,
+    /// Device to open the output on (None = system default)
+    output_device: RwLock<Option<OutputDeviceId>>,
+    crossfade: Arc<RwLock<Crossfade>>,
+    replay_gain: Arc<RwLock<ReplayGainSettings>>,
+    loudness_cache: Arc<LoudnessCache>,
+    equalizer: Arc<RwLock<EqualizerSettings>>,
+    /// Playback speed factor (0.5 to 3.0), applied without changing pitch
+    speed: Arc<RwLock<f32>>,
+    normalize_downmix: Arc<RwLock<bool>>,
+    resample: Arc<RwLock<ResampleSettings>>,
+    /// A-B loop within the current track
+    ab_loop: Arc<RwLock<Option<( Duration, Duration )>>>,
+    /// Audio stream picked for the current (or last) track, for files with several
+    audio_stream: Arc<RwLock<Option<( PathBuf, usize )>>>,
+    /// Length of the fades that keep pausing, stopping, seeking and volume changes click-free
+    fade: Arc<RwLock<Duration>>,
+    /// Where long tracks were left off, and the user's named bookmarks
+    bookmarks: Arc<Bookmarks>,
+    /// Tracks at least this long resume where they were left off (None = never)
+    resume_threshold: Arc<RwLock<Option<Duration>>>,
+    /// Stages applied to the mixed output of every track, in order
+    dsp: Arc<Mutex<DspChain>>,
+    /// Analyzes the played audio for `vis_data`
+    spectrum: Mutex<SpectrumAnalyzer>,
+    events_tx: mpsc::Sender<PlayerEvent>,
+    events_rx: Mutex<mpsc::Receiver<PlayerEvent>>,
 }
 
 
 impl Player {
     /// Creates a new Player instance.
+    ///
+    /// Plays through the system's sound card, opened on first playback. Without an
+    /// output device, playback carries on silently in real time.
     pub fn new() -> Result<Self, PlayerError> {
-        Ok( Self {
+        Ok( Self::build( None, LoudnessCache::load(), Bookmarks::load() ) )
+    }
+
+
+    /// Creates a Player that sends its output to `sink` instead of a sound card.
+    ///
+    /// Useful for headless use and tests, e.g. with a `WavSink` or `NullSink`. The
+    /// loudness cache and bookmarks are given rather than loaded from the user's data
+    /// directory; `LoudnessCache::open( None )` and `Bookmarks::open( None )` keep them
+    /// in memory.
+    pub fn with_sink( sink: Box<dyn AudioSink>, loudness_cache: LoudnessCache, bookmarks: Bookmarks ) -> Result<Self, PlayerError> {
+        Ok( Self::build( Some( sink ), loudness_cache, bookmarks ) )
+    }
+
+
+    fn build( sink: Option<Box<dyn AudioSink>>, loudness_cache: LoudnessCache, bookmarks: Bookmarks ) -> Self {
+        let ( events_tx, events_rx ) = mpsc::channel();
+        let volume = Arc::new( RwLock::new( 1.0 ) );
+        let equalizer = Arc::new( RwLock::new( EqualizerSettings::default() ) );
+        let fade = Arc::new( RwLock::new( DEFAULT_FADE ) );
+
+        let mut dsp = DspChain::new();
+        dsp.push( Box::new( Equalizer::new( Arc::clone( &equalizer ) ) ) );
+        dsp.push( Box::new( Volume::with_fade( Arc::clone( &volume ), Arc::clone( &fade ) ) ) );
+        dsp.push( Box::new( Limiter::new() ) );
+
+        Self {
             state: Arc::new( RwLock::new( PlaybackState::Stopped ) ),
             current_track: Arc::new( RwLock::new( None ) ),
             playlist: Arc::new( RwLock::new( Playlist::new() ) ),
             playback: Arc::new( RwLock::new( None ) ),
-            volume: Arc::new( RwLock::new( 1.0 ) ),
-        })
+            volume,
+            fixed_output: sink.is_some(),
+            output: Mutex::new( sink ),
+            output_device: RwLock::new( None ),
+            crossfade: Arc::new( RwLock::new( Crossfade::default() ) ),
+            replay_gain: Arc::new( RwLock::new( ReplayGainSettings::default() ) ),
+            loudness_cache: Arc::new( loudness_cache ),
+            equalizer,
+            speed: Arc::new( RwLock::new( 1.0 ) ),
+            normalize_downmix: Arc::new( RwLock::new( false ) ),
+            resample: Arc::new( RwLock::new( ResampleSettings::default() ) ),
+            ab_loop: Arc::new( RwLock::new( None ) ),
+            audio_stream: Arc::new( RwLock::new( None ) ),
+            fade,
+            bookmarks: Arc::new( bookmarks ),
+            resume_threshold: Arc::new( RwLock::new( Some( DEFAULT_RESUME_THRESHOLD ) ) ),
+            dsp: Arc::new( Mutex::new( dsp ) ),
+            spectrum: Mutex::new( SpectrumAnalyzer::default() ),
+            events_tx,
+            events_rx: Mutex::new( events_rx ),
+        }
     }
 
 
     /// Starts playback of the specified file.
+    ///
+    /// Long tracks pick up where they were left off.
     pub fn play( &self, path: PathBuf ) -> Result<(), PlayerError> {
+        tracing::info!( "Playing: {:?}", path );
+        self.set_loop( None );
+        let resume = self.resume_point( &path );
+        self.start( path, resume, false )
+    }
+
+
+    /// Starts playback of the specified file at `position`.
+    pub fn play_from( &self, path: PathBuf, position: Duration ) -> Result<(), PlayerError> {
+        tracing::info!( "Playing: {:?} from {:?}", path, position );
+        self.set_loop( None );
+        self.start( path, Some( position ), false )
+    }
+
+
+    /// Returns the shared output stream, opening the device on first use.
+    fn output( &self ) -> Result<( Arc<SampleBuffer>, OutputFormat ), PlayerError> {
+        let mut output = self.output.lock().unwrap();
+        if output.is_none() {
+            let device = self.output_device.read().unwrap().clone();
+            let sink: Box<dyn AudioSink> = match CpalSink::open( device ) {
+                Ok( sink ) => Box::new( sink ),
+                Err( OutputError::NoDevice ) => {
+                    tracing::warn!( "No output device available, playing silently" );
+                    let _ = self.events_tx.send( PlayerEvent::Error {
+                        message: "No output device available, playing silently".into(),
+                    });
+                    Box::new( NullSink::new( FALLBACK_FORMAT, Pacing::Realtime )
+                        .map_err( |e| PlayerError::Output( e.to_string() ) )? )
+                }
+                Err( e ) => return Err( PlayerError::Output( e.to_string() ) ),
+            };
+            *output = Some( sink );
+        }
+        let output = output.as_ref().unwrap();
+        let ( buffer, format ) = ( output.sample_buffer(), output.format() );
+        buffer.set_fade_length( fade_frames( *self.fade.read().unwrap(), format ) );
+        Ok(( buffer, format ))
+    }
+
+
+    /// Stops any current playback and starts decoding `path`, optionally from an offset.
+    fn start( &self, path: PathBuf, position: Option<Duration>, paused: bool ) -> Result<(), PlayerError> {
         // Stop any current playback
         self.stop()?;
 
-        tracing::info!( "Playing: {:?}", path );
+        // Don't let audio the output stages were holding back leak into the new track
+        self.dsp.lock().unwrap().reset();
 
         // Open the decoder
-        let mut decoder = Decoder::open( &path )
-            .map_err( |e| PlayerError::FileOpen( e.to_string() ) )?;
+        let ( decoder, cue ) = open_track( &path )?;
 
-        let source_sample_rate = decoder.sample_rate();
-        let channels = decoder.channels() as u16;
-        let duration = decoder.duration().map( |secs| Duration::from_secs_f64( secs ) );
-        let metadata = decoder.metadata();
-
-        // Create audio output - this also creates the sample buffer with proper channel config
-        let ( output, sample_buffer ) = AudioOutput::new( source_sample_rate, channels )
-            .map_err( |e| PlayerError::Output( e.to_string() ) )?;
-
-        // Apply stored volume to new sample buffer
-        let vol = *self.volume.read().unwrap();
-        sample_buffer.set_volume( vol );
-
-        let target_sample_rate = output.sample_rate();
-
-        output.play().map_err( |e| PlayerError::Output( e.to_string() ) )?;
-
-        // Create resampler if sample rates don't match
-        let resampler = if source_sample_rate != target_sample_rate {
-            tracing::info!(
-                "Resampling: {} Hz → {} Hz",
-                source_sample_rate,
-                target_sample_rate
-            );
-
-            // Use FastFixedOut which handles variable input sizes
-            let resampler = FastFixedOut::<f32>::new(
-                target_sample_rate as f64 / source_sample_rate as f64,
-                2.0,  // max relative input/output size ratio
-                PolynomialDegree::Cubic,
-                1024, // output chunk size
-                channels as usize,
-            ).map_err( |e| PlayerError::Output( format!( "Failed to create resampler: {}", e ) ) )?;
-
-            Some( resampler )
-        } else {
-            None
+        // Attach to the long-lived output stream
+        let ( sample_buffer, output ) = self.output()?;
+
+        // Start paused if requested (e.g. seeking while paused)
+        sample_buffer.set_paused( paused );
+        if position.is_none() && !paused {
+            // A new track starts from the top, so there's nothing to fade in
+            sample_buffer.unmute();
+        }
+
+        let config = SourceConfig {
+            output,
+            replay_gain: Arc::clone( &self.replay_gain ),
+            loudness_cache: Arc::clone( &self.loudness_cache ),
+            speed: Arc::clone( &self.speed ),
+            normalize_downmix: Arc::clone( &self.normalize_downmix ),
+            resample: Arc::clone( &self.resample ),
+            ab_loop: Arc::clone( &self.ab_loop ),
+            playlist: Arc::clone( &self.playlist ),
+            audio_stream: Arc::clone( &self.audio_stream ),
         };
+        let mut source = TrackSource::new( path.clone(), decoder, cue, &config )?;
 
-        // Set up control flags and position tracking
+        if let Some( position ) = position {
+            source.seek( position )
+                .map_err( |e| PlayerError::Decode( e.to_string() ) )?;
+        }
+
+        // Set up control flags
         let stop_flag = Arc::new( AtomicBool::new( false ) );
-        let frames_played = Arc::new( AtomicU64::new( 0 ) );
         let track_ended = Arc::new( AtomicBool::new( false ) );
-
-        // Clone for the decode thread
-        let stop_flag_clone = Arc::clone( &stop_flag );
-        let sample_buffer_clone = Arc::clone( &sample_buffer );
-        let state_clone = Arc::clone( &self.state );
-        let frames_played_clone = Arc::clone( &frames_played );
-        let track_ended_clone = Arc::clone( &track_ended );
+        let track = Arc::new( RwLock::new( source.info.clone() ) );
+        let ( commands_tx, commands_rx ) = mpsc::channel();
+
+        let context = DecodeContext {
+            sample_buffer: Arc::clone( &sample_buffer ),
+            stop_flag: Arc::clone( &stop_flag ),
+            state: Arc::clone( &self.state ),
+            track_ended: Arc::clone( &track_ended ),
+            track: Arc::clone( &track ),
+            current_track: Arc::clone( &self.current_track ),
+            playlist: Arc::clone( &self.playlist ),
+            events: self.events_tx.clone(),
+            commands: commands_rx,
+            crossfade: Arc::clone( &self.crossfade ),
+            dsp: Arc::clone( &self.dsp ),
+            bookmarks: Arc::clone( &self.bookmarks ),
+            resume_threshold: Arc::clone( &self.resume_threshold ),
+            config: config.clone(),
+        };
 
         // Spawn decode thread
         let thread = thread::spawn( move || {
-            Self::decode_loop(
-                decoder,
-                sample_buffer_clone,
-                stop_flag_clone,
-                state_clone,
-                resampler,
-                frames_played_clone,
-                track_ended_clone,
-            );
+            Self::decode_loop( context, source );
         });
 
         // Store playback handle
@@ -210,13 +871,11 @@ impl Player {
             *playback = Some( PlaybackHandle {
                 stop_flag,
                 sample_buffer,
-                output: AudioOutputHandle( output ),
+                config,
                 thread: Some( thread ),
-                frames_played,
-                sample_rate: source_sample_rate,
-                duration,
+                commands: commands_tx,
+                track,
                 track_ended,
-                metadata,
             });
         }
 
@@ -228,7 +887,7 @@ impl Player {
 
         {
             let mut state = self.state.write().unwrap();
-            *state = PlaybackState::Playing;
+            *state = if paused { PlaybackState::Paused } else { PlaybackState::Playing };
         }
 
         Ok(())
@@ -236,158 +895,354 @@ impl Player {
 
 
     /// The decode loop that runs in a separate thread.
-    fn decode_loop(
-        mut decoder: Decoder,
-        sample_buffer: Arc<SampleBuffer>,
-        stop_flag: Arc<AtomicBool>,
-        state: Arc<RwLock<PlaybackState>>,
-        mut resampler: Option<FastFixedOut<f32>>,
-        frames_played: Arc<AtomicU64>,
-        track_ended: Arc<AtomicBool>,
-    ) {
-        let channels = decoder.channels();
-
-        // Input buffer for resampler (stores planar samples per channel)
-        let mut resample_input: Vec<Vec<f32>> = ( 0..channels ).map( |_| Vec::new() ).collect();
+    ///
+    /// When the current track nears its end, the next playlist entry is opened in the
+    /// background and its samples are queued straight after the current ones, so the
+    /// output never sees a gap between tracks. With a crossfade configured, the two
+    /// tracks overlap and are mixed through the fade curve instead.
+    fn decode_loop( ctx: DecodeContext, mut source: TrackSource ) {
+        let channels = ctx.config.output.channels as usize;
+        let mut preload: Option<Preload> = None;
+        // Set once a preload has been attempted for the current source
+        let mut preloaded = false;
+        let mut fade_out: Option<FadeOut> = None;
+        // Set once the track is exhausted and we're waiting for the output to play it out
+        let mut draining = false;
+        let mut resume_saved = Instant::now();
+        // Decoded samples still to go through the output stages, and how far they've got
+        let mut unprocessed: Vec<f32> = Vec::new();
+        let mut processed = 0;
+        let slice = ( ctx.config.output.sample_rate as usize / 100 ).max( 1 ) * channels;
+
+        // The first track is announced once its first sample is heard, like any other
+        let mut pending: VecDeque<PendingTrackChange> = VecDeque::new();
+        pending.push_back( PendingTrackChange::starting( &ctx, &source, false ) );
 
         loop {
             // Check for stop signal
-            if stop_flag.load( Ordering::Relaxed ) {
+            if ctx.stop_flag.load( Ordering::Relaxed ) {
                 tracing::debug!( "Decode loop: stop signal received" );
                 break;
             }
 
+            Self::apply_track_changes( &ctx, &mut pending );
+
+            if resume_saved.elapsed() >= RESUME_SAVE_INTERVAL {
+                Self::save_position( &ctx );
+                resume_saved = Instant::now();
+            }
+
+            // Handle requests from the player. Seeks are coalesced so that only the most
+            // recent one is carried out.
+            let mut seek_to = None;
+            let mut reopen = false;
+            while let Ok( command ) = ctx.commands.try_recv() {
+                match command {
+                    DecodeCommand::CrossfadeTo( next ) => {
+                        let fade = *ctx.crossfade.read().unwrap();
+                        tracing::info!( "Crossfading to {:?}", next.path );
+                        // The outgoing track's decoded samples are queued before the change
+                        Self::output_samples( &ctx, &mut unprocessed.split_off( processed ) );
+                        source.info.queued_to( ctx.sample_buffer.pushed() );
+                        unprocessed.clear();
+                        processed = 0;
+                        pending.push_back( PendingTrackChange::starting( &ctx, &next, false ) );
+                        // Any fade already in progress is cut short
+                        let outgoing = std::mem::replace( &mut source, *next );
+                        let length = ( fade.duration.as_secs_f64() * ctx.config.output.sample_rate as f64 ) as usize;
+                        fade_out = Some( FadeOut::new( outgoing, length, fade.curve ) );
+                        preload = None;
+                        preloaded = false;
+                        draining = false;
+                    }
+                    DecodeCommand::Seek( position ) => seek_to = Some( position ),
+                    DecodeCommand::Reopen => reopen = true,
+                }
+            }
+            if reopen && seek_to.is_none() {
+                let track = ctx.track.read().unwrap();
+                seek_to = Some( heard_position( &track, &ctx.sample_buffer, ctx.config.output.sample_rate ) );
+            }
+
+            if let Some( position ) = seek_to {
+                Self::seek_source( &ctx, &mut source, &mut pending, position, reopen );
+                unprocessed.clear();
+                processed = 0;
+                fade_out = None;
+                preload = None;
+                preloaded = false;
+                draining = false;
+            }
+
             // Check for pause signal - if paused, just sleep
-            if sample_buffer.is_paused() {
+            if ctx.sample_buffer.is_paused() {
                 thread::sleep( Duration::from_millis( 10 ) );
                 continue;
             }
 
+            let fade = *ctx.crossfade.read().unwrap();
+            let fade_ahead = if fade.is_enabled() { fade.duration } else { Duration::ZERO };
+            let remaining = source.remaining();
+
+            // Start opening the next track shortly before this one runs out
+            if !preloaded && remaining.is_some_and( |r| r <= PRELOAD_AHEAD + fade_ahead ) {
+                preload = Self::start_preload( &ctx );
+                preloaded = true;
+            }
+
+            // Crossfade into the next track once it's open and we're within the fade window
+            if fade.is_enabled() && fade_out.is_none() && processed == unprocessed.len() {
+                let ready = preload.as_ref().is_some_and( |p| p.thread.is_finished() );
+                if let ( true, Some( remaining ) ) = ( ready, remaining.filter( |r| *r <= fade.duration ) ) {
+                    if let Some( next ) = preload.take().and_then( |p| Self::finish_preload( &ctx, p ) ) {
+                        tracing::info!( "Crossfading to {:?}", next.path );
+                        pending.push_back( PendingTrackChange::starting( &ctx, &next, true ) );
+                        let outgoing = std::mem::replace( &mut source, next );
+                        let length = ( remaining.as_secs_f64() * ctx.config.output.sample_rate as f64 ) as usize;
+                        fade_out = Some( FadeOut::new( outgoing, length, fade.curve ) );
+                        preloaded = false;
+                    }
+                }
+            }
+
             // Check if output buffer has room
             // Don't decode too far ahead - keep about 50ms buffered
-            let target_buffer = ( decoder.sample_rate() as usize * channels ) / 20;
-            if sample_buffer.len() > target_buffer {
+            let target_buffer = ( ctx.config.output.sample_rate as usize * channels ) / 20;
+            if ctx.sample_buffer.len() > target_buffer {
                 thread::sleep( Duration::from_millis( 5 ) );
                 continue;
             }
 
-            // Decode next chunk
-            match decoder.decode_next() {
-                Ok( Some( samples ) ) => {
-                    // Track position based on source frames (before resampling)
-                    let source_frames = samples.len() / channels;
-                    frames_played.fetch_add( source_frames as u64, Ordering::Relaxed );
-
-                    // Apply resampling if needed
-                    let output_samples = if let Some( ref mut resampler ) = resampler {
-                        // Add new samples to input buffer (convert interleaved to planar)
-                        for chunk in samples.chunks( channels ) {
-                            for ( ch_idx, sample ) in chunk.iter().enumerate() {
-                                if ch_idx < resample_input.len() {
-                                    resample_input[ ch_idx ].push( *sample );
-                                }
-                            }
-                        }
+            // Volume, EQ and the limiter only see audio about to be queued, ~10 ms at a
+            // time, so changes to them are heard within the buffered ~60 ms
+            if processed < unprocessed.len() {
+                let end = ( processed + slice ).min( unprocessed.len() );
+                Self::output_samples( &ctx, &mut unprocessed[ processed..end ].to_vec() );
+                processed = end;
+                if processed == unprocessed.len() {
+                    source.info.queued_to( ctx.sample_buffer.pushed() );
+                    source.info.timeline.lock().unwrap().prune( ctx.sample_buffer.heard( ctx.config.output.sample_rate ) );
+                }
+                continue;
+            }
+
+            // Decode next chunk
+            match source.next_chunk() {
+                Ok( Some( mut samples ) ) => {
+                    if let Some( ref mut fade ) = fade_out {
+                        if !fade.mix( &mut samples, channels ) {
+                            fade_out = None;
+                        }
+                    }
+                    unprocessed = samples;
+                    processed = 0;
+                }
+                Ok( None ) => {
+                    if !draining {
+                        tracing::info!( "Decode loop: reached end of file" );
+                        fade_out = None;
+
+                        // Continue straight into the next track if we can
+                        let next_preload = if preloaded { preload.take() } else { Self::start_preload( &ctx ) };
+                        if let Some( next ) = next_preload.and_then( |p| Self::finish_preload( &ctx, p ) ) {
+                            tracing::info!( "Gapless transition to {:?}", next.path );
+                            // A loop that let its track end belongs to that track alone
+                            *ctx.config.ab_loop.write().unwrap() = None;
+                            pending.push_back( PendingTrackChange::starting( &ctx, &next, true ) );
+                            source = next;
+                            preloaded = false;
+                            continue;
+                        }
+                        // Let out what the output stages are holding back
+                        let tail = ctx.dsp.lock().unwrap().flush( channels, ctx.config.output.sample_rate );
+                        Self::push_samples( &ctx, &tail );
+
+                        ctx.sample_buffer.end_of_stream();
+                        draining = true;
+                    }
+
+                    // Wait for the last sample to be heard (still handling seeks), then signal end
+                    if ctx.sample_buffer.heard( ctx.config.output.sample_rate ) < ctx.sample_buffer.pushed() {
+                        thread::sleep( Duration::from_millis( 10 ) );
+                        continue;
+                    }
+                    Self::apply_track_changes( &ctx, &mut pending );
+
+                    if !ctx.stop_flag.load( Ordering::Relaxed ) {
+                        // Played to the end, so the next play starts from the top
+                        Self::save_position( &ctx );
+                        // Signal that track ended naturally (not stopped by user)
+                        ctx.track_ended.store( true, Ordering::Relaxed );
+                        let _ = ctx.events.send( PlayerEvent::TrackEnded );
+                        // Update state to stopped
+                        let mut s = ctx.state.write().unwrap();
+                        *s = PlaybackState::Stopped;
+                    }
+                    break;
+                }
+                Err( e ) => {
+                    tracing::error!( "Decode error: {}", e );
+                    let _ = ctx.events.send( PlayerEvent::Error { message: e.to_string() } );
+                    break;
+                }
+            }
+        }
+
+        tracing::debug!( "Decode loop: exiting" );
+    }
+
+
+    /// Moves the track being heard to `position`, discarding everything already queued.
+    ///
+    /// If the next track has been queued but hasn't reached the output yet, the track
+    /// being heard is reopened so the seek applies to what the user is listening to.
+    /// With `reopen`, it's reopened regardless, picking up a change of audio stream.
+    fn seek_source(
+        ctx: &DecodeContext,
+        source: &mut TrackSource,
+        pending: &mut VecDeque<PendingTrackChange>,
+        position: Duration,
+        reopen: bool,
+    ) {
+        let heard = ctx.current_track.read().unwrap().clone();
+        if let Some( path ) = heard.filter( |path| reopen || *path != source.path ) {
+            let reopened = open_track( &path ).and_then( |( mut decoder, cue )| {
+                // Reopening the same file (e.g. for another audio stream) keeps its chapters
+                if path == source.path {
+                    decoder.reuse_chapters( source.decoder.chapters() );
+                }
+                TrackSource::new( path, decoder, cue, &ctx.config )
+            });
+            match reopened {
+                Ok( reopened ) => {
+                    *ctx.track.write().unwrap() = reopened.info.clone();
+                    *source = reopened;
+                    pending.clear();
+                }
+                Err( e ) => {
+                    tracing::warn!( "Failed to reopen track for seeking: {}", e );
+                    return;
+                }
+            }
+        }
+
+        ctx.sample_buffer.clear();
+        ctx.sample_buffer.fade_in();
+        ctx.dsp.lock().unwrap().reset();
+
+        match source.seek( position ) {
+            Ok( landed ) => tracing::debug!( "Seeked to {:?} (requested {:?})", landed, position ),
+            Err( e ) => tracing::warn!( "Seek failed: {}", e ),
+        }
+        source.info.start_at( ctx.sample_buffer.pushed() );
+        source.info.timeline.lock().unwrap().seeking = None;
+    }
+
+
+    /// Runs samples through the output stages and pushes them to the output buffer.
+    fn output_samples( ctx: &DecodeContext, samples: &mut Vec<f32> ) {
+        let channels = ctx.config.output.channels as usize;
+        ctx.dsp.lock().unwrap().process( samples, channels, ctx.config.output.sample_rate );
+        Self::push_samples( ctx, samples );
+    }
+
+
+    /// Pushes samples to the output buffer, waiting for room as needed.
+    fn push_samples( ctx: &DecodeContext, samples: &[f32] ) {
+        let mut offset = 0;
+        while offset < samples.len() && !ctx.stop_flag.load( Ordering::Relaxed ) {
+            let pushed = ctx.sample_buffer.push( &samples[ offset.. ] );
+            offset += pushed;
+            if pushed == 0 {
+                // Buffer full, wait a bit
+                thread::sleep( Duration::from_millis( 5 ) );
+            }
+        }
+    }
+
+
+    /// Starts opening the next playlist entry on a background thread.
+    fn start_preload( ctx: &DecodeContext ) -> Option<Preload> {
+        let path = ctx.playlist.read().unwrap().peek_next().cloned()?;
+        tracing::debug!( "Preloading next track: {:?}", path );
+
+        let open_path = path.clone();
+        // Chapters are read here too, rather than on the decode thread when the track starts
+        let thread = thread::spawn( move || {
+            open_track( &open_path ).inspect( |( decoder, _ )| {
+                decoder.chapters();
+            })
+        });
+        Some( Preload { path, thread } )
+    }
+
+
+    /// Waits for a preload to finish and prepares it to continue the current output.
+    ///
+    /// Returns None when the file failed to open. If the playlist changed since the
+    /// preload started, the new next entry is opened instead.
+    fn finish_preload( ctx: &DecodeContext, preload: Preload ) -> Option<TrackSource> {
+        // The user may have edited the playlist while we were opening the file
+        let still_next = ctx.playlist.read().unwrap().peek_next() == Some( &preload.path );
+        if !still_next {
+            return Self::start_preload( ctx ).and_then( |p| Self::finish_preload( ctx, p ) );
+        }
+
+        let ( decoder, cue ) = match preload.thread.join() {
+            Ok( Ok( opened ) ) => opened,
+            Ok( Err( e ) ) => {
+                tracing::warn!( "Failed to preload {:?}: {}", preload.path, e );
+                return None;
+            }
+            Err( _ ) => return None,
+        };
+
+        TrackSource::new( preload.path, decoder, cue, &ctx.config )
+            .map_err( |e| tracing::warn!( "Failed to prepare next track: {}", e ) )
+            .ok()
+    }
 
-                        // Process when we have enough input frames
-                        let mut output_interleaved = Vec::new();
-                        while resample_input[ 0 ].len() >= resampler.input_frames_next() {
-                            let needed = resampler.input_frames_next();
-
-                            // Extract needed frames from input buffer
-                            let input_chunk: Vec<Vec<f32>> = resample_input
-                                .iter_mut()
-                                .map( |ch| ch.drain( ..needed ).collect() )
-                                .collect();
-
-                            // Resample
-                            match resampler.process( &input_chunk, None ) {
-                                Ok( resampled ) => {
-                                    output_interleaved.extend( interleave( &resampled ) );
-                                }
-                                Err( e ) => {
-                                    tracing::error!( "Resample error: {}", e );
-                                    // Put samples back on error
-                                    for ( ch_idx, samples ) in input_chunk.into_iter().enumerate() {
-                                        for sample in samples.into_iter().rev() {
-                                            resample_input[ ch_idx ].insert( 0, sample );
-                                        }
-                                    }
-                                    break;
-                                }
-                            }
-                        }
 
-                        output_interleaved
-                    } else {
-                        samples
-                    };
-
-                    // Push samples to buffer
-                    if !output_samples.is_empty() {
-                        let mut offset = 0;
-                        while offset < output_samples.len() && !stop_flag.load( Ordering::Relaxed ) {
-                            let pushed = sample_buffer.push( &output_samples[ offset.. ] );
-                            offset += pushed;
-                            if pushed == 0 {
-                                // Buffer full, wait a bit
-                                thread::sleep( Duration::from_millis( 5 ) );
-                            }
-                        }
-                    }
-                }
-                Ok( None ) => {
-                    // EOF - flush any remaining samples in resample buffer
-                    if let Some( ref mut resampler ) = resampler {
-                        if !resample_input[ 0 ].is_empty() {
-                            // Use process_partial for remaining samples
-                            match resampler.process_partial( Some( &resample_input ), None ) {
-                                Ok( resampled ) => {
-                                    let output_interleaved = interleave( &resampled );
-                                    let mut offset = 0;
-                                    while offset < output_interleaved.len() && !stop_flag.load( Ordering::Relaxed ) {
-                                        let pushed = sample_buffer.push( &output_interleaved[ offset.. ] );
-                                        offset += pushed;
-                                        if pushed == 0 {
-                                            thread::sleep( Duration::from_millis( 5 ) );
-                                        }
-                                    }
-                                }
-                                Err( e ) => tracing::error!( "Final resample error: {}", e ),
-                            }
-                        }
-                    }
+    /// Saves the position of the track being heard, if it's long enough to resume.
+    fn save_position( ctx: &DecodeContext ) {
+        let Some( path ) = ctx.current_track.read().unwrap().clone() else {
+            return;
+        };
+        let track = ctx.track.read().unwrap();
+        let position = heard_position( &track, &ctx.sample_buffer, ctx.config.output.sample_rate );
+        let threshold = *ctx.resume_threshold.read().unwrap();
+        ctx.bookmarks.remember( &path, position, track.duration, threshold );
+    }
 
-                    // Wait for buffer to drain, then signal end
-                    tracing::info!( "Decode loop: reached end of file" );
-                    while !sample_buffer.is_empty() && !stop_flag.load( Ordering::Relaxed ) {
-                        thread::sleep( Duration::from_millis( 10 ) );
-                    }
-                    // Signal that track ended naturally (not stopped by user)
-                    track_ended.store( true, Ordering::Relaxed );
-                    // Update state to stopped
-                    {
-                        let mut s = state.write().unwrap();
-                        *s = PlaybackState::Stopped;
-                    }
-                    break;
-                }
-                Err( e ) => {
-                    tracing::error!( "Decode error: {}", e );
-                    break;
-                }
+
+    /// Publishes every queued track change whose first sample has been heard.
+    fn apply_track_changes( ctx: &DecodeContext, pending: &mut VecDeque<PendingTrackChange> ) {
+        let heard = ctx.sample_buffer.heard( ctx.config.output.sample_rate );
+
+        while pending.front().is_some_and( |change| heard > change.boundary ) {
+            let change = pending.pop_front().unwrap();
+
+            // Remember where the outgoing track was left, or forget it if it played out
+            Self::save_position( ctx );
+
+            if change.advance_playlist {
+                ctx.playlist.write().unwrap().next();
             }
-        }
 
-        tracing::debug!( "Decode loop: exiting" );
+            *ctx.track.write().unwrap() = change.info;
+            *ctx.current_track.write().unwrap() = Some( change.path.clone() );
+            let _ = ctx.events.send( PlayerEvent::TrackChanged { path: change.path } );
+        }
     }
 
 
-    /// Pauses playback.
+    /// Pauses playback, once the output has faded out.
     pub fn pause( &self ) -> Result<(), PlayerError> {
         let playback = self.playback.read().unwrap();
         if let Some( ref handle ) = *playback {
             handle.sample_buffer.set_paused( true );
+            self.wait_for_fade( &handle.sample_buffer );
+            self.remember_position( handle );
 
             let mut state = self.state.write().unwrap();
             *state = PlaybackState::Paused;
@@ -397,11 +1252,12 @@ impl Player {
     }
 
 
-    /// Resumes playback.
+    /// Resumes playback, fading back in.
     pub fn resume( &self ) -> Result<(), PlayerError> {
         let playback = self.playback.read().unwrap();
         if let Some( ref handle ) = *playback {
             handle.sample_buffer.set_paused( false );
+            self.wait_for_fade( &handle.sample_buffer );
 
             let mut state = self.state.write().unwrap();
             *state = PlaybackState::Playing;
@@ -411,11 +1267,19 @@ impl Player {
     }
 
 
-    /// Stops playback.
+    /// Stops playback, fading out whatever is still playing first.
     pub fn stop( &self ) -> Result<(), PlayerError> {
         let mut playback = self.playback.write().unwrap();
 
         if let Some( mut handle ) = playback.take() {
+            if !handle.track_ended.load( Ordering::Relaxed ) {
+                self.remember_position( &handle );
+            }
+            if !handle.sample_buffer.is_paused() && !handle.sample_buffer.is_empty() {
+                handle.sample_buffer.fade_out();
+                self.wait_for_fade( &handle.sample_buffer );
+            }
+
             // Signal stop
             handle.stop_flag.store( true, Ordering::Relaxed );
             handle.sample_buffer.clear();
@@ -425,7 +1289,7 @@ impl Player {
                 let _ = thread.join();
             }
 
-            // AudioOutput is dropped here, which stops the cpal stream
+            // The output stream keeps running (silent) for the next track
             tracing::info!( "Stopped" );
         }
 
@@ -444,6 +1308,66 @@ impl Player {
     }
 
 
+    /// Saves the position of the track being heard, if it's long enough to resume.
+    fn remember_position( &self, handle: &PlaybackHandle ) {
+        let Some( path ) = self.current_track() else {
+            return;
+        };
+        let track = handle.track.read().unwrap();
+        let position = heard_position( &track, &handle.sample_buffer, handle.config.output.sample_rate );
+        let threshold = *self.resume_threshold.read().unwrap();
+        self.bookmarks.remember( &path, position, track.duration, threshold );
+    }
+
+
+    /// Gets where `path` was left off, if it's long enough to resume.
+    fn resume_point( &self, path: &Path ) -> Option<Duration> {
+        self.resume_threshold.read().unwrap().and( self.bookmarks.resume_point( path ) )
+    }
+
+
+    /// Sets how long a track must be for playback to pick up where it was left off
+    /// (None = always start from the top).
+    pub fn set_resume_threshold( &self, threshold: Option<Duration> ) {
+        *self.resume_threshold.write().unwrap() = threshold;
+    }
+
+
+    /// Gets how long a track must be for playback to pick up where it was left off.
+    pub fn resume_threshold( &self ) -> Option<Duration> {
+        *self.resume_threshold.read().unwrap()
+    }
+
+
+    /// Bookmarks the current position under `name`.
+    pub fn add_bookmark( &self, name: &str ) -> Result<Bookmark, PlayerError> {
+        let path = self.current_track().ok_or( PlayerError::NoTrack )?;
+        Ok( self.bookmarks.add( &path, name, self.position() ) )
+    }
+
+
+    /// Lists the tracks' resume points followed by the named bookmarks.
+    pub fn bookmarks( &self ) -> Vec<Bookmark> {
+        self.bookmarks.list()
+    }
+
+
+    /// Deletes a bookmark or resume point.
+    pub fn remove_bookmark( &self, bookmark: &Bookmark ) -> bool {
+        self.bookmarks.remove( bookmark )
+    }
+
+
+    /// Goes to a bookmark, seeking within the current track or starting its own.
+    pub fn jump_to_bookmark( &self, bookmark: &Bookmark ) -> Result<(), PlayerError> {
+        if self.current_track().as_ref() == Some( &bookmark.path ) && !self.track_ended() {
+            self.seek( bookmark.position )
+        } else {
+            self.play_from( bookmark.path.clone(), bookmark.position )
+        }
+    }
+
+
     /// Gets the current playback state.
     pub fn state( &self ) -> PlaybackState {
         *self.state.read().unwrap()
@@ -462,13 +1386,25 @@ impl Player {
     }
 
 
+    /// Returns the next pending player event, if any.
+    ///
+    /// `TrackChanged` is only emitted once the track's first sample has reached the
+    /// output device, so gapless transitions are reported when they are heard.
+    pub fn poll_event( &self ) -> Option<PlayerEvent> {
+        self.events_rx.lock().unwrap().try_recv().ok()
+    }
+
+
     /// Gets the current playback position.
+    ///
+    /// This is the position within the track of the audio being heard, allowing for
+    /// what is still buffered and the output device's latency. It is in track time, so
+    /// one second of track time counts as one second whatever the playback speed.
     pub fn position( &self ) -> Duration {
         let playback = self.playback.read().unwrap();
         if let Some( ref handle ) = *playback {
-            let frames = handle.frames_played.load( Ordering::Relaxed );
-            let seconds = frames as f64 / handle.sample_rate as f64;
-            Duration::from_secs_f64( seconds )
+            let track = handle.track.read().unwrap();
+            heard_position( &track, &handle.sample_buffer, handle.config.output.sample_rate )
         } else {
             Duration::ZERO
         }
@@ -478,47 +1414,231 @@ impl Player {
     /// Gets the total duration of the current track.
     pub fn duration( &self ) -> Option<Duration> {
         let playback = self.playback.read().unwrap();
-        playback.as_ref().and_then( |h| h.duration )
+        playback.as_ref().and_then( |h| h.track.read().unwrap().duration )
+    }
+
+
+    /// Gets the (source, output) sample rates of the current track and the quality
+    /// it is resampled with, or None for the quality if no resampling is needed.
+    pub fn sample_rates( &self ) -> Option<( u32, u32, Option<ResampleQuality> )> {
+        let playback = self.playback.read().unwrap();
+        playback.as_ref().map( |h| {
+            let track = h.track.read().unwrap();
+            ( track.sample_rate, h.config.output.sample_rate, track.resample_quality )
+        })
     }
 
 
     /// Gets the metadata of the current track.
     pub fn metadata( &self ) -> Option<AudioMetadata> {
         let playback = self.playback.read().unwrap();
-        playback.as_ref().map( |h| h.metadata.clone() )
+        playback.as_ref().map( |h| h.track.read().unwrap().metadata.clone() )
+    }
+
+
+    /// Gets the chapters of the current track.
+    pub fn chapters( &self ) -> Vec<Chapter> {
+        let playback = self.playback.read().unwrap();
+        playback.as_ref().map_or( Vec::new(), |h| h.track.read().unwrap().chapters.clone() )
+    }
+
+
+    /// Gets the index of the chapter being heard, if the track has chapters.
+    pub fn current_chapter( &self ) -> Option<usize> {
+        let position = self.position();
+        self.chapters().iter().rposition( |c| c.start <= position )
+    }
+
+
+    /// Seeks to the start of chapter `index` (counting from 0) of the current track.
+    pub fn seek_chapter( &self, index: usize ) -> Result<(), PlayerError> {
+        let chapter = self.chapters().get( index ).cloned().ok_or( PlayerError::NoChapter( index + 1 ) )?;
+        self.seek( chapter.start )
+    }
+
+
+    /// Seeks to the next chapter.
+    /// Returns Ok(false) if the last chapter is playing.
+    pub fn next_chapter( &self ) -> Result<bool, PlayerError> {
+        let next = self.current_chapter().map_or( 0, |i| i + 1 );
+        if next >= self.chapters().len() {
+            return Ok( false );
+        }
+        self.seek_chapter( next )?;
+        Ok( true )
+    }
+
+
+    /// Restarts the current chapter, or seeks to the previous one near its start.
+    /// Returns Ok(false) if the track has no chapters.
+    pub fn previous_chapter( &self ) -> Result<bool, PlayerError> {
+        let chapters = self.chapters();
+        let Some( current ) = self.current_chapter() else {
+            return Ok( false );
+        };
+        let into = self.position().saturating_sub( chapters[ current ].start );
+        let index = if into < CHAPTER_RESTART { current.saturating_sub( 1 ) } else { current };
+        self.seek_chapter( index )?;
+        Ok( true )
+    }
+
+
+    /// Gets the audio streams of the current track's file.
+    pub fn audio_streams( &self ) -> Vec<AudioStream> {
+        let playback = self.playback.read().unwrap();
+        playback.as_ref().map_or( Vec::new(), |h| h.track.read().unwrap().streams.clone() )
     }
 
 
-    /// Gets the visualization data (RMS amplitudes for frequency bars).
-    pub fn vis_data( &self ) -> Option<[f32; crate::output::VIS_BARS]> {
+    /// Gets the index of the audio stream being heard.
+    pub fn audio_stream( &self ) -> Option<usize> {
         let playback = self.playback.read().unwrap();
-        playback.as_ref().map( |h| h.sample_buffer.vis_data() )
+        playback.as_ref().map( |h| h.track.read().unwrap().stream )
     }
 
 
-    /// Sets the volume level (0.0 = mute, 1.0 = normal, >1.0 = boost).
-    pub fn set_volume( &self, volume: f32 ) {
-        // Store volume for future tracks
+    /// Switches the current track to audio stream `index` (counting from 0), carrying
+    /// on from the same position.
+    ///
+    /// The choice sticks whenever the track is reopened, e.g. to seek after it ended.
+    pub fn select_audio_stream( &self, index: usize ) -> Result<(), PlayerError> {
+        let current_track = self.current_track().ok_or( PlayerError::NoTrack )?;
+        if index >= self.audio_streams().len() {
+            return Err( PlayerError::NoStream( index + 1 ) );
+        }
+        tracing::info!( "Switching {:?} to audio stream {}", current_track, index );
+        *self.audio_stream.write().unwrap() = Some(( current_track.clone(), index ));
+
         {
-            let mut vol = self.volume.write().unwrap();
-            *vol = volume;
+            let playback = self.playback.read().unwrap();
+            if let Some( handle ) = playback.as_ref().filter( |h| !h.track_ended.load( Ordering::Relaxed ) ) {
+                handle.sample_buffer.fade_out();
+                self.wait_for_fade( &handle.sample_buffer );
+                if handle.commands.send( DecodeCommand::Reopen ).is_ok() {
+                    return Ok(());
+                }
+            }
         }
-        // Apply to current playback if any
+
+        // The decode thread has already finished, so reopen the track
+        let was_playing = self.state() == PlaybackState::Playing;
+        self.start( current_track, Some( self.position() ), !was_playing )
+    }
+
+
+    /// Gets the spectrum of the audio being heard, split into `bins` log-spaced bands.
+    ///
+    /// Meant to be called once per displayed frame: bars fall off and peaks are held
+    /// between calls according to the spectrum settings.
+    pub fn vis_data( &self, bins: usize ) -> Option<Spectrum> {
         let playback = self.playback.read().unwrap();
-        if let Some( ref handle ) = *playback {
-            handle.sample_buffer.set_volume( volume );
+        let handle = playback.as_ref()?;
+
+        let mut samples = [ 0.0; FFT_SIZE ];
+        handle.sample_buffer.played( &mut samples );
+        let sample_rate = handle.config.output.sample_rate;
+        Some( self.spectrum.lock().unwrap().analyze( &samples, sample_rate, bins ) )
+    }
+
+
+    /// Sets how the spectrum returned by `vis_data` falls off and holds its peaks.
+    pub fn set_spectrum_settings( &self, settings: SpectrumSettings ) {
+        self.spectrum.lock().unwrap().set_settings( settings );
+    }
+
+
+    /// Gets the spectrum display settings.
+    pub fn spectrum_settings( &self ) -> SpectrumSettings {
+        self.spectrum.lock().unwrap().settings()
+    }
+
+
+    /// Gets how many times the output has run out of audio mid-playback since it was opened.
+    ///
+    /// Each one is an audible dropout, typically from decoding falling behind.
+    pub fn underruns( &self ) -> u64 {
+        let output = self.output.lock().unwrap();
+        output.as_ref().map_or( 0, |o| o.sample_buffer().underruns() )
+    }
+
+
+    /// Sets the volume in dB (0 = unity, `MIN_VOLUME_DB` or below = mute, up to
+    /// `MAX_VOLUME_DB` of boost). While boosting, the output is limited to keep it
+    /// from clipping. The change is heard after the ~60 ms already queued for output.
+    pub fn set_volume_db( &self, db: f32 ) {
+        // The volume and limiter stages in the output chain pick this up on their next slice
+        *self.volume.write().unwrap() = volume_gain( db );
+    }
+
+
+    /// Gets the current volume in dB.
+    pub fn volume_db( &self ) -> f32 {
+        volume_db( *self.volume.read().unwrap() )
+    }
+
+
+    /// Loops the current track between two positions, or stops looping with None.
+    ///
+    /// When decoding reaches the end of the loop it seeks back to the start without
+    /// a gap, to the sample. A loop ending past the end of the track wraps around at
+    /// the end. Playing another track clears the loop.
+    pub fn set_loop( &self, region: Option<( Duration, Duration )> ) {
+        let region = region
+            .map( |( a, b )| ( a.min( b ), a.max( b ) ) )
+            .filter( |( start, end )| end > start );
+        *self.ab_loop.write().unwrap() = region;
+    }
+
+
+    /// Gets the A-B loop of the current track as (start, end), if one is set.
+    pub fn loop_region( &self ) -> Option<( Duration, Duration )> {
+        *self.ab_loop.read().unwrap()
+    }
+
+
+    /// Sets how long pausing, resuming, stopping, seeking and volume changes fade
+    /// for, up to `MAX_FADE` (zero switches instantly).
+    pub fn set_fade( &self, fade: Duration ) {
+        let fade = fade.min( MAX_FADE );
+        *self.fade.write().unwrap() = fade;
+        if let Some( output ) = self.output.lock().unwrap().as_ref() {
+            output.sample_buffer().set_fade_length( fade_frames( fade, output.format() ) );
+        }
+    }
+
+
+    /// Gets the length of the fades on pause, resume, stop, seek and volume changes.
+    pub fn fade( &self ) -> Duration {
+        *self.fade.read().unwrap()
+    }
+
+
+    /// Blocks until the output has finished fading, giving up if it isn't playing.
+    fn wait_for_fade( &self, buffer: &SampleBuffer ) {
+        let timeout = self.fade() * 2 + Duration::from_millis( 100 );
+        let started = Instant::now();
+        while !buffer.fade_done() && started.elapsed() < timeout {
+            thread::sleep( Duration::from_millis( 1 ) );
         }
     }
 
 
-    /// Gets the current volume level.
-    pub fn volume( &self ) -> f32 {
-        *self.volume.read().unwrap()
+    /// Returns the processing chain applied to the output of every track.
+    ///
+    /// Stages can be added, replaced or removed while playing and take effect on the
+    /// next chunk. They receive audio in the output format and must not change its
+    /// sample rate or channel count. The chain starts with `equalizer`, `volume` and
+    /// `limiter` stages.
+    pub fn dsp( &self ) -> Arc<Mutex<DspChain>> {
+        Arc::clone( &self.dsp )
     }
 
 
     /// Returns true if the current track ended naturally (EOF reached).
     /// This is reset when a new track starts playing.
+    ///
+    /// With gapless playback this only happens at the end of the playlist, or when
+    /// the next track couldn't be chained onto the running output.
     pub fn track_ended( &self ) -> bool {
         let playback = self.playback.read().unwrap();
         playback.as_ref()
@@ -527,6 +1647,152 @@ impl Player {
     }
 
 
+    /// Sets how consecutive tracks are crossfaded (zero duration disables it).
+    pub fn set_crossfade( &self, crossfade: Crossfade ) {
+        *self.crossfade.write().unwrap() = crossfade;
+    }
+
+
+    /// Sets the ReplayGain settings. Takes effect immediately, including for the current track.
+    pub fn set_replay_gain( &self, settings: ReplayGainSettings ) {
+        *self.replay_gain.write().unwrap() = settings;
+    }
+
+
+    /// Gets the current ReplayGain settings.
+    pub fn replay_gain( &self ) -> ReplayGainSettings {
+        *self.replay_gain.read().unwrap()
+    }
+
+
+    /// Gets the current crossfade settings.
+    pub fn crossfade( &self ) -> Crossfade {
+        *self.crossfade.read().unwrap()
+    }
+
+
+    /// Sets the equalizer settings. Takes effect on the next chunk, without interrupting playback.
+    pub fn set_equalizer( &self, settings: EqualizerSettings ) {
+        *self.equalizer.write().unwrap() = settings;
+    }
+
+
+    /// Gets the current equalizer settings.
+    pub fn equalizer( &self ) -> EqualizerSettings {
+        self.equalizer.read().unwrap().clone()
+    }
+
+
+    /// Sets whether mixing a file down to fewer output channels scales the result
+    /// so it can't clip. Takes effect immediately, including for the current track.
+    pub fn set_normalize_downmix( &self, normalize: bool ) {
+        *self.normalize_downmix.write().unwrap() = normalize;
+    }
+
+
+    /// Gets whether downmixes are normalized against clipping.
+    pub fn normalize_downmix( &self ) -> bool {
+        *self.normalize_downmix.read().unwrap()
+    }
+
+
+    /// Sets how tracks at other sample rates are converted to the output rate.
+    /// Takes effect from the next track opened.
+    pub fn set_resample_settings( &self, settings: ResampleSettings ) {
+        *self.resample.write().unwrap() = settings;
+    }
+
+
+    /// Gets the resampler settings.
+    pub fn resample_settings( &self ) -> ResampleSettings {
+        *self.resample.read().unwrap()
+    }
+
+
+    /// Sets the playback speed (0.5x to 3.0x), keeping the pitch unchanged.
+    /// Takes effect immediately, including for the current track. A speed that
+    /// isn't a finite number resets it to 1x.
+    pub fn set_speed( &self, speed: f32 ) {
+        *self.speed.write().unwrap() = clamp_speed( speed );
+    }
+
+
+    /// Gets the current playback speed.
+    pub fn speed( &self ) -> f32 {
+        *self.speed.read().unwrap()
+    }
+
+
+    /// Sets the device to play through (None = system default).
+    ///
+    /// If a stream is already open it is reopened on the new device straight away,
+    /// and the current track carries on from where it was. A player created with
+    /// `with_sink` only records the preference.
+    pub fn set_output_device( &self, device: Option<OutputDeviceId> ) -> Result<(), PlayerError> {
+        if *self.output_device.read().unwrap() == device {
+            return Ok(());
+        }
+        tracing::info!( "Switching output device to {:?}", device );
+        *self.output_device.write().unwrap() = device;
+
+        if self.fixed_output || self.output.lock().unwrap().is_none() {
+            return Ok(());
+        }
+
+        // Remember what was playing before tearing the stream down
+        let resume = self.current_track()
+            .filter( |_| self.state() != PlaybackState::Stopped && !self.track_ended() )
+            .map( |path| ( path, self.position(), self.state() == PlaybackState::Paused ) );
+
+        self.stop()?;
+        // The new device may run at a different rate, so filters start over
+        self.dsp.lock().unwrap().reset();
+        *self.output.lock().unwrap() = None;
+
+        match resume {
+            Some(( path, position, paused )) => self.start( path, Some( position ), paused ),
+            None => self.output().map( |_| () ),
+        }
+    }
+
+
+    /// Gets the preferred output device (None = system default).
+    pub fn output_device( &self ) -> Option<OutputDeviceId> {
+        self.output_device.read().unwrap().clone()
+    }
+
+
+    /// Switches to the specified file, crossfading from the current track if a
+    /// crossfade is configured and something is playing.
+    ///
+    /// Falls back to `play()` when there is nothing to fade from.
+    pub fn transition_to( &self, path: PathBuf ) -> Result<(), PlayerError> {
+        self.set_loop( None );
+        if !self.crossfade().is_enabled() || self.state() != PlaybackState::Playing || self.track_ended() {
+            return self.play( path );
+        }
+
+        let config = match self.playback.read().unwrap().as_ref() {
+            Some( handle ) => handle.config.clone(),
+            None => return self.play( path ),
+        };
+
+        let ( decoder, cue ) = open_track( &path )?;
+        let mut source = TrackSource::new( path.clone(), decoder, cue, &config )?;
+        if let Some( resume ) = self.resume_point( &path ) {
+            source.seek( resume ).map_err( |e| PlayerError::Decode( e.to_string() ) )?;
+        }
+        let playback = self.playback.read().unwrap();
+        match playback.as_ref() {
+            Some( handle ) if handle.commands.send( DecodeCommand::CrossfadeTo( Box::new( source ) ) ).is_ok() => Ok(()),
+            _ => {
+                drop( playback );
+                self.play( path )
+            }
+        }
+    }
+
+
     /// Plays the next track in the playlist.
     /// Returns Ok(true) if a track was started, Ok(false) if no next track.
     pub fn play_next( &self ) -> Result<bool, PlayerError> {
@@ -536,7 +1802,7 @@ impl Player {
         };
 
         if let Some( path ) = next_track {
-            self.play( path )?;
+            self.transition_to( path )?;
             Ok( true )
         } else {
             Ok( false )
@@ -553,7 +1819,7 @@ impl Player {
         };
 
         if let Some( path ) = prev_track {
-            self.play( path )?;
+            self.transition_to( path )?;
             Ok( true )
         } else {
             Ok( false )
@@ -563,118 +1829,70 @@ impl Player {
 
     /// Seeks to a specific position in the current track.
     ///
-    /// This works by stopping playback, reopening the file at the seek position,
-    /// and resuming playback.
+    /// The decode thread seeks in place and flushes what it had queued. Seeks sent
+    /// faster than they can be carried out are coalesced into the latest one.
     pub fn seek( &self, position: Duration ) -> Result<(), PlayerError> {
         let current_track = self.current_track().ok_or( PlayerError::NoTrack )?;
-        let was_playing = self.state() == PlaybackState::Playing;
-
-        // Stop current playback
-        self.stop()?;
-
-        // Reopen and seek
         tracing::info!( "Seeking to {:?} in {:?}", position, current_track );
 
-        // Open the decoder
-        let mut decoder = Decoder::open( &current_track )
-            .map_err( |e| PlayerError::FileOpen( e.to_string() ) )?;
-
-        // Seek to position
-        decoder.seek( position.as_secs_f64() )
-            .map_err( |e| PlayerError::Decode( e.to_string() ) )?;
-
-        let source_sample_rate = decoder.sample_rate();
-        let channels = decoder.channels() as u16;
-        let duration = decoder.duration().map( |secs| Duration::from_secs_f64( secs ) );
-        let metadata = decoder.metadata();
-
-        // Create audio output
-        let ( output, sample_buffer ) = AudioOutput::new( source_sample_rate, channels )
-            .map_err( |e| PlayerError::Output( e.to_string() ) )?;
-
-        // Apply stored volume to new sample buffer
-        let vol = *self.volume.read().unwrap();
-        sample_buffer.set_volume( vol );
-
-        let target_sample_rate = output.sample_rate();
-
-        output.play().map_err( |e| PlayerError::Output( e.to_string() ) )?;
-
-        // Create resampler if sample rates don't match
-        let resampler = if source_sample_rate != target_sample_rate {
-            let resampler = FastFixedOut::<f32>::new(
-                target_sample_rate as f64 / source_sample_rate as f64,
-                2.0,
-                PolynomialDegree::Cubic,
-                1024,
-                channels as usize,
-            ).map_err( |e| PlayerError::Output( format!( "Failed to create resampler: {}", e ) ) )?;
+        // Hand the seek to the running decode thread
+        {
+            let playback = self.playback.read().unwrap();
+            if let Some( handle ) = playback.as_ref().filter( |h| !h.track_ended.load( Ordering::Relaxed ) ) {
+                // Report the target straight away so repeated relative seeks build on each other
+                {
+                    let track = handle.track.read().unwrap();
+                    let frames = ( position.as_secs_f64() * track.sample_rate as f64 ) as u64;
+                    track.timeline.lock().unwrap().seeking = Some( frames );
+                }
+                // The decode thread fades back in once it has seeked
+                handle.sample_buffer.fade_out();
+                self.wait_for_fade( &handle.sample_buffer );
+                if handle.commands.send( DecodeCommand::Seek( position ) ).is_ok() {
+                    return Ok(());
+                }
+            }
+        }
 
-            Some( resampler )
-        } else {
-            None
-        };
+        // The decode thread has already finished, so reopen the track
+        let was_playing = self.state() == PlaybackState::Playing;
+        self.start( current_track, Some( position ), !was_playing )
+    }
+}
 
-        // Set up control flags - start with frames_played at the seek position
-        let stop_flag = Arc::new( AtomicBool::new( false ) );
-        let seek_frames = ( position.as_secs_f64() * source_sample_rate as f64 ) as u64;
-        let frames_played = Arc::new( AtomicU64::new( seek_frames ) );
-        let track_ended = Arc::new( AtomicBool::new( false ) );
 
-        // Clone for the decode thread
-        let stop_flag_clone = Arc::clone( &stop_flag );
-        let sample_buffer_clone = Arc::clone( &sample_buffer );
-        let state_clone = Arc::clone( &self.state );
-        let frames_played_clone = Arc::clone( &frames_played );
-        let track_ended_clone = Arc::clone( &track_ended );
+/// Position within `track` of the audio being heard from `buffer`.
+fn heard_position( track: &TrackInfo, buffer: &SampleBuffer, output_rate: u32 ) -> Duration {
+    let frames = track.timeline.lock().unwrap().frame_at( buffer.heard( output_rate ) );
+    Duration::from_secs_f64( frames as f64 / track.sample_rate as f64 )
+}
 
-        // Start paused if we were paused before
-        if !was_playing {
-            sample_buffer.set_paused( true );
-        }
 
-        // Spawn decode thread
-        let thread = thread::spawn( move || {
-            Self::decode_loop(
-                decoder,
-                sample_buffer_clone,
-                stop_flag_clone,
-                state_clone,
-                resampler,
-                frames_played_clone,
-                track_ended_clone,
-            );
-        });
+/// Opens the file behind a playlist entry.
+///
+/// Cue sheet tracks (`Album.cue#3`) open the audio file they're part of and return
+/// where the track lies within it.
+fn open_track( path: &Path ) -> Result<( Decoder, Option<CueTrack> ), PlayerError> {
+    let track = cue::resolve( path ).map_err( |e| PlayerError::FileOpen( e.to_string() ) )?;
+    let file = track.as_ref().map_or( path, |t| t.file.as_path() );
+    let decoder = Decoder::open( file ).map_err( |e| PlayerError::FileOpen( e.to_string() ) )?;
+    Ok(( decoder, track ))
+}
 
-        // Store playback handle
-        {
-            let mut playback = self.playback.write().unwrap();
-            *playback = Some( PlaybackHandle {
-                stop_flag,
-                sample_buffer,
-                output: AudioOutputHandle( output ),
-                thread: Some( thread ),
-                frames_played,
-                sample_rate: source_sample_rate,
-                duration,
-                track_ended,
-                metadata,
-            });
-        }
 
-        // Update state
-        {
-            let mut track = self.current_track.write().unwrap();
-            *track = Some( current_track );
-        }
+/// Returns the chapters of a file that fall within a track starting `start` into it,
+/// relative to the track.
+fn track_chapters( chapters: &[Chapter], start: Duration, duration: Option<Duration> ) -> Vec<Chapter> {
+    chapters.iter()
+        .filter( |c| c.start >= start && duration.is_none_or( |d| c.start < start + d ) )
+        .map( |c| Chapter { title: c.title.clone(), start: c.start - start } )
+        .collect()
+}
 
-        {
-            let mut state = self.state.write().unwrap();
-            *state = if was_playing { PlaybackState::Playing } else { PlaybackState::Paused };
-        }
 
-        Ok(())
-    }
+/// Number of output frames a fade of `length` lasts.
+fn fade_frames( length: Duration, format: OutputFormat ) -> u32 {
+    ( length.as_secs_f64() * format.sample_rate as f64 ).round() as u32
 }
 
 
@@ -8,6 +8,8 @@ use std::path::{ Path, PathBuf };
 
 use thiserror::Error;
 
+use crate::volume;
+
 
 /// Errors that can occur with playlist operations.
 #[derive( Debug, Error )]
@@ -37,7 +39,9 @@ pub struct SessionState {
     pub track_index: Option<usize>,
     pub shuffle: bool,
     pub repeat: RepeatMode,
-    pub volume: f32,
+    /// Volume in dB
+    pub volume_db: f32,
+    pub speed: f32,
 }
 
 
@@ -159,6 +163,45 @@ impl Playlist {
     }
 
 
+    /// Returns the track that `next()` would advance to, without advancing.
+    ///
+    /// Returns None at the end of the playlist, and when a shuffled playlist
+    /// would have to be reshuffled to continue (the next order isn't known yet).
+    pub fn peek_next( &self ) -> Option<&PathBuf> {
+        if self.tracks.is_empty() {
+            return None;
+        }
+
+        let next_index = if self.shuffle {
+            let position = self.shuffle_position + 1;
+            if position >= self.shuffle_order.len() {
+                match self.repeat {
+                    RepeatMode::One => self.shuffle_order.get( self.shuffle_position ).copied(),
+                    RepeatMode::Off | RepeatMode::All => None,
+                }
+            } else {
+                self.shuffle_order.get( position ).copied()
+            }
+        } else {
+            match self.repeat {
+                RepeatMode::One => self.current_index,
+                RepeatMode::Off | RepeatMode::All => {
+                    let next = self.current_index.unwrap_or( 0 ) + 1;
+                    if next < self.tracks.len() {
+                        Some( next )
+                    } else if self.repeat == RepeatMode::All {
+                        Some( 0 )
+                    } else {
+                        None
+                    }
+                }
+            }
+        };
+
+        next_index.and_then( |i| self.tracks.get( i ) )
+    }
+
+
     /// Goes to the previous track.
     pub fn previous( &mut self ) -> Option<&PathBuf> {
         if self.tracks.is_empty() {
@@ -216,6 +259,32 @@ impl Playlist {
     }
 
 
+    /// Returns true if `path` is being played as part of its album.
+    ///
+    /// That is the case when the playlist isn't shuffled and a neighbouring entry
+    /// comes from the same folder.
+    pub fn in_album_order( &self, path: &Path ) -> bool {
+        if self.shuffle {
+            return false;
+        }
+
+        let index = match self.current_index.filter( |&i| self.tracks.get( i ).is_some_and( |t| t == path ) ) {
+            Some( i ) => i,
+            None => match self.tracks.iter().position( |t| t == path ) {
+                Some( i ) => i,
+                None => return false,
+            },
+        };
+
+        let folder = path.parent();
+        [ index.checked_sub( 1 ), Some( index + 1 ) ]
+            .into_iter()
+            .flatten()
+            .filter_map( |i| self.tracks.get( i ) )
+            .any( |t| t.parent() == folder )
+    }
+
+
     /// Sets repeat mode.
     pub fn set_repeat( &mut self, repeat: RepeatMode ) {
         self.repeat = repeat;
@@ -401,7 +470,8 @@ impl Playlist {
                 RepeatMode::One => "one",
                 RepeatMode::All => "all",
             })?;
-            writeln!( file, "volume={}", ( state.volume * 100.0 ).round() as i32 )?;
+            writeln!( file, "volume_db={}", state.volume_db )?;
+            writeln!( file, "speed={}", state.speed )?;
         }
         Ok(())
     }
@@ -417,7 +487,8 @@ impl Playlist {
         let mut track_index = None;
         let mut shuffle = false;
         let mut repeat = RepeatMode::Off;
-        let mut volume = 1.0_f32;
+        let mut volume_db = 0.0_f32;
+        let mut speed = 1.0_f32;
 
         for line in reader.lines().map_while( Result::ok ) {
             if let Some(( key, value )) = line.split_once( '=' ) {
@@ -430,7 +501,10 @@ impl Playlist {
                         "all" | "2" => RepeatMode::All,
                         _ => RepeatMode::Off,
                     },
-                    "volume" => volume = value.trim().parse::<i32>().map( |v| v as f32 / 100.0 ).unwrap_or( 1.0 ),
+                    "volume_db" => volume_db = value.trim().parse().ok().filter( |v: &f32| v.is_finite() ).unwrap_or( 0.0 ),
+                    // Older sessions stored a linear percentage
+                    "volume" => volume_db = value.trim().parse::<i32>().map_or( 0.0, |v| volume::volume_db( v as f32 / 100.0 ) ),
+                    "speed" => speed = value.trim().parse().ok().filter( |s: &f32| s.is_finite() ).unwrap_or( 1.0 ),
                     _ => {}
                 }
             }
@@ -445,7 +519,8 @@ impl Playlist {
             track_index,
             shuffle,
             repeat,
-            volume,
+            volume_db,
+            speed,
         })
     }
 
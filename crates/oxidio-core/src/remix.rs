@@ -0,0 +1,304 @@
+//! Channel layouts and remixing
+//!
+//! Maps audio between speaker layouts with mix matrices: ITU-R BS.775 downmix
+//! coefficients when the output has fewer speakers, and plain routing when it
+//! has more.
+
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use symphonia::core::audio::Channels;
+
+
+/// Gain of the low frequency channel when the output has no LFE speaker.
+///
+/// BS.775 leaves the LFE out of a downmix. It's mixed in 6 dB down instead, so
+/// content that relies on it for bass isn't lost.
+const LFE_GAIN: f32 = 0.5;
+
+
+/// A speaker position within a channel layout.
+#[derive( Debug, Clone, Copy, PartialEq, Eq, Hash )]
+pub enum Speaker {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    Lfe,
+    RearLeft,
+    RearRight,
+    FrontLeftCenter,
+    FrontRightCenter,
+    RearCenter,
+    SideLeft,
+    SideRight,
+    /// Height, wide or other extra positions on the left
+    ExtraLeft,
+    /// Height, wide or other extra positions on the right
+    ExtraRight,
+    /// Height or other extra positions in the middle, and unlabelled channels
+    ExtraCenter,
+}
+
+
+impl Speaker {
+    /// Returns the alternative ways of playing this speaker on a layout that lacks
+    /// it, best first. Each is a set of (speaker, gain) that must all be present.
+    fn routes( self, mono_source: bool ) -> &'static [&'static [( Speaker, f32 )]] {
+        use Speaker::*;
+
+        const H: f32 = FRAC_1_SQRT_2;
+        match self {
+            FrontLeft => &[ &[ ( FrontLeft, 1.0 ) ] ],
+            FrontRight => &[ &[ ( FrontRight, 1.0 ) ] ],
+            // A mono source is copied to both sides at full level
+            FrontCenter if mono_source => &[ &[ ( FrontCenter, 1.0 ) ], &[ ( FrontLeft, 1.0 ), ( FrontRight, 1.0 ) ] ],
+            FrontCenter => &[ &[ ( FrontCenter, 1.0 ) ], &[ ( FrontLeft, H ), ( FrontRight, H ) ] ],
+            Lfe => &[ &[ ( Lfe, 1.0 ) ], &[ ( FrontLeft, LFE_GAIN ), ( FrontRight, LFE_GAIN ) ] ],
+            RearLeft => &[ &[ ( RearLeft, 1.0 ) ], &[ ( SideLeft, 1.0 ) ], &[ ( FrontLeft, H ) ] ],
+            RearRight => &[ &[ ( RearRight, 1.0 ) ], &[ ( SideRight, 1.0 ) ], &[ ( FrontRight, H ) ] ],
+            SideLeft => &[ &[ ( SideLeft, 1.0 ) ], &[ ( RearLeft, 1.0 ) ], &[ ( FrontLeft, H ) ] ],
+            SideRight => &[ &[ ( SideRight, 1.0 ) ], &[ ( RearRight, 1.0 ) ], &[ ( FrontRight, H ) ] ],
+            FrontLeftCenter => &[ &[ ( FrontLeftCenter, 1.0 ) ], &[ ( FrontLeft, 1.0 ) ] ],
+            FrontRightCenter => &[ &[ ( FrontRightCenter, 1.0 ) ], &[ ( FrontRight, 1.0 ) ] ],
+            RearCenter => &[
+                &[ ( RearCenter, 1.0 ) ],
+                &[ ( RearLeft, H ), ( RearRight, H ) ],
+                &[ ( SideLeft, H ), ( SideRight, H ) ],
+                &[ ( FrontLeft, 0.5 ), ( FrontRight, 0.5 ) ],
+            ],
+            ExtraLeft => &[ &[ ( ExtraLeft, 1.0 ) ], &[ ( FrontLeft, H ) ] ],
+            ExtraRight => &[ &[ ( ExtraRight, 1.0 ) ], &[ ( FrontRight, H ) ] ],
+            ExtraCenter => &[ &[ ( ExtraCenter, 1.0 ) ], &[ ( FrontCenter, H ) ], &[ ( FrontLeft, 0.5 ), ( FrontRight, 0.5 ) ] ],
+        }
+    }
+}
+
+
+/// The speaker each interleaved channel belongs to, in order.
+#[derive( Debug, Clone, PartialEq, Eq )]
+pub struct ChannelLayout {
+    speakers: Vec<Speaker>,
+}
+
+
+impl ChannelLayout {
+    /// Creates a layout from speakers in channel order.
+    pub fn new( speakers: Vec<Speaker> ) -> Self {
+        Self { speakers }
+    }
+
+
+    /// Returns the usual layout for `channels` channels when nothing more is known,
+    /// following the WAVE channel order.
+    pub fn default_for( channels: usize ) -> Self {
+        use Speaker::*;
+
+        let speakers: &[Speaker] = match channels {
+            1 => &[ FrontCenter ],
+            2 => &[ FrontLeft, FrontRight ],
+            3 => &[ FrontLeft, FrontRight, FrontCenter ],
+            4 => &[ FrontLeft, FrontRight, RearLeft, RearRight ],
+            5 => &[ FrontLeft, FrontRight, FrontCenter, RearLeft, RearRight ],
+            6 => &[ FrontLeft, FrontRight, FrontCenter, Lfe, RearLeft, RearRight ],
+            7 => &[ FrontLeft, FrontRight, FrontCenter, Lfe, RearCenter, SideLeft, SideRight ],
+            _ => &[ FrontLeft, FrontRight, FrontCenter, Lfe, RearLeft, RearRight, SideLeft, SideRight ],
+        };
+
+        let mut speakers = speakers[ ..speakers.len().min( channels ) ].to_vec();
+        speakers.resize( channels, ExtraCenter );
+        Self { speakers }
+    }
+
+
+    /// Number of channels in the layout.
+    pub fn channels( &self ) -> usize {
+        self.speakers.len()
+    }
+
+
+    /// Speakers in channel order.
+    pub fn speakers( &self ) -> &[Speaker] {
+        &self.speakers
+    }
+
+
+    fn contains( &self, speaker: Speaker ) -> bool {
+        self.speakers.contains( &speaker )
+    }
+
+
+    fn index_of( &self, speaker: Speaker ) -> Option<usize> {
+        self.speakers.iter().position( |s| *s == speaker )
+    }
+}
+
+
+impl From<Channels> for ChannelLayout {
+    fn from( channels: Channels ) -> Self {
+        // Symphonia labels mono as front left
+        if channels.count() == 1 {
+            return Self::new( vec![ Speaker::FrontCenter ] );
+        }
+
+        let speakers = channels.iter().map( |channel| match channel {
+            Channels::FRONT_LEFT => Speaker::FrontLeft,
+            Channels::FRONT_RIGHT => Speaker::FrontRight,
+            Channels::FRONT_CENTRE => Speaker::FrontCenter,
+            Channels::LFE1 | Channels::LFE2 => Speaker::Lfe,
+            Channels::REAR_LEFT => Speaker::RearLeft,
+            Channels::REAR_RIGHT => Speaker::RearRight,
+            Channels::FRONT_LEFT_CENTRE => Speaker::FrontLeftCenter,
+            Channels::FRONT_RIGHT_CENTRE => Speaker::FrontRightCenter,
+            Channels::REAR_CENTRE => Speaker::RearCenter,
+            Channels::SIDE_LEFT => Speaker::SideLeft,
+            Channels::SIDE_RIGHT => Speaker::SideRight,
+            Channels::TOP_FRONT_LEFT | Channels::TOP_REAR_LEFT | Channels::REAR_LEFT_CENTRE
+                | Channels::FRONT_LEFT_WIDE | Channels::FRONT_LEFT_HIGH => Speaker::ExtraLeft,
+            Channels::TOP_FRONT_RIGHT | Channels::TOP_REAR_RIGHT | Channels::REAR_RIGHT_CENTRE
+                | Channels::FRONT_RIGHT_WIDE | Channels::FRONT_RIGHT_HIGH => Speaker::ExtraRight,
+            _ => Speaker::ExtraCenter,
+        }).collect();
+
+        Self::new( speakers )
+    }
+}
+
+
+/// Gains taking every source channel to every output channel.
+#[derive( Debug, Clone, PartialEq )]
+pub struct MixMatrix {
+    inputs: usize,
+    outputs: usize,
+    /// Row per output channel, column per input channel
+    gains: Vec<f32>,
+}
+
+
+impl MixMatrix {
+    /// Builds the matrix playing `source` on `output`.
+    ///
+    /// Speakers the output has are copied straight across. Missing ones are folded
+    /// into their neighbours with BS.775 gains (centre and surrounds at -3 dB), and
+    /// speakers the source lacks stay silent. With `normalize`, the gains are scaled
+    /// down so that no output channel can clip.
+    pub fn new( source: &ChannelLayout, output: &ChannelLayout, normalize: bool ) -> Self {
+        let inputs = source.channels();
+        let outputs = output.channels();
+        let mut gains = vec![ 0.0; inputs * outputs ];
+        let mono_source = inputs == 1;
+        let stereo = ChannelLayout::default_for( 2 );
+
+        for ( input, speaker ) in source.speakers().iter().enumerate() {
+            let routes = speaker.routes( mono_source );
+            let direct = routes.iter().find( |route| route.iter().all( |( s, _ )| output.contains( *s ) ) );
+
+            let targets: Vec<( Speaker, f32 )> = match direct {
+                Some( route ) => route.to_vec(),
+                None => {
+                    // A mono output gets the stereo downmix, averaged
+                    let Some( route ) = routes.iter().find( |route| route.iter().all( |( s, _ )| stereo.contains( *s ) ) ) else {
+                        continue;
+                    };
+                    route.iter().map( |( _, gain )| ( Speaker::FrontCenter, gain * 0.5 ) ).collect()
+                }
+            };
+
+            for ( target, gain ) in targets {
+                if let Some( index ) = output.index_of( target ) {
+                    gains[ index * inputs + input ] += gain;
+                }
+            }
+        }
+
+        if normalize {
+            let loudest = gains.chunks( inputs.max( 1 ) )
+                .map( |row| row.iter().map( |g| g.abs() ).sum::<f32>() )
+                .fold( 0.0, f32::max );
+            if loudest > 1.0 {
+                gains.iter_mut().for_each( |g| *g /= loudest );
+            }
+        }
+
+        Self { inputs, outputs, gains }
+    }
+
+
+    /// Gain from input channel `input` to output channel `output`.
+    pub fn gain( &self, output: usize, input: usize ) -> f32 {
+        self.gains[ output * self.inputs + input ]
+    }
+
+
+    /// Mixes interleaved `samples` in the source layout into the output layout.
+    pub fn apply( &self, samples: &[f32] ) -> Vec<f32> {
+        if self.inputs == 0 {
+            return Vec::new();
+        }
+
+        let frames = samples.len() / self.inputs;
+        let mut out = Vec::with_capacity( frames * self.outputs );
+        for frame in samples.chunks_exact( self.inputs ) {
+            for row in self.gains.chunks_exact( self.inputs ) {
+                out.push( row.iter().zip( frame ).map( |( gain, sample )| gain * sample ).sum() );
+            }
+        }
+        out
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_surround_downmix_keeps_center_and_lfe() {
+        let matrix = MixMatrix::new( &ChannelLayout::default_for( 6 ), &ChannelLayout::default_for( 2 ), false );
+
+        // Dialogue in the centre channel reaches both sides at -3 dB
+        let out = matrix.apply( &[ 0.0, 0.0, 1.0, 0.0, 0.0, 0.0 ] );
+        assert!( ( out[ 0 ] - FRAC_1_SQRT_2 ).abs() < 1e-6 && ( out[ 1 ] - FRAC_1_SQRT_2 ).abs() < 1e-6 );
+
+        // Surrounds stay on their side, and the LFE isn't dropped
+        assert_eq!( matrix.apply( &[ 0.0, 0.0, 0.0, 0.0, 1.0, 0.0 ] ), vec![ FRAC_1_SQRT_2, 0.0 ] );
+        assert_eq!( matrix.apply( &[ 0.0, 0.0, 0.0, 1.0, 0.0, 0.0 ] ), vec![ LFE_GAIN, LFE_GAIN ] );
+    }
+
+
+    #[test]
+    fn test_normalize_prevents_clipping() {
+        let source = ChannelLayout::default_for( 8 );
+        let stereo = ChannelLayout::default_for( 2 );
+        let loud = vec![ 1.0; 8 ];
+
+        assert!( MixMatrix::new( &source, &stereo, false ).apply( &loud )[ 0 ] > 1.0 );
+        let out = MixMatrix::new( &source, &stereo, true ).apply( &loud );
+        assert!( out.iter().all( |s| *s <= 1.0 + 1e-6 ), "{:?}", out );
+    }
+
+
+    #[test]
+    fn test_upmix_and_mono() {
+        let mono = ChannelLayout::default_for( 1 );
+        let stereo = ChannelLayout::default_for( 2 );
+        let surround = ChannelLayout::default_for( 6 );
+
+        // Mono is duplicated to stereo and stereo averaged to mono, as before
+        assert_eq!( MixMatrix::new( &mono, &stereo, false ).apply( &[ 0.5 ] ), vec![ 0.5, 0.5 ] );
+        assert_eq!( MixMatrix::new( &stereo, &mono, false ).apply( &[ 1.0, 0.5 ] ), vec![ 0.75 ] );
+
+        // Upmixing keeps stereo on the front speakers and mono in the centre
+        assert_eq!( MixMatrix::new( &stereo, &surround, false ).apply( &[ 1.0, 0.5 ] ), vec![ 1.0, 0.5, 0.0, 0.0, 0.0, 0.0 ] );
+        assert_eq!( MixMatrix::new( &mono, &surround, false ).apply( &[ 1.0 ] ), vec![ 0.0, 0.0, 1.0, 0.0, 0.0, 0.0 ] );
+    }
+
+
+    #[test]
+    fn test_layout_from_symphonia_channels() {
+        let channels = Channels::FRONT_LEFT | Channels::FRONT_RIGHT | Channels::FRONT_CENTRE
+            | Channels::LFE1 | Channels::SIDE_LEFT | Channels::SIDE_RIGHT;
+        let layout = ChannelLayout::from( channels );
+        assert_eq!( layout.speakers()[ 4 ], Speaker::SideLeft );
+        assert_eq!( ChannelLayout::from( Channels::FRONT_LEFT ), ChannelLayout::default_for( 1 ) );
+    }
+}
@@ -0,0 +1,263 @@
+//! ReplayGain support
+//!
+//! Reads ReplayGain tag values and turns them into a playback gain.
+
+use std::str::FromStr;
+use std::sync::{ Arc, RwLock };
+
+use crate::command::CommandError;
+use crate::dsp::AudioProcessor;
+use crate::loudness::{ Loudness, DEFAULT_TARGET_LUFS };
+
+
+/// ReplayGain values read from a file's tags.
+#[derive( Debug, Clone, Copy, PartialEq, Default )]
+pub struct ReplayGain {
+    /// Track gain in dB
+    pub track_gain: Option<f32>,
+    /// Track peak as a linear sample value (1.0 = full scale)
+    pub track_peak: Option<f32>,
+    /// Album gain in dB
+    pub album_gain: Option<f32>,
+    /// Album peak as a linear sample value (1.0 = full scale)
+    pub album_peak: Option<f32>,
+}
+
+
+impl ReplayGain {
+    /// Returns true if the file carries any ReplayGain gain value.
+    pub fn is_tagged( &self ) -> bool {
+        self.track_gain.is_some() || self.album_gain.is_some()
+    }
+}
+
+
+/// Which ReplayGain values are applied during playback.
+#[derive( Debug, Clone, Copy, PartialEq, Eq, Default )]
+#[cfg_attr( feature = "serde", derive( serde::Serialize, serde::Deserialize ) )]
+#[cfg_attr( feature = "serde", serde( rename_all = "kebab-case" ) )]
+pub enum ReplayGainMode {
+    /// No ReplayGain or loudness normalization (default)
+    #[default]
+    Off,
+
+    /// Always use the track gain
+    Track,
+
+    /// Always use the album gain
+    Album,
+
+    /// Album gain while an album is being played in order, track gain otherwise
+    Auto,
+}
+
+
+impl ReplayGainMode {
+    /// Returns the display name of the mode.
+    pub fn name( &self ) -> &'static str {
+        match self {
+            ReplayGainMode::Off => "off",
+            ReplayGainMode::Track => "track",
+            ReplayGainMode::Album => "album",
+            ReplayGainMode::Auto => "auto",
+        }
+    }
+}
+
+
+impl FromStr for ReplayGainMode {
+    type Err = CommandError;
+
+
+    fn from_str( s: &str ) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Ok( ReplayGainMode::Off ),
+            "track" => Ok( ReplayGainMode::Track ),
+            "album" => Ok( ReplayGainMode::Album ),
+            "auto" => Ok( ReplayGainMode::Auto ),
+            _ => Err( CommandError::InvalidArgument(
+                format!( "Invalid ReplayGain mode: '{}'. Use off, track, album or auto", s )
+            )),
+        }
+    }
+}
+
+
+/// ReplayGain settings applied by the player.
+#[derive( Debug, Clone, Copy, PartialEq )]
+pub struct ReplayGainSettings {
+    pub mode: ReplayGainMode,
+    /// Extra gain in dB added on top of the tagged gain
+    pub preamp_db: f32,
+    /// Lower the gain where needed so the tagged peak doesn't clip
+    pub prevent_clipping: bool,
+    /// Loudness that untagged files with a measured loudness are normalized to
+    pub target_lufs: f32,
+}
+
+
+impl Default for ReplayGainSettings {
+    fn default() -> Self {
+        Self {
+            mode: ReplayGainMode::Off,
+            preamp_db: 0.0,
+            prevent_clipping: true,
+            target_lufs: DEFAULT_TARGET_LUFS,
+        }
+    }
+}
+
+
+impl ReplayGainSettings {
+    /// Returns the linear gain to apply to a track with the given tag values.
+    ///
+    /// `in_album` tells the auto mode whether the track is being played as part of
+    /// its album. Files without ReplayGain tags are normalized from their measured
+    /// `loudness` when known, and left untouched otherwise. With the mode off, nothing
+    /// is adjusted, measured loudness included.
+    pub fn factor( &self, values: &ReplayGain, loudness: Option<Loudness>, in_album: bool ) -> f32 {
+        let use_album = match self.mode {
+            ReplayGainMode::Off => return 1.0,
+            ReplayGainMode::Track => false,
+            ReplayGainMode::Album => true,
+            ReplayGainMode::Auto => in_album,
+        };
+
+        // Fall back to the other value when only one of them is tagged
+        let ( gain, peak ) = if use_album {
+            ( values.album_gain.or( values.track_gain ), values.album_peak.or( values.track_peak ) )
+        } else {
+            ( values.track_gain.or( values.album_gain ), values.track_peak.or( values.album_peak ) )
+        };
+
+        // Untagged files fall back to their measured loudness
+        let measured = loudness.filter( |_| gain.is_none() );
+        let gain = gain.or( measured.map( |l| l.gain_to( self.target_lufs ) ) );
+        let peak = peak.or( measured.map( |l| l.peak ) );
+
+        let Some( gain ) = gain else {
+            return 1.0;
+        };
+
+        let mut factor = db_to_linear( gain + self.preamp_db );
+        if self.prevent_clipping {
+            if let Some( peak ) = peak.filter( |p| *p > 0.0 ) {
+                factor = factor.min( 1.0 / peak );
+            }
+        }
+        factor
+    }
+}
+
+
+/// Processing stage applying a track's ReplayGain under the shared settings.
+pub struct ReplayGainStage {
+    settings: Arc<RwLock<ReplayGainSettings>>,
+    values: ReplayGain,
+    loudness: Option<Loudness>,
+    in_album: bool,
+}
+
+
+impl ReplayGainStage {
+    /// Creates the stage for a track with the given tags and measured loudness.
+    pub fn new(
+        settings: Arc<RwLock<ReplayGainSettings>>,
+        values: ReplayGain,
+        loudness: Option<Loudness>,
+        in_album: bool,
+    ) -> Self {
+        Self { settings, values, loudness, in_album }
+    }
+}
+
+
+impl AudioProcessor for ReplayGainStage {
+    fn name( &self ) -> &'static str {
+        "replaygain"
+    }
+
+
+    fn process( &mut self, samples: &mut Vec<f32>, _channels: usize, _sample_rate: u32 ) {
+        let gain = self.settings.read().unwrap().factor( &self.values, self.loudness, self.in_album );
+        if gain != 1.0 {
+            for sample in samples.iter_mut() {
+                *sample *= gain;
+            }
+        }
+    }
+}
+
+
+/// Converts a gain in dB to a linear factor.
+pub fn db_to_linear( db: f32 ) -> f32 {
+    10f32.powf( db / 20.0 )
+}
+
+
+/// Parses a gain tag value such as "-6.48 dB".
+pub fn parse_gain( value: &str ) -> Option<f32> {
+    let value = value.trim();
+    let value = value
+        .strip_suffix( "dB" )
+        .or_else( || value.strip_suffix( "db" ) )
+        .or_else( || value.strip_suffix( "DB" ) )
+        .unwrap_or( value );
+    value.trim().parse().ok().filter( |g: &f32| g.is_finite() )
+}
+
+
+/// Parses a peak tag value such as "0.988525".
+pub fn parse_peak( value: &str ) -> Option<f32> {
+    value.trim().parse().ok().filter( |p: &f32| p.is_finite() && *p >= 0.0 )
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_parse_gain() {
+        assert_eq!( parse_gain( "-6.48 dB" ), Some( -6.48 ) );
+        assert_eq!( parse_gain( "+2.5dB" ), Some( 2.5 ) );
+        assert_eq!( parse_gain( "garbage" ), None );
+    }
+
+
+    #[test]
+    fn test_peak_prevents_clipping() {
+        let values = ReplayGain {
+            track_gain: Some( 6.0 ),
+            track_peak: Some( 0.9 ),
+            ..Default::default()
+        };
+        let settings = ReplayGainSettings { mode: ReplayGainMode::Track, ..Default::default() };
+        assert!(( settings.factor( &values, None, false ) - 1.0 / 0.9 ).abs() < 1e-5 );
+
+        let settings = ReplayGainSettings { prevent_clipping: false, ..settings };
+        assert!(( settings.factor( &values, None, false ) - db_to_linear( 6.0 ) ).abs() < 1e-5 );
+    }
+
+
+    #[test]
+    fn test_auto_mode_picks_album_in_order() {
+        let values = ReplayGain {
+            track_gain: Some( -3.0 ),
+            album_gain: Some( -6.0 ),
+            ..Default::default()
+        };
+        let settings = ReplayGainSettings { mode: ReplayGainMode::Auto, ..Default::default() };
+        assert_eq!( settings.factor( &values, None, true ), db_to_linear( -6.0 ) );
+        assert_eq!( settings.factor( &values, None, false ), db_to_linear( -3.0 ) );
+    }
+
+
+    #[test]
+    fn test_measured_loudness_for_untagged() {
+        let measured = Loudness { integrated_lufs: -12.0, peak: 0.5 };
+        let settings = ReplayGainSettings { mode: ReplayGainMode::Track, ..Default::default() };
+        assert_eq!( settings.factor( &ReplayGain::default(), Some( measured ), false ), db_to_linear( -6.0 ) );
+    }
+}
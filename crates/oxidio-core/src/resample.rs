@@ -0,0 +1,450 @@
+//! Sample rate conversion
+//!
+//! Wraps rubato as a processing stage that converts a track to the output rate,
+//! with a choice between a fast polynomial and band-limited sinc interpolation.
+
+use rubato::{
+    calculate_cutoff, FastFixedOut, PolynomialDegree, ResampleError, ResampleResult, ResamplerConstructionError,
+    Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction,
+};
+
+use crate::dsp::AudioProcessor;
+
+
+/// Number of frames handed to the resampler at a time.
+const CHUNK_SIZE: usize = 1024;
+
+
+/// Trade-off between resampling quality and CPU use.
+#[derive( Debug, Clone, Copy, PartialEq, Eq, Default )]
+#[cfg_attr( feature = "serde", derive( serde::Serialize, serde::Deserialize ) )]
+#[cfg_attr( feature = "serde", serde( rename_all = "kebab-case" ) )]
+pub enum ResampleQuality {
+    /// Cubic polynomial interpolation. Cheap, but aliases audibly on some material.
+    Fast,
+
+    /// Short sinc filter with linear interpolation between its points (default)
+    #[default]
+    Balanced,
+
+    /// Long sinc filter with cubic interpolation between its points
+    High,
+}
+
+
+impl ResampleQuality {
+    /// Returns the display name of the quality.
+    pub fn name( &self ) -> &'static str {
+        match self {
+            ResampleQuality::Fast => "fast",
+            ResampleQuality::Balanced => "balanced sinc",
+            ResampleQuality::High => "high-quality sinc",
+        }
+    }
+
+
+    /// Returns the next quality, wrapping around after the highest.
+    pub fn next( self ) -> Self {
+        match self {
+            ResampleQuality::Fast => ResampleQuality::Balanced,
+            ResampleQuality::Balanced => ResampleQuality::High,
+            ResampleQuality::High => ResampleQuality::Fast,
+        }
+    }
+
+
+    /// Returns the (filter length, oversampling factor, interpolation) of a sinc quality.
+    fn sinc_parameters( self ) -> Option<( usize, usize, SincInterpolationType )> {
+        match self {
+            ResampleQuality::Fast => None,
+            ResampleQuality::Balanced => Some(( 64, 128, SincInterpolationType::Linear )),
+            ResampleQuality::High => Some(( 256, 256, SincInterpolationType::Cubic )),
+        }
+    }
+}
+
+
+/// Window applied to the sinc filter.
+#[derive( Debug, Clone, Copy, PartialEq, Eq, Default )]
+#[cfg_attr( feature = "serde", derive( serde::Serialize, serde::Deserialize ) )]
+#[cfg_attr( feature = "serde", serde( rename_all = "kebab-case" ) )]
+pub enum SincWindow {
+    /// Squared Blackman. Steep rolloff, good attenuation.
+    Blackman,
+
+    /// Squared Blackman-Harris. Gentler rolloff, best attenuation (default)
+    #[default]
+    BlackmanHarris,
+
+    /// Squared Hann. Steepest rolloff, least attenuation.
+    Hann,
+}
+
+
+impl SincWindow {
+    /// Returns the display name of the window.
+    pub fn name( &self ) -> &'static str {
+        match self {
+            SincWindow::Blackman => "blackman",
+            SincWindow::BlackmanHarris => "blackman-harris",
+            SincWindow::Hann => "hann",
+        }
+    }
+
+
+    fn function( self ) -> WindowFunction {
+        match self {
+            SincWindow::Blackman => WindowFunction::Blackman2,
+            SincWindow::BlackmanHarris => WindowFunction::BlackmanHarris2,
+            SincWindow::Hann => WindowFunction::Hann2,
+        }
+    }
+}
+
+
+/// How tracks are converted to the output sample rate.
+#[derive( Debug, Clone, Copy, PartialEq, Default )]
+#[cfg_attr( feature = "serde", derive( serde::Serialize, serde::Deserialize ) )]
+pub struct ResampleSettings {
+    pub quality: ResampleQuality,
+
+    /// Window of the sinc filter. Unused by `ResampleQuality::Fast`.
+    pub window: SincWindow,
+
+    /// Filter cutoff relative to the lower Nyquist frequency (0.5 to 0.99).
+    /// None picks the highest cutoff the filter length and window allow.
+    pub cutoff: Option<f32>,
+}
+
+
+/// Converts planar samples back to interleaved format.
+/// [[L0, L1, ...], [R0, R1, ...]] → [L0, R0, L1, R1, ...]
+fn interleave( channels: &[Vec<f32>] ) -> Vec<f32> {
+    if channels.is_empty() || channels[ 0 ].is_empty() {
+        return Vec::new();
+    }
+    let frames = channels[ 0 ].len();
+    let num_ch = channels.len();
+    let mut out = Vec::with_capacity( frames * num_ch );
+    for f in 0..frames {
+        for ch in channels {
+            out.push( ch[ f ] );
+        }
+    }
+    out
+}
+
+
+/// The rubato resampler behind a `Resample`, picked by quality.
+enum Engine {
+    Fast( FastFixedOut<f32> ),
+    Sinc( SincFixedIn<f32> ),
+}
+
+
+impl Engine {
+    fn input_frames_next( &self ) -> usize {
+        match self {
+            Engine::Fast( r ) => r.input_frames_next(),
+            Engine::Sinc( r ) => r.input_frames_next(),
+        }
+    }
+
+
+    /// Number of output frames the audio comes out late by.
+    fn output_delay( &self ) -> usize {
+        match self {
+            Engine::Fast( r ) => r.output_delay(),
+            // The sinc resampler starts its first window half a filter length before
+            // the first input frame, so its output is already aligned
+            Engine::Sinc( _ ) => 0,
+        }
+    }
+
+
+    fn process( &mut self, input: &[Vec<f32>] ) -> ResampleResult<Vec<Vec<f32>>> {
+        match self {
+            Engine::Fast( r ) => r.process( input, None ),
+            Engine::Sinc( r ) => r.process( input, None ),
+        }
+    }
+
+
+    /// Processes fewer frames than a full chunk, padding with silence (None = all silence).
+    fn process_partial( &mut self, input: Option<&[Vec<f32>]> ) -> ResampleResult<Vec<Vec<f32>>> {
+        match self {
+            Engine::Fast( r ) => r.process_partial( input, None ),
+            Engine::Sinc( r ) => r.process_partial( input, None ),
+        }
+    }
+
+
+    fn reset( &mut self ) {
+        match self {
+            Engine::Fast( r ) => r.reset(),
+            Engine::Sinc( r ) => r.reset(),
+        }
+    }
+}
+
+
+/// Converts audio from one fixed sample rate to another.
+///
+/// The filter delay is trimmed from the start and the tail is flushed out at the
+/// end, so the output lines up with the input and is exactly as long.
+pub struct Resample {
+    engine: Engine,
+    /// Input buffer for the resampler (stores planar samples per channel)
+    input: Vec<Vec<f32>>,
+    output_rate: u32,
+    /// Output frames per input frame
+    ratio: f64,
+    /// Leading output frames still to drop to make up for the filter delay
+    skip: usize,
+    /// Frames taken in and given out since the last reset
+    frames_in: u64,
+    frames_out: u64,
+}
+
+
+impl Resample {
+    /// Creates a resampler for `channels` channels of audio.
+    pub fn new(
+        input_rate: u32,
+        output_rate: u32,
+        channels: usize,
+        settings: ResampleSettings,
+    ) -> Result<Self, ResamplerConstructionError> {
+        tracing::info!( "Resampling: {} Hz → {} Hz ({})", input_rate, output_rate, settings.quality.name() );
+
+        let ratio = output_rate as f64 / input_rate as f64;
+        let engine = match settings.quality.sinc_parameters() {
+            // FastFixedOut handles variable input sizes
+            None => Engine::Fast( FastFixedOut::<f32>::new(
+                ratio,
+                2.0, // max relative input/output size ratio
+                PolynomialDegree::Cubic,
+                CHUNK_SIZE,
+                channels,
+            )? ),
+            Some(( sinc_len, oversampling_factor, interpolation )) => {
+                let window = settings.window.function();
+                let f_cutoff = settings.cutoff
+                    .map_or_else( || calculate_cutoff( sinc_len, window ), |cutoff| cutoff.clamp( 0.5, 0.99 ) );
+                let parameters = SincInterpolationParameters {
+                    sinc_len,
+                    f_cutoff,
+                    interpolation,
+                    oversampling_factor,
+                    window,
+                };
+                Engine::Sinc( SincFixedIn::<f32>::new( ratio, 1.0, parameters, CHUNK_SIZE, channels )? )
+            }
+        };
+
+        Ok( Self {
+            skip: engine.output_delay(),
+            engine,
+            input: ( 0..channels ).map( |_| Vec::new() ).collect(),
+            output_rate,
+            ratio,
+            frames_in: 0,
+            frames_out: 0,
+        })
+    }
+
+
+    /// Resamples as many full chunks as the buffered input allows.
+    fn drain_input( &mut self ) -> Result<Vec<f32>, ResampleError> {
+        let mut output = Vec::new();
+        while self.input[ 0 ].len() >= self.engine.input_frames_next() {
+            let needed = self.engine.input_frames_next();
+
+            // Extract needed frames from input buffer
+            let chunk: Vec<Vec<f32>> = self.input
+                .iter_mut()
+                .map( |ch| ch.drain( ..needed ).collect() )
+                .collect();
+
+            match self.engine.process( &chunk ) {
+                Ok( resampled ) => self.emit( &resampled, &mut output ),
+                Err( e ) => {
+                    // Put samples back on error
+                    for ( ch_idx, samples ) in chunk.into_iter().enumerate() {
+                        self.input[ ch_idx ].splice( 0..0, samples );
+                    }
+                    return Err( e );
+                }
+            }
+        }
+        Ok( output )
+    }
+
+
+    /// Appends resampled frames to `output`, dropping any delay still left to skip.
+    fn emit( &mut self, resampled: &[Vec<f32>], output: &mut Vec<f32> ) {
+        let frames = resampled.first().map_or( 0, |ch| ch.len() );
+        let skipped = self.skip.min( frames );
+        self.skip -= skipped;
+        self.frames_out += ( frames - skipped ) as u64;
+
+        let start = output.len();
+        output.extend( interleave( resampled ) );
+        output.drain( start..start + skipped * resampled.len() );
+    }
+}
+
+
+impl AudioProcessor for Resample {
+    fn name( &self ) -> &'static str {
+        "resample"
+    }
+
+
+    fn process( &mut self, samples: &mut Vec<f32>, channels: usize, _sample_rate: u32 ) {
+        // Add new samples to input buffer (convert interleaved to planar)
+        for frame in samples.chunks( channels ) {
+            for ( ch_idx, sample ) in frame.iter().enumerate() {
+                if let Some( input ) = self.input.get_mut( ch_idx ) {
+                    input.push( *sample );
+                }
+            }
+        }
+        self.frames_in += ( samples.len() / channels.max( 1 ) ) as u64;
+
+        *samples = self.drain_input().unwrap_or_else( |e| {
+            tracing::error!( "Resample error: {}", e );
+            Vec::new()
+        });
+    }
+
+
+    fn output_rate( &self, _sample_rate: u32 ) -> u32 {
+        self.output_rate
+    }
+
+
+    fn flush( &mut self, _channels: usize, _sample_rate: u32 ) -> Vec<f32> {
+        if self.frames_in == 0 {
+            return Vec::new();
+        }
+        let expected = ( self.frames_in as f64 * self.ratio ).round() as u64;
+        let mut output = Vec::new();
+
+        // Push the remaining samples through, then silence until the delayed tail is out
+        let input = std::mem::take( &mut self.input );
+        let mut result = self.engine.process_partial( Some( &input ) );
+        self.input = input.into_iter().map( |mut ch| { ch.clear(); ch } ).collect();
+        loop {
+            match result {
+                Ok( resampled ) => {
+                    self.emit( &resampled, &mut output );
+                    if self.frames_out >= expected || resampled.first().is_none_or( |ch| ch.is_empty() ) {
+                        break;
+                    }
+                    result = self.engine.process_partial( None );
+                }
+                Err( e ) => {
+                    tracing::error!( "Final resample error: {}", e );
+                    break;
+                }
+            }
+        }
+
+        // Trim the silence past the end of the input
+        let excess = self.frames_out.saturating_sub( expected ) as usize;
+        output.truncate( output.len().saturating_sub( excess * self.input.len() ) );
+        self.frames_out -= excess as u64;
+        output
+    }
+
+
+    fn reset( &mut self ) {
+        self.engine.reset();
+        for input in &mut self.input {
+            input.clear();
+        }
+        self.skip = self.engine.output_delay();
+        self.frames_in = 0;
+        self.frames_out = 0;
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    fn sine( frequency: f32, rate: u32, frames: usize ) -> Vec<f32> {
+        ( 0..frames )
+            .map( |i| ( 2.0 * std::f32::consts::PI * frequency * i as f32 / rate as f32 ).sin() * 0.5 )
+            .collect()
+    }
+
+
+    /// Feeds `input` through in uneven blocks, like a decoder would, and flushes.
+    fn run( resample: &mut Resample, input: &[f32] ) -> Vec<f32> {
+        let mut output = Vec::new();
+        for block in input.chunks( 1000 ) {
+            let mut samples = block.to_vec();
+            resample.process( &mut samples, 1, 0 );
+            output.extend( samples );
+        }
+        output.extend( resample.flush( 1, 0 ) );
+        output
+    }
+
+
+    fn rms( samples: &[f32] ) -> f32 {
+        ( samples.iter().map( |s| s * s ).sum::<f32>() / samples.len() as f32 ).sqrt()
+    }
+
+
+    #[test]
+    fn test_output_is_aligned_and_complete() {
+        for quality in [ ResampleQuality::Fast, ResampleQuality::Balanced, ResampleQuality::High ] {
+            let settings = ResampleSettings { quality, ..Default::default() };
+            let mut resample = Resample::new( 44100, 48000, 1, settings ).unwrap();
+
+            let input = sine( 200.0, 44100, 44100 );
+            let output = run( &mut resample, &input );
+            assert_eq!( output.len(), 48000, "{}", quality.name() );
+
+            // With the filter delay trimmed, the output matches the same sine at the new rate
+            let expected = sine( 200.0, 48000, 48000 );
+            let error: Vec<f32> = output.iter().zip( &expected ).map( |( a, b )| a - b ).collect();
+            assert!( rms( &error[ 1000..47000 ] ) < 0.01, "{}: error {}", quality.name(), rms( &error[ 1000..47000 ] ) );
+        }
+    }
+
+
+    #[test]
+    fn test_sinc_rejects_aliasing() {
+        // A 30 kHz tone can't be represented at 48 kHz and must be filtered out,
+        // instead of folding back down to 18 kHz
+        let input = sine( 30000.0, 96000, 96000 );
+        let alias = |quality| {
+            let settings = ResampleSettings { quality, ..Default::default() };
+            let mut resample = Resample::new( 96000, 48000, 1, settings ).unwrap();
+            let output = run( &mut resample, &input );
+            rms( &output[ 1000..47000 ] )
+        };
+
+        let high = alias( ResampleQuality::High );
+        assert!( high < 0.001, "alias level {}", high );
+        assert!( alias( ResampleQuality::Balanced ) < alias( ResampleQuality::Fast ) );
+    }
+
+
+    #[test]
+    fn test_reset_starts_over() {
+        let mut resample = Resample::new( 44100, 48000, 2, ResampleSettings::default() ).unwrap();
+        let mut samples = vec![ 0.25; 8820 ];
+        resample.process( &mut samples, 2, 0 );
+        resample.reset();
+
+        // Nothing from before the reset is flushed
+        assert!( resample.flush( 2, 0 ).is_empty() );
+    }
+}
@@ -0,0 +1,286 @@
+//! Audio sinks
+//!
+//! Defines the `AudioSink` trait the player sends its output to, along with sinks
+//! that don't need a sound card: one that renders playback to a WAV file and one
+//! that discards it.
+
+use std::fs::File;
+use std::io::{ BufWriter, Seek, SeekFrom, Write };
+use std::path::Path;
+use std::sync::{ mpsc, Arc };
+use std::thread;
+use std::time::{ Duration, Instant };
+
+use crate::output::{ OutputError, OutputFormat, SampleBuffer };
+
+
+/// Destination for the player's mixed output.
+///
+/// A sink owns the `SampleBuffer` the player pushes into and drains it at its own
+/// pace on a thread of its own. Dropping the sink stops it.
+pub trait AudioSink: Send + Sync {
+    /// Format of the samples the sink consumes. Producers resample and remix to it.
+    fn format( &self ) -> OutputFormat;
+
+
+    /// Gets the buffer feeding the sink.
+    fn sample_buffer( &self ) -> Arc<SampleBuffer>;
+}
+
+
+/// How fast a sink without a device consumes its samples.
+#[derive( Debug, Clone, Copy, PartialEq, Eq, Default )]
+pub enum Pacing {
+    /// In step with the wall clock, like a sound card. Pauses and gaps are
+    /// rendered as silence.
+    #[default]
+    Realtime,
+
+    /// As fast as the player can decode. Only samples that were actually played
+    /// are rendered, so pauses and gaps leave no trace.
+    Fast,
+}
+
+
+/// Amount of audio taken from the buffer at a time.
+const PERIOD: Duration = Duration::from_millis( 10 );
+
+
+/// Creates a buffer holding ~500ms of audio in `format`, matching the cpal output.
+fn new_buffer( format: OutputFormat ) -> Arc<SampleBuffer> {
+    let capacity = format.sample_rate as usize * format.channels as usize / 2;
+    Arc::new( SampleBuffer::new( capacity, format.channels ) )
+}
+
+
+/// Drains a sample buffer on a background thread, handing each chunk to a writer.
+struct Pump {
+    shutdown: mpsc::Sender<()>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+
+impl Pump {
+    /// Starts draining `buffer` at the given pace until the pump is dropped.
+    fn spawn(
+        name: &str,
+        buffer: Arc<SampleBuffer>,
+        format: OutputFormat,
+        pacing: Pacing,
+        mut write: impl FnMut( &[f32] ) + Send + 'static,
+    ) -> Result<Self, OutputError> {
+        let ( shutdown_tx, shutdown_rx ) = mpsc::channel::<()>();
+
+        let thread = thread::Builder::new()
+            .name( name.into() )
+            .spawn( move || {
+                let period_frames = ( format.sample_rate as f64 * PERIOD.as_secs_f64() ).max( 1.0 ) as usize;
+                let mut chunk = vec![ 0.0; period_frames * format.channels as usize ];
+                let started = Instant::now();
+                let mut frames_played: u64 = 0;
+
+                loop {
+                    let wait = match pacing {
+                        Pacing::Realtime => {
+                            let due = started + Duration::from_secs_f64( frames_played as f64 / format.sample_rate as f64 );
+                            due.saturating_duration_since( Instant::now() )
+                        }
+                        Pacing::Fast => Duration::ZERO,
+                    };
+
+                    // Sleeps until the next period is due, waking early to shut down
+                    match shutdown_rx.recv_timeout( wait ) {
+                        Err( mpsc::RecvTimeoutError::Timeout ) => {}
+                        _ => break,
+                    }
+
+                    let written = buffer.pop( &mut chunk );
+                    match pacing {
+                        Pacing::Realtime => {
+                            write( &chunk );
+                            frames_played += period_frames as u64;
+                        }
+                        Pacing::Fast if written > 0 => write( &chunk[ ..written ] ),
+                        Pacing::Fast => {
+                            // Nothing to play yet, don't spin
+                            if shutdown_rx.recv_timeout( Duration::from_millis( 1 ) ) != Err( mpsc::RecvTimeoutError::Timeout ) {
+                                break;
+                            }
+                        }
+                    }
+                }
+            })
+            .map_err( |e| OutputError::BuildStream( e.to_string() ) )?;
+
+        Ok( Self {
+            shutdown: shutdown_tx,
+            thread: Some( thread ),
+        })
+    }
+}
+
+
+impl Drop for Pump {
+    fn drop( &mut self ) {
+        let _ = self.shutdown.send(());
+        if let Some( thread ) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+
+/// Sink that consumes samples and throws them away.
+pub struct NullSink {
+    sample_buffer: Arc<SampleBuffer>,
+    format: OutputFormat,
+    _pump: Pump,
+}
+
+
+impl NullSink {
+    /// Creates a sink consuming `format` at the given pace.
+    pub fn new( format: OutputFormat, pacing: Pacing ) -> Result<Self, OutputError> {
+        let sample_buffer = new_buffer( format );
+        let pump = Pump::spawn( "oxidio-null-sink", Arc::clone( &sample_buffer ), format, pacing, |_| {} )?;
+
+        Ok( Self {
+            sample_buffer,
+            format,
+            _pump: pump,
+        })
+    }
+}
+
+
+impl AudioSink for NullSink {
+    fn format( &self ) -> OutputFormat {
+        self.format
+    }
+
+
+    fn sample_buffer( &self ) -> Arc<SampleBuffer> {
+        Arc::clone( &self.sample_buffer )
+    }
+}
+
+
+/// Sink that renders playback to a 32-bit float WAV file.
+///
+/// The file is complete once the sink is dropped.
+pub struct WavSink {
+    sample_buffer: Arc<SampleBuffer>,
+    format: OutputFormat,
+    _pump: Pump,
+}
+
+
+impl WavSink {
+    /// Creates (or truncates) the file at `path` and starts writing `format` to it.
+    pub fn create( path: impl AsRef<Path>, format: OutputFormat, pacing: Pacing ) -> Result<Self, OutputError> {
+        let path = path.as_ref();
+        let mut writer = WavWriter::create( path, format )
+            .map_err( |e| OutputError::File( format!( "{}: {}", path.display(), e ) ) )?;
+
+        let sample_buffer = new_buffer( format );
+        let file_name = path.display().to_string();
+        let mut failed = false;
+        let pump = Pump::spawn( "oxidio-wav-sink", Arc::clone( &sample_buffer ), format, pacing, move |samples| {
+            if failed {
+                return;
+            }
+            if let Err( e ) = writer.write( samples ) {
+                tracing::error!( "Failed to write {}: {}", file_name, e );
+                failed = true;
+            }
+        })?;
+
+        Ok( Self {
+            sample_buffer,
+            format,
+            _pump: pump,
+        })
+    }
+}
+
+
+impl AudioSink for WavSink {
+    fn format( &self ) -> OutputFormat {
+        self.format
+    }
+
+
+    fn sample_buffer( &self ) -> Arc<SampleBuffer> {
+        Arc::clone( &self.sample_buffer )
+    }
+}
+
+
+/// Minimal streaming writer for IEEE float WAV files.
+///
+/// The chunk sizes are filled in when the writer is dropped.
+struct WavWriter {
+    file: BufWriter<File>,
+    /// Bytes of sample data written so far
+    data_len: u32,
+}
+
+
+impl WavWriter {
+    /// Size of the RIFF, fmt and data chunk headers
+    const HEADER_LEN: u32 = 44;
+
+
+    fn create( path: &Path, format: OutputFormat ) -> std::io::Result<Self> {
+        let mut file = BufWriter::new( File::create( path )? );
+
+        let channels = format.channels;
+        let block_align = channels * 4;
+        let byte_rate = format.sample_rate * block_align as u32;
+
+        file.write_all( b"RIFF" )?;
+        file.write_all( &( Self::HEADER_LEN - 8 ).to_le_bytes() )?;
+        file.write_all( b"WAVE" )?;
+        file.write_all( b"fmt " )?;
+        file.write_all( &16u32.to_le_bytes() )?;
+        // WAVE_FORMAT_IEEE_FLOAT
+        file.write_all( &3u16.to_le_bytes() )?;
+        file.write_all( &channels.to_le_bytes() )?;
+        file.write_all( &format.sample_rate.to_le_bytes() )?;
+        file.write_all( &byte_rate.to_le_bytes() )?;
+        file.write_all( &block_align.to_le_bytes() )?;
+        file.write_all( &32u16.to_le_bytes() )?;
+        file.write_all( b"data" )?;
+        file.write_all( &0u32.to_le_bytes() )?;
+
+        Ok( Self { file, data_len: 0 } )
+    }
+
+
+    fn write( &mut self, samples: &[f32] ) -> std::io::Result<()> {
+        for sample in samples {
+            self.file.write_all( &sample.to_le_bytes() )?;
+        }
+        self.data_len = self.data_len.saturating_add( samples.len() as u32 * 4 );
+        Ok(())
+    }
+
+
+    /// Writes the final chunk sizes into the header.
+    fn finish( &mut self ) -> std::io::Result<()> {
+        self.file.seek( SeekFrom::Start( 4 ) )?;
+        self.file.write_all( &( Self::HEADER_LEN - 8 + self.data_len ).to_le_bytes() )?;
+        self.file.seek( SeekFrom::Start( Self::HEADER_LEN as u64 - 4 ) )?;
+        self.file.write_all( &self.data_len.to_le_bytes() )?;
+        self.file.flush()
+    }
+}
+
+
+impl Drop for WavWriter {
+    fn drop( &mut self ) {
+        if let Err( e ) = self.finish() {
+            tracing::error!( "Failed to finalize WAV file: {}", e );
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Spectrum analysis for visualization
+//!
+//! Turns the most recently played audio into levels for log-spaced frequency
+//! bands, with bars that fall off gradually and held peaks.
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::{ Duration, Instant };
+
+use realfft::num_complex::Complex;
+use realfft::{ RealFftPlanner, RealToComplex };
+
+
+/// Number of samples analyzed at a time (~43ms at 48kHz).
+pub const FFT_SIZE: usize = 2048;
+
+/// Lower edge of the lowest band in Hz.
+const MIN_FREQUENCY: f32 = 30.0;
+
+/// Upper edge of the highest band in Hz, if the sample rate allows it.
+const MAX_FREQUENCY: f32 = 16000.0;
+
+/// Level in dBFS shown as an empty band.
+const FLOOR_DB: f32 = -72.0;
+
+/// Longest gap between two analyses that still counts towards the falloff.
+const MAX_STEP: Duration = Duration::from_secs( 1 );
+
+
+/// How the spectrum display responds when the audio gets quieter.
+#[derive( Debug, Clone, Copy, PartialEq )]
+pub struct SpectrumSettings {
+    /// Speed in dB per second at which bars and expired peaks fall
+    pub falloff: f32,
+
+    /// How long a peak stays in place before it starts falling
+    pub peak_hold: Duration,
+}
+
+
+impl Default for SpectrumSettings {
+    fn default() -> Self {
+        Self {
+            falloff: 60.0,
+            peak_hold: Duration::from_millis( 800 ),
+        }
+    }
+}
+
+
+/// Band levels and held peaks, lowest frequency first.
+///
+/// Values run from 0.0 (silence) to 1.0 (full scale) on a decibel scale.
+#[derive( Debug, Clone, Default, PartialEq )]
+pub struct Spectrum {
+    pub levels: Vec<f32>,
+    pub peaks: Vec<f32>,
+}
+
+
+/// A held peak in dBFS and when it was last raised.
+#[derive( Debug, Clone, Copy )]
+struct Peak {
+    db: f32,
+    since: Instant,
+}
+
+
+/// Windowed FFT analyzer keeping the falloff and peak state between frames.
+pub struct SpectrumAnalyzer {
+    settings: SpectrumSettings,
+    fft: Arc<dyn RealToComplex<f32>>,
+    /// Hann window over one block
+    window: Vec<f32>,
+    input: Vec<f32>,
+    output: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    /// Displayed level of each band in dBFS
+    levels: Vec<f32>,
+    peaks: Vec<Peak>,
+    last_update: Option<Instant>,
+}
+
+
+impl SpectrumAnalyzer {
+    /// Creates an analyzer with the given display settings.
+    pub fn new( settings: SpectrumSettings ) -> Self {
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward( FFT_SIZE );
+        let window = ( 0..FFT_SIZE )
+            .map( |i| 0.5 - 0.5 * ( 2.0 * PI * i as f32 / FFT_SIZE as f32 ).cos() )
+            .collect();
+
+        Self {
+            settings,
+            input: fft.make_input_vec(),
+            output: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            fft,
+            window,
+            levels: Vec::new(),
+            peaks: Vec::new(),
+            last_update: None,
+        }
+    }
+
+
+    /// Gets the display settings.
+    pub fn settings( &self ) -> SpectrumSettings {
+        self.settings
+    }
+
+
+    /// Sets the display settings. Takes effect on the next analysis.
+    pub fn set_settings( &mut self, settings: SpectrumSettings ) {
+        self.settings = settings;
+    }
+
+
+    /// Forgets the falloff and peak state, so the next analysis starts fresh.
+    pub fn reset( &mut self ) {
+        self.levels.clear();
+        self.peaks.clear();
+        self.last_update = None;
+    }
+
+
+    /// Analyzes the last `FFT_SIZE` samples of mono `samples` (oldest first) into
+    /// `bins` log-spaced bands.
+    pub fn analyze( &mut self, samples: &[f32], sample_rate: u32, bins: usize ) -> Spectrum {
+        self.analyze_at( samples, sample_rate, bins, Instant::now() )
+    }
+
+
+    fn analyze_at( &mut self, samples: &[f32], sample_rate: u32, bins: usize, now: Instant ) -> Spectrum {
+        if bins == 0 || sample_rate == 0 {
+            return Spectrum::default();
+        }
+        if self.levels.len() != bins {
+            self.reset();
+            self.levels = vec![ FLOOR_DB; bins ];
+            self.peaks = vec![ Peak { db: FLOOR_DB, since: now }; bins ];
+        }
+
+        let magnitudes = self.magnitudes( samples );
+
+        let step = self.last_update
+            .map_or( Duration::ZERO, |last| now.saturating_duration_since( last ).min( MAX_STEP ) );
+        let fall = self.settings.falloff.max( 0.0 ) * step.as_secs_f32();
+        self.last_update = Some( now );
+
+        // Spread the bands evenly over the octaves between the lowest and highest frequency
+        let top = MAX_FREQUENCY.min( sample_rate as f32 / 2.0 );
+        let ratio = top / MIN_FREQUENCY;
+        let hz_per_bin = sample_rate as f32 / FFT_SIZE as f32;
+
+        for band in 0..bins {
+            let low = MIN_FREQUENCY * ratio.powf( band as f32 / bins as f32 ) / hz_per_bin;
+            let high = MIN_FREQUENCY * ratio.powf( ( band + 1 ) as f32 / bins as f32 ) / hz_per_bin;
+
+            // Narrow low bands may not contain a whole FFT bin, so read between bins there
+            let first = low.ceil() as usize;
+            let last = ( high.floor() as usize ).min( magnitudes.len() - 1 );
+            let magnitude = if first <= last {
+                magnitudes[ first..=last ].iter().copied().fold( 0.0, f32::max )
+            } else {
+                let center = ( low * high ).sqrt();
+                let index = ( center.floor() as usize ).min( magnitudes.len() - 2 );
+                let t = center - index as f32;
+                magnitudes[ index ] * ( 1.0 - t ) + magnitudes[ index + 1 ] * t
+            };
+            let db = ( 20.0 * magnitude.max( 1e-9 ).log10() ).max( FLOOR_DB );
+
+            let level = &mut self.levels[ band ];
+            *level = db.max( *level - fall );
+
+            let peak = &mut self.peaks[ band ];
+            if *level >= peak.db {
+                *peak = Peak { db: *level, since: now };
+            } else if now.saturating_duration_since( peak.since ) > self.settings.peak_hold {
+                peak.db = ( peak.db - fall ).max( *level );
+            }
+        }
+
+        Spectrum {
+            levels: self.levels.iter().map( |db| scale( *db ) ).collect(),
+            peaks: self.peaks.iter().map( |peak| scale( peak.db ) ).collect(),
+        }
+    }
+
+
+    /// Windows the block and returns the amplitude of each FFT bin, where a
+    /// full-scale sine reads 1.0.
+    fn magnitudes( &mut self, samples: &[f32] ) -> Vec<f32> {
+        // Right-align the samples in the block, padding with silence if there are too few
+        let recent = &samples[ samples.len().saturating_sub( FFT_SIZE ).. ];
+        let padding = FFT_SIZE - recent.len();
+        self.input[ ..padding ].fill( 0.0 );
+        for ( i, sample ) in recent.iter().enumerate() {
+            self.input[ padding + i ] = sample * self.window[ padding + i ];
+        }
+
+        if self.fft.process_with_scratch( &mut self.input, &mut self.output, &mut self.scratch ).is_err() {
+            return vec![ 0.0; self.output.len() ];
+        }
+
+        // The Hann window halves the amplitude, and a real sine splits between two bins
+        let gain = 4.0 / FFT_SIZE as f32;
+        self.output.iter().map( |c| c.norm() * gain ).collect()
+    }
+}
+
+
+impl Default for SpectrumAnalyzer {
+    fn default() -> Self {
+        Self::new( SpectrumSettings::default() )
+    }
+}
+
+
+/// Maps a level in dBFS onto the 0.0 to 1.0 display range.
+fn scale( db: f32 ) -> f32 {
+    ( ( db - FLOOR_DB ) / -FLOOR_DB ).clamp( 0.0, 1.0 )
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    fn sine( frequency: f32, rate: u32, amplitude: f32 ) -> Vec<f32> {
+        ( 0..FFT_SIZE )
+            .map( |i| ( 2.0 * PI * frequency * i as f32 / rate as f32 ).sin() * amplitude )
+            .collect()
+    }
+
+
+    #[test]
+    fn test_sine_lands_in_its_band() {
+        let mut analyzer = SpectrumAnalyzer::default();
+        let spectrum = analyzer.analyze( &sine( 1000.0, 48000, 1.0 ), 48000, 16 );
+
+        let loudest = ( 0..16 ).max_by( |a, b| spectrum.levels[ *a ].total_cmp( &spectrum.levels[ *b ] ) ).unwrap();
+        let low = MIN_FREQUENCY * ( MAX_FREQUENCY / MIN_FREQUENCY ).powf( loudest as f32 / 16.0 );
+        let high = MIN_FREQUENCY * ( MAX_FREQUENCY / MIN_FREQUENCY ).powf( ( loudest + 1 ) as f32 / 16.0 );
+        assert!( low <= 1000.0 && 1000.0 < high, "loudest band {} is {}-{} Hz", loudest, low, high );
+
+        // A full-scale sine reads close to 0 dBFS
+        assert!( spectrum.levels[ loudest ] > 0.95, "level {}", spectrum.levels[ loudest ] );
+        assert!( spectrum.levels[ 0 ] < 0.5 );
+    }
+
+
+    #[test]
+    fn test_levels_fall_off_and_peaks_hold() {
+        let settings = SpectrumSettings { falloff: 36.0, peak_hold: Duration::from_millis( 500 ) };
+        let mut analyzer = SpectrumAnalyzer::new( settings );
+        let start = Instant::now();
+        let silence = vec![ 0.0; FFT_SIZE ];
+
+        let loud = analyzer.analyze_at( &sine( 1000.0, 48000, 1.0 ), 48000, 8, start );
+        let band = ( 0..8 ).find( |b| loud.levels[ *b ] > 0.95 ).unwrap();
+
+        // Half a second later the bar has dropped 18 dB, but the peak is still held
+        let later = analyzer.analyze_at( &silence, 48000, 8, start + Duration::from_millis( 500 ) );
+        assert!( ( later.levels[ band ] - ( loud.levels[ band ] - 0.25 ) ).abs() < 0.01 );
+        assert_eq!( later.peaks[ band ], loud.peaks[ band ] );
+
+        // Once the hold runs out the peak falls too, never below the bar
+        let after = analyzer.analyze_at( &silence, 48000, 8, start + Duration::from_millis( 750 ) );
+        assert!( after.peaks[ band ] < later.peaks[ band ] );
+        assert!( after.peaks[ band ] >= after.levels[ band ] );
+    }
+}
@@ -0,0 +1,334 @@
+//! Playback speed control
+//!
+//! Changes the tempo of the audio without changing its pitch, using WSOLA
+//! (waveform similarity overlap-add).
+
+use std::f32::consts::PI;
+use std::sync::{ Arc, RwLock };
+
+use crate::dsp::AudioProcessor;
+
+
+/// Slowest supported playback speed.
+pub const MIN_SPEED: f32 = 0.5;
+
+/// Fastest supported playback speed.
+pub const MAX_SPEED: f32 = 3.0;
+
+/// Brings a speed factor into the supported range, with anything but a finite
+/// number meaning normal speed.
+pub fn clamp_speed( speed: f32 ) -> f32 {
+    if speed.is_finite() { speed.clamp( MIN_SPEED, MAX_SPEED ) } else { 1.0 }
+}
+
+
+/// Length of each overlap-added segment.
+const WINDOW_SECS: f64 = 0.040;
+
+/// How far from its nominal position a segment may be taken to line up with the last one.
+const SEARCH_SECS: f64 = 0.010;
+
+/// Step between the candidate offsets and samples compared in the coarse search.
+const COARSE_STEP: usize = 4;
+
+
+/// Processing stage playing audio at a shared speed factor, keeping its pitch.
+///
+/// Segments of the input are taken at `speed` times the rate they're written out
+/// and cross-faded together, each one shifted slightly so its waveform lines up
+/// with the end of the previous one. At 1x the stage passes audio through untouched.
+pub struct TimeStretch {
+    speed: Arc<RwLock<f32>>,
+    /// Channel count and sample rate the window was sized for
+    format: Option<( usize, u32 )>,
+    /// Hann window over one segment
+    window: Vec<f32>,
+    /// Search radius in frames
+    search: usize,
+    /// Input not yet consumed, interleaved
+    input: Vec<f32>,
+    /// Frame in `input` the next segment would start at without searching
+    position: f64,
+    /// Frame in `input` where the last segment would naturally have continued.
+    /// None while passing audio through.
+    continuation: Option<usize>,
+    /// Windowed second half of the last segment, waiting for the next one
+    overlap: Vec<f32>,
+}
+
+
+impl TimeStretch {
+    /// Creates a time-stretch stage following a shared speed factor.
+    pub fn new( speed: Arc<RwLock<f32>> ) -> Self {
+        Self {
+            speed,
+            format: None,
+            window: Vec::new(),
+            search: 0,
+            input: Vec::new(),
+            position: 0.0,
+            continuation: None,
+            overlap: Vec::new(),
+        }
+    }
+
+
+    /// Sizes the window and search range for a new format, dropping any state.
+    fn configure( &mut self, channels: usize, sample_rate: u32 ) {
+        let length = ( ( sample_rate as f64 * WINDOW_SECS ) as usize / 2 * 2 ).max( 2 );
+        self.window = ( 0..length )
+            .map( |i| 0.5 - 0.5 * ( 2.0 * PI * i as f32 / length as f32 ).cos() )
+            .collect();
+        self.search = ( sample_rate as f64 * SEARCH_SECS ) as usize;
+        self.format = Some(( channels, sample_rate ));
+        self.reset();
+    }
+
+
+    /// Produces as many stretched frames as the buffered input allows.
+    fn stretch( &mut self, out: &mut Vec<f32>, channels: usize, speed: f32 ) {
+        let length = self.window.len();
+        let half = length / 2;
+        let hop = half as f64 * speed as f64;
+
+        loop {
+            let frames = self.input.len() / channels;
+
+            let continuation = match self.continuation {
+                None => {
+                    // First segment: its first half is played as-is, so there's no seam
+                    if frames < length {
+                        break;
+                    }
+                    out.extend_from_slice( &self.input[ ..half * channels ] );
+                    self.position = hop;
+                    half
+                }
+                Some( continuation ) => {
+                    let nominal = self.position.round() as usize;
+                    let lo = nominal.saturating_sub( self.search );
+                    let hi = nominal + self.search;
+                    if hi + length > frames {
+                        break;
+                    }
+
+                    let start = self.best_offset( continuation, lo, hi, channels );
+                    for i in 0..half {
+                        for c in 0..channels {
+                            let index = i * channels + c;
+                            out.push( self.overlap[ index ] + self.input[ start * channels + index ] * self.window[ i ] );
+                        }
+                    }
+                    self.position += hop;
+                    start + half
+                }
+            };
+
+            // Keep the second half to fade into the next segment
+            self.overlap.clear();
+            for i in half..length {
+                for c in 0..channels {
+                    let sample = self.input[ ( continuation - half + i ) * channels + c ];
+                    self.overlap.push( sample * self.window[ i ] );
+                }
+            }
+
+            // Drop input that no later segment or comparison can reach
+            let search_start = ( self.position.floor() as usize ).saturating_sub( self.search );
+            let consumed = continuation.min( search_start );
+            self.input.drain( ..consumed * channels );
+            self.position -= consumed as f64;
+            self.continuation = Some( continuation - consumed );
+        }
+    }
+
+
+    /// Finds the offset in `lo..=hi` whose first half best matches the natural
+    /// continuation of the last segment.
+    fn best_offset( &self, continuation: usize, lo: usize, hi: usize, channels: usize ) -> usize {
+        let half = self.window.len() / 2;
+
+        // Compare a mono mixdown of the span covering the template and every candidate
+        let first = lo.min( continuation );
+        let last = ( hi + half ).max( continuation + half );
+        let mono: Vec<f32> = self.input[ first * channels..last * channels ]
+            .chunks_exact( channels )
+            .map( |frame| frame.iter().sum() )
+            .collect();
+        let template = continuation - first;
+
+        let similarity = |offset: usize, step: usize| -> f32 {
+            let candidate = offset - first;
+            let ( mut dot, mut energy ) = ( 0.0, 0.0 );
+            for i in ( 0..half ).step_by( step ) {
+                let c = mono[ candidate + i ];
+                dot += mono[ template + i ] * c;
+                energy += c * c;
+            }
+            if energy > 0.0 { dot / energy.sqrt() } else { 0.0 }
+        };
+
+        let best_in = |range: std::ops::RangeInclusive<usize>, step: usize| -> usize {
+            let start = *range.start();
+            range.step_by( step ).fold( ( start, f32::MIN ), |( best, score ), offset| {
+                let s = similarity( offset, step );
+                if s > score { ( offset, s ) } else { ( best, score ) }
+            }).0
+        };
+
+        // Coarse pass over the whole range, then refine around the best match
+        let coarse = best_in( lo..=hi, COARSE_STEP );
+        let refine_lo = coarse.saturating_sub( COARSE_STEP ).max( lo );
+        let refine_hi = ( coarse + COARSE_STEP ).min( hi );
+        best_in( refine_lo..=refine_hi, 1 )
+    }
+
+
+    /// Finishes the last segment and returns the rest of the input unchanged.
+    ///
+    /// Fading the buffered input in against the last segment's tail gives back the
+    /// original signal, so switching to 1x this way is seamless.
+    fn drain( &mut self, channels: usize ) -> Vec<f32> {
+        let Some( continuation ) = self.continuation.take() else {
+            return std::mem::take( &mut self.input );
+        };
+
+        let half = self.window.len() / 2;
+        let mut out = Vec::with_capacity( self.input.len() );
+        for i in 0..half {
+            for c in 0..channels {
+                let index = i * channels + c;
+                let sample = self.input.get( continuation * channels + index ).copied().unwrap_or( 0.0 );
+                out.push( self.overlap[ index ] + sample * self.window[ i ] );
+            }
+        }
+        if let Some( rest ) = self.input.get( ( continuation + half ) * channels.. ) {
+            out.extend_from_slice( rest );
+        }
+
+        self.reset();
+        out
+    }
+}
+
+
+impl AudioProcessor for TimeStretch {
+    fn name( &self ) -> &'static str {
+        "timestretch"
+    }
+
+
+    fn process( &mut self, samples: &mut Vec<f32>, channels: usize, sample_rate: u32 ) {
+        if channels == 0 {
+            return;
+        }
+        if self.format != Some(( channels, sample_rate )) {
+            self.configure( channels, sample_rate );
+        }
+
+        let speed = clamp_speed( *self.speed.read().unwrap() );
+        if speed == 1.0 {
+            if self.continuation.is_none() {
+                return;
+            }
+            // Back to normal speed: wait for enough input to fade out the last segment
+            self.input.extend_from_slice( samples );
+            let buffered = self.input.len() / channels;
+            *samples = if buffered >= self.continuation.unwrap_or( 0 ) + self.window.len() / 2 {
+                self.drain( channels )
+            } else {
+                Vec::new()
+            };
+            return;
+        }
+
+        self.input.extend_from_slice( samples );
+        samples.clear();
+        self.stretch( samples, channels, speed );
+    }
+
+
+    fn flush( &mut self, channels: usize, _sample_rate: u32 ) -> Vec<f32> {
+        if channels == 0 {
+            return Vec::new();
+        }
+        self.drain( channels )
+    }
+
+
+    fn reset( &mut self ) {
+        self.input.clear();
+        self.overlap.clear();
+        self.position = 0.0;
+        self.continuation = None;
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    fn sine( frequency: f32, rate: u32, frames: usize ) -> Vec<f32> {
+        ( 0..frames )
+            .map( |i| ( 2.0 * PI * frequency * i as f32 / rate as f32 ).sin() * 0.5 )
+            .collect()
+    }
+
+
+    /// Runs mono `samples` through a stage at `speed` in chunks, including the flush.
+    fn stretch( samples: &[f32], rate: u32, speed: f32 ) -> Vec<f32> {
+        let mut stage = TimeStretch::new( Arc::new( RwLock::new( speed ) ) );
+        let mut out = Vec::new();
+        for chunk in samples.chunks( 1152 ) {
+            let mut chunk = chunk.to_vec();
+            stage.process( &mut chunk, 1, rate );
+            out.extend( chunk );
+        }
+        out.extend( stage.flush( 1, rate ) );
+        out
+    }
+
+
+    /// Counts upward zero crossings per second.
+    fn frequency( samples: &[f32], rate: u32 ) -> f32 {
+        let crossings = samples.windows( 2 ).filter( |w| w[ 0 ] < 0.0 && w[ 1 ] >= 0.0 ).count();
+        crossings as f32 * rate as f32 / samples.len() as f32
+    }
+
+
+    #[test]
+    fn test_normal_speed_is_passthrough() {
+        let input = sine( 440.0, 48000, 48000 );
+        assert_eq!( stretch( &input, 48000, 1.0 ), input );
+    }
+
+
+    #[test]
+    fn test_non_finite_speed_plays_normally() {
+        assert_eq!( clamp_speed( f32::NAN ), 1.0 );
+        assert_eq!( clamp_speed( f32::INFINITY ), 1.0 );
+        assert_eq!( clamp_speed( 10.0 ), MAX_SPEED );
+
+        let input = sine( 440.0, 48000, 4800 );
+        assert_eq!( stretch( &input, 48000, f32::NAN ), input );
+    }
+
+
+    #[test]
+    fn test_speed_changes_length_not_pitch() {
+        let rate = 48000;
+        let input = sine( 440.0, rate, rate as usize * 2 );
+
+        for speed in [ 0.5, 2.0 ] {
+            let output = stretch( &input, rate, speed );
+            // The last few buffered milliseconds are flushed unstretched
+            let expected = input.len() as f32 / speed;
+            assert!( ( output.len() as f32 - expected ).abs() < rate as f32 * 0.1, "{}x: {} samples", speed, output.len() );
+
+            let middle = &output[ output.len() / 4..output.len() * 3 / 4 ];
+            assert!( ( frequency( middle, rate ) - 440.0 ).abs() < 5.0, "{}x: {} Hz", speed, frequency( middle, rate ) );
+        }
+    }
+}
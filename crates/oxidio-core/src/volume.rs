@@ -0,0 +1,364 @@
+//! Volume scale and limiting
+//!
+//! The volume is set in decibels, which maps evenly onto perceived loudness, and
+//! can be boosted above unity. A true-peak limiter at the end of the chain keeps
+//! the output from clipping, whether the volume, the equalizer or ReplayGain
+//! pushed it over.
+
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use crate::dsp::AudioProcessor;
+use crate::replaygain::db_to_linear;
+
+
+/// Volume at or below which the output is muted.
+pub const MIN_VOLUME_DB: f32 = -60.0;
+
+/// Highest volume, boosting by up to this much above unity.
+pub const MAX_VOLUME_DB: f32 = 12.0;
+
+/// Level the limiter keeps true peaks under, in dBTP.
+const CEILING_DB: f32 = -0.1;
+
+/// How far ahead the limiter looks for peaks, in seconds.
+const LOOKAHEAD: f32 = 0.002;
+
+/// Time for the limiter to recover most of its gain after a peak, in seconds.
+const RELEASE: f32 = 0.1;
+
+/// Oversampling factor of the true-peak detector.
+const PHASES: usize = 4;
+
+/// Taps per phase of the true-peak interpolation filter.
+const TAPS: usize = 12;
+
+/// Frames between a sample going into the peak detector and the interpolated
+/// values around it coming out.
+const DETECTOR_DELAY: usize = TAPS / 2 - 1;
+
+
+/// Converts a volume in dB to a linear gain. Anything at or below `MIN_VOLUME_DB`, or
+/// NaN, is silence.
+pub fn volume_gain( db: f32 ) -> f32 {
+    if db.is_nan() || db <= MIN_VOLUME_DB {
+        0.0
+    } else {
+        db_to_linear( db.min( MAX_VOLUME_DB ) )
+    }
+}
+
+
+/// Converts a linear gain back to a volume in dB, within the volume range.
+pub fn volume_db( gain: f32 ) -> f32 {
+    if gain.is_nan() || gain <= 0.0 {
+        MIN_VOLUME_DB
+    } else {
+        ( 20.0 * gain.log10() ).clamp( MIN_VOLUME_DB, MAX_VOLUME_DB )
+    }
+}
+
+
+/// Converts a percentage of perceived loudness to a volume in dB.
+///
+/// 100% is unity gain, and loudness roughly halves for every 10 dB taken off,
+/// so 50% is -10 dB and 25% is -20 dB. 0% mutes.
+pub fn percent_to_db( percent: f32 ) -> f32 {
+    if percent <= 0.0 {
+        MIN_VOLUME_DB
+    } else {
+        ( 10.0 * ( percent / 100.0 ).log2() ).clamp( MIN_VOLUME_DB, MAX_VOLUME_DB )
+    }
+}
+
+
+/// Converts a volume in dB to a percentage of perceived loudness (see `percent_to_db`).
+pub fn db_to_percent( db: f32 ) -> f32 {
+    if db <= MIN_VOLUME_DB {
+        0.0
+    } else {
+        100.0 * ( db.min( MAX_VOLUME_DB ) / 10.0 ).exp2()
+    }
+}
+
+
+/// Look-ahead limiter that keeps true (inter-sample) peaks under the ceiling.
+///
+/// It follows the signal itself, so it catches overs from any stage before it, and
+/// signals that stay under the ceiling pass through untouched. The output is delayed
+/// by a couple of milliseconds either way, so engaging it doesn't shift the audio.
+pub struct Limiter {
+    /// Interpolation filter, indexed by phase and then tap
+    filter: [[f32; TAPS]; PHASES],
+    /// Most the filter can raise an interpolated value above the samples it's made from
+    overshoot: f32,
+    /// Channels and sample rate the state below was set up for
+    format: Option<( usize, u32 )>,
+    /// Most recent input samples of each channel, newest first
+    history: Vec<[f32; TAPS]>,
+    /// Length of the gain smoothing window in frames
+    window: usize,
+    /// Frames since the last reset, to index the gain windows
+    frame: u64,
+    /// Increasing run of (frame, required gain), for the running minimum
+    minimum: VecDeque<( u64, f32 )>,
+    /// Recent running minimums, averaged into a smooth gain curve
+    smoothing: VecDeque<f32>,
+    smoothing_sum: f64,
+    /// Gain currently applied to the output
+    envelope: f32,
+    /// Per-frame step of the envelope back towards unity
+    release: f32,
+    /// Samples waiting to be output, interleaved
+    delay: VecDeque<f32>,
+}
+
+
+impl Limiter {
+    /// Creates a limiter.
+    pub fn new() -> Self {
+        // Windowed sinc lowpass at the original Nyquist, split into one filter per phase.
+        // Centering it on a whole sample puts the phases at quarters between samples.
+        let length = ( PHASES * TAPS ) as f32;
+        let center = length / 2.0;
+        let mut filter = [[ 0.0; TAPS ]; PHASES];
+        for ( phase, taps ) in filter.iter_mut().enumerate() {
+            for ( tap, coefficient ) in taps.iter_mut().enumerate() {
+                let n = ( tap * PHASES + phase ) as f32;
+                let x = ( n - center ) / PHASES as f32;
+                let sinc = if x == 0.0 { 1.0 } else { ( PI * x ).sin() / ( PI * x ) };
+                let window = 0.5 - 0.5 * ( 2.0 * PI * n / length ).cos();
+                *coefficient = sinc * window;
+            }
+            // Unity gain at DC for every phase
+            let sum: f32 = taps.iter().sum();
+            taps.iter_mut().for_each( |c| *c /= sum );
+        }
+        let overshoot = filter.iter()
+            .map( |taps| taps.iter().map( |c| c.abs() ).sum::<f32>() )
+            .fold( 1.0, f32::max );
+
+        Self {
+            filter,
+            overshoot,
+            format: None,
+            history: Vec::new(),
+            window: 1,
+            frame: 0,
+            minimum: VecDeque::new(),
+            smoothing: VecDeque::new(),
+            smoothing_sum: 0.0,
+            envelope: 1.0,
+            release: 0.0,
+            delay: VecDeque::new(),
+        }
+    }
+
+
+    /// Frames each sample is held back for.
+    fn latency( &self ) -> usize {
+        // The gain for a peak is fully applied `window - 1` frames after it's detected,
+        // and stays there for two more frames to cover the samples either side
+        self.window + DETECTOR_DELAY + 1
+    }
+
+
+    fn setup( &mut self, channels: usize, sample_rate: u32 ) {
+        if self.format == Some(( channels, sample_rate )) {
+            return;
+        }
+        self.format = Some(( channels, sample_rate ));
+        self.window = ( ( LOOKAHEAD * sample_rate as f32 ).ceil() as usize ).max( 1 );
+        self.release = 1.0 - ( -1.0 / ( RELEASE * sample_rate as f32 ) ).exp();
+        self.reset();
+    }
+
+
+    /// Highest absolute value of the signal between the two oldest samples the
+    /// detector has settled on, including the samples themselves.
+    ///
+    /// Returns 0.0 when no interpolated value could reach `ceiling`, skipping the filter.
+    fn true_peak( &self, ceiling: f32 ) -> f32 {
+        let sample_peak = self.history.iter().flatten().fold( 0.0f32, |m, s| m.max( s.abs() ) );
+        if sample_peak * self.overshoot <= ceiling {
+            return 0.0;
+        }
+
+        let mut peak = 0.0f32;
+        for history in &self.history {
+            peak = peak.max( history[ DETECTOR_DELAY ].abs() ).max( history[ DETECTOR_DELAY + 1 ].abs() );
+            for taps in &self.filter {
+                let value: f32 = taps.iter().zip( history ).map( |( c, x )| c * x ).sum();
+                peak = peak.max( value.abs() );
+            }
+        }
+        peak
+    }
+
+
+    /// Feeds the next input frame to the detector and returns the gain for the
+    /// frame leaving the delay.
+    fn advance( &mut self, frame: &[f32] ) -> f32 {
+        for ( history, sample ) in self.history.iter_mut().zip( frame ) {
+            history.copy_within( 0..TAPS - 1, 1 );
+            history[ 0 ] = *sample;
+        }
+
+        let ceiling = db_to_linear( CEILING_DB );
+        let peak = self.true_peak( ceiling );
+        let required = if peak > ceiling { ceiling / peak } else { 1.0 };
+
+        // Running minimum over the window plus the two frames of hold
+        let hold = self.window as u64 + 2;
+        while self.minimum.back().is_some_and( |( _, g )| *g >= required ) {
+            self.minimum.pop_back();
+        }
+        self.minimum.push_back(( self.frame, required ));
+        while self.minimum.front().is_some_and( |( f, _ )| f + hold <= self.frame ) {
+            self.minimum.pop_front();
+        }
+        let minimum = self.minimum.front().map_or( 1.0, |( _, g )| *g );
+        self.frame += 1;
+
+        // Averaging the minimums ramps the gain down to it over the look-ahead
+        self.smoothing.push_back( minimum );
+        self.smoothing_sum += minimum as f64;
+        while self.smoothing.len() > self.window {
+            self.smoothing_sum -= self.smoothing.pop_front().unwrap_or( 1.0 ) as f64;
+        }
+        // Frames before the window filled count as needing no reduction
+        let missing = self.window - self.smoothing.len();
+        let smoothed = ( ( self.smoothing_sum + missing as f64 ) / self.window as f64 ) as f32;
+
+        self.envelope = if smoothed < self.envelope {
+            smoothed
+        } else {
+            self.envelope + ( smoothed - self.envelope ) * self.release
+        };
+        self.envelope
+    }
+}
+
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl AudioProcessor for Limiter {
+    fn name( &self ) -> &'static str {
+        "limiter"
+    }
+
+
+    fn process( &mut self, samples: &mut Vec<f32>, channels: usize, sample_rate: u32 ) {
+        if channels == 0 {
+            return;
+        }
+        self.setup( channels, sample_rate );
+
+        let latency = self.latency() * channels;
+        let mut output = Vec::with_capacity( samples.len() );
+
+        for frame in samples.chunks_exact( channels ) {
+            let envelope = self.advance( frame );
+            self.delay.extend( frame );
+            if self.delay.len() > latency {
+                output.extend( self.delay.drain( ..channels ).map( |s| s * envelope ) );
+            }
+        }
+        *samples = output;
+    }
+
+
+    fn flush( &mut self, channels: usize, _sample_rate: u32 ) -> Vec<f32> {
+        if channels == 0 {
+            return Vec::new();
+        }
+
+        // Push silence through the detector to let the held samples out
+        let silence = vec![ 0.0; channels ];
+        let mut output = Vec::with_capacity( self.delay.len() );
+        while self.delay.len() >= channels {
+            let envelope = self.advance( &silence );
+            output.extend( self.delay.drain( ..channels ).map( |s| s * envelope ) );
+        }
+        self.reset();
+        output
+    }
+
+
+    fn reset( &mut self ) {
+        let channels = self.format.map_or( 0, |( channels, _ )| channels );
+        self.history = vec![ [ 0.0; TAPS ]; channels ];
+        self.frame = 0;
+        self.minimum.clear();
+        self.smoothing.clear();
+        self.smoothing_sum = 0.0;
+        self.envelope = 1.0;
+        self.delay.clear();
+    }
+}
+
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+
+    fn run( limiter: &mut Limiter, input: &[f32], channels: usize ) -> Vec<f32> {
+        let mut output = Vec::new();
+        for block in input.chunks( 512 * channels ) {
+            let mut samples = block.to_vec();
+            limiter.process( &mut samples, channels, 48000 );
+            output.extend( samples );
+        }
+        output.extend( limiter.flush( channels, 48000 ) );
+        output
+    }
+
+
+    #[test]
+    fn test_percent_scale() {
+        assert_eq!( percent_to_db( 100.0 ), 0.0 );
+        assert_eq!( percent_to_db( 50.0 ), -10.0 );
+        assert_eq!( percent_to_db( 0.0 ), MIN_VOLUME_DB );
+        assert_eq!( db_to_percent( -20.0 ), 25.0 );
+        assert_eq!( volume_gain( MIN_VOLUME_DB ), 0.0 );
+        assert_eq!( volume_gain( f32::NAN ), 0.0 );
+        assert_eq!( volume_db( f32::NAN ), MIN_VOLUME_DB );
+        assert!( ( volume_db( volume_gain( -6.0 ) ) + 6.0 ).abs() < 1e-4 );
+    }
+
+
+    #[test]
+    fn test_signal_under_ceiling_passes_through() {
+        let mut limiter = Limiter::new();
+        let input: Vec<f32> = ( 0..5000 ).map( |i| ( i as f32 * 0.37 ).sin() * 0.9 ).collect();
+
+        // Untouched and complete once the delay is flushed
+        assert_eq!( run( &mut limiter, &input, 2 ), input );
+    }
+
+
+    #[test]
+    fn test_keeps_true_peaks_under_ceiling() {
+        // Whatever made the signal hot (volume, EQ, ReplayGain), it's caught here
+        let mut limiter = Limiter::new();
+
+        // A quarter-rate sine sampled halfway between its peaks: the samples read
+        // 1.3, but the waveform they describe peaks at 1.84
+        let input: Vec<f32> = ( 0..9600 )
+            .map( |i| ( PI / 2.0 * i as f32 + PI / 4.0 ).sin() * 1.84 )
+            .collect();
+        let output = run( &mut limiter, &input, 1 );
+        assert_eq!( output.len(), input.len() );
+
+        // The limited sine's samples still sit at 0.707 of its peak
+        let peak = output[ 200..9000 ].iter().fold( 0.0f32, |m, s| m.max( s.abs() ) ) * 2f32.sqrt();
+        assert!( peak <= db_to_linear( CEILING_DB ) + 0.01, "true peak {}", peak );
+        assert!( peak > 0.9, "over-limited to {}", peak );
+    }
+}
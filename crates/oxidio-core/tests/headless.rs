@@ -0,0 +1,351 @@
+//! Plays generated files through a `WavSink` and checks the rendered PCM.
+//!
+//! Each fixture is a mono ramp whose samples count up by one, so the rendered
+//! output shows exactly which frames were played and in what order.
+
+use std::fs;
+use std::path::{ Path, PathBuf };
+use std::time::{ Duration, Instant };
+
+use oxidio_core::decoder::Decoder;
+use oxidio_core::output::OutputFormat;
+use oxidio_core::{ cue, Bookmarks, LoudnessCache, Pacing, Player, WavSink, MIN_VOLUME_DB };
+
+
+const RATE: u32 = 8000;
+
+const FORMAT: OutputFormat = OutputFormat { sample_rate: RATE, channels: 1 };
+
+
+/// Creates an empty scratch directory for one test.
+fn scratch_dir( name: &str ) -> PathBuf {
+    let dir = std::env::temp_dir().join( format!( "oxidio-{}-{}", name, std::process::id() ) );
+    let _ = fs::remove_dir_all( &dir );
+    fs::create_dir_all( &dir ).unwrap();
+    dir
+}
+
+
+/// Writes a mono 16-bit WAV whose samples are `start`, `start + 1`, ...
+fn write_ramp( path: &Path, start: i16, frames: usize ) {
+    let data_len = frames as u32 * 2;
+    let mut bytes = Vec::with_capacity( 44 + data_len as usize );
+    bytes.extend_from_slice( b"RIFF" );
+    bytes.extend_from_slice( &( 36 + data_len ).to_le_bytes() );
+    bytes.extend_from_slice( b"WAVEfmt " );
+    bytes.extend_from_slice( &16u32.to_le_bytes() );
+    bytes.extend_from_slice( &1u16.to_le_bytes() );
+    bytes.extend_from_slice( &1u16.to_le_bytes() );
+    bytes.extend_from_slice( &RATE.to_le_bytes() );
+    bytes.extend_from_slice( &( RATE * 2 ).to_le_bytes() );
+    bytes.extend_from_slice( &2u16.to_le_bytes() );
+    bytes.extend_from_slice( &16u16.to_le_bytes() );
+    bytes.extend_from_slice( b"data" );
+    bytes.extend_from_slice( &data_len.to_le_bytes() );
+    for i in 0..frames {
+        bytes.extend_from_slice( &( start + i as i16 ).to_le_bytes() );
+    }
+    fs::write( path, bytes ).unwrap();
+}
+
+
+/// Writes an MP4 with one mono 16-bit ramp stream per entry of `starts`, each `frames` long.
+fn write_streams( path: &Path, starts: &[i16], frames: usize ) {
+    fn atom( kind: &[u8; 4], body: &[u8] ) -> Vec<u8> {
+        [ &( body.len() as u32 + 8 ).to_be_bytes()[ .. ], kind, body ].concat()
+    }
+    fn words( fields: &[u32] ) -> Vec<u8> {
+        fields.iter().flat_map( |f| f.to_be_bytes() ).collect()
+    }
+
+    let ftyp = atom( b"ftyp", b"isom\0\0\0\0isom" );
+    let data: Vec<u8> = starts.iter()
+        .flat_map( |&start| ( 0..frames ).flat_map( move |i| ( start + i as i16 ).to_le_bytes() ) )
+        .collect();
+    let mdat = atom( b"mdat", &data );
+    let data_offset = ftyp.len() as u32 + 8;
+    let frames = frames as u32;
+
+    let traks = starts.iter().enumerate().map( |( i, _ )| {
+        let sowt = [
+            &[ 0u8; 6 ][ .. ], &1u16.to_be_bytes(), &[ 0; 8 ],
+            &1u16.to_be_bytes(), &16u16.to_be_bytes(), &[ 0; 4 ], &( RATE << 16 ).to_be_bytes(),
+        ].concat();
+        let stbl = [
+            atom( b"stsd", &[ words( &[ 0, 1 ] ), atom( b"sowt", &sowt ) ].concat() ),
+            atom( b"stts", &words( &[ 0, 1, frames, 1 ] ) ),
+            atom( b"stsc", &words( &[ 0, 1, 1, frames, 1 ] ) ),
+            atom( b"stsz", &words( &[ 0, 2, frames ] ) ),
+            atom( b"stco", &words( &[ 0, 1, data_offset + i as u32 * frames * 2 ] ) ),
+        ].concat();
+        let mdia = [
+            atom( b"mdhd", &[ words( &[ 0, 0, 0, RATE, frames ] ), vec![ 0; 4 ] ].concat() ),
+            atom( b"hdlr", &[ words( &[ 0, 0 ] ), b"soun".to_vec(), vec![ 0; 13 ] ].concat() ),
+            atom( b"minf", &atom( b"stbl", &stbl ) ),
+        ].concat();
+        let tkhd = [ words( &[ 0, 0, 0, i as u32 + 1, 0, frames ] ), vec![ 0; 60 ] ].concat();
+        atom( b"trak", &[ atom( b"tkhd", &tkhd ), atom( b"mdia", &mdia ) ].concat() )
+    });
+    let mvhd = [ words( &[ 0, 0, 0, RATE, frames ] ), vec![ 0; 76 ], words( &[ starts.len() as u32 + 1 ] ) ].concat();
+    let moov = atom( b"moov", &[ atom( b"mvhd", &mvhd ) ].into_iter().chain( traks ).collect::<Vec<_>>().concat() );
+
+    fs::write( path, [ ftyp, mdat, moov ].concat() ).unwrap();
+}
+
+
+/// Reads a rendered file back as the ramp values that were played.
+fn read_ramp( path: &Path ) -> Vec<i32> {
+    let mut decoder = Decoder::open( path ).unwrap();
+    let mut values = Vec::new();
+    while let Some( samples ) = decoder.decode_next().unwrap() {
+        values.extend( samples.iter().map( |s| ( s * 32768.0 ).round() as i32 ) );
+    }
+    values
+}
+
+
+/// Waits for playback to reach the end of the playlist.
+///
+/// The player has to be dropped afterwards to finish the rendered file.
+fn wait_until_ended( player: &Player ) {
+    let deadline = Instant::now() + Duration::from_secs( 10 );
+    while !player.track_ended() {
+        assert!( Instant::now() < deadline, "playback did not finish" );
+        std::thread::sleep( Duration::from_millis( 5 ) );
+    }
+}
+
+
+/// Creates a player rendering to `output`, with its caches kept in memory.
+fn player( output: &Path ) -> Player {
+    let sink = WavSink::create( output, FORMAT, Pacing::Fast ).unwrap();
+    Player::with_sink( Box::new( sink ), LoudnessCache::open( None ), Bookmarks::open( None ) ).unwrap()
+}
+
+
+#[test]
+fn test_play_renders_every_frame() {
+    let dir = scratch_dir( "play" );
+    let track = dir.join( "a.wav" );
+    let output = dir.join( "out.wav" );
+    write_ramp( &track, 0, 4000 );
+
+    let player = player( &output );
+    player.play( track ).unwrap();
+    wait_until_ended( &player );
+    drop( player );
+
+    assert_eq!( read_ramp( &output ), ( 0..4000 ).collect::<Vec<_>>() );
+}
+
+
+#[test]
+fn test_seek_continues_from_target() {
+    let dir = scratch_dir( "seek" );
+    let track = dir.join( "a.wav" );
+    let output = dir.join( "out.wav" );
+    write_ramp( &track, 0, 16000 );
+
+    let player = player( &output );
+    player.play( track ).unwrap();
+    player.seek( Duration::from_secs( 1 ) ).unwrap();
+    wait_until_ended( &player );
+    drop( player );
+
+    // Whatever played before the seek, the output must end with an unbroken run
+    // from the seek target to the end of the track
+    let rendered = read_ramp( &output );
+    let run_start = rendered.windows( 2 ).rposition( |w| w[ 1 ] != w[ 0 ] + 1 ).map_or( 0, |i| i + 1 );
+    let landed = rendered[ run_start ];
+    assert!( ( landed - RATE as i32 ).abs() < 1200, "landed at frame {}", landed );
+    assert_eq!( rendered.last(), Some( &15999 ) );
+}
+
+
+#[test]
+fn test_ab_loop_repeats_region_exactly() {
+    let dir = scratch_dir( "loop" );
+    let track = dir.join( "a.wav" );
+    let output = dir.join( "out.wav" );
+    write_ramp( &track, 0, 24000 );
+
+    let player = player( &output );
+    player.play( track ).unwrap();
+    player.set_loop( Some(( Duration::from_millis( 300 ), Duration::from_millis( 700 ) )) );
+    std::thread::sleep( Duration::from_millis( 200 ) );
+    player.set_loop( None );
+    wait_until_ended( &player );
+    drop( player );
+
+    // Every jump in the output is from the last frame of the loop straight to its first
+    let rendered = read_ramp( &output );
+    let jumps: Vec<_> = rendered.windows( 2 ).filter( |w| w[ 1 ] != w[ 0 ] + 1 ).collect();
+    assert!( !jumps.is_empty(), "never looped" );
+    for jump in jumps {
+        assert_eq!( jump, [ 5599, 2400 ] );
+    }
+    assert_eq!( rendered.last(), Some( &23999 ) );
+}
+
+
+#[test]
+fn test_position_follows_output() {
+    let dir = scratch_dir( "position" );
+    let track = dir.join( "a.wav" );
+    let output = dir.join( "out.wav" );
+    write_ramp( &track, 0, 16000 );
+
+    let player = player( &output );
+    player.play( track ).unwrap();
+
+    // A seek is reported straight away, before the decoder gets to it
+    player.seek( Duration::from_millis( 1500 ) ).unwrap();
+    assert_eq!( player.position(), Duration::from_millis( 1500 ) );
+
+    // Once everything has been heard, the position is the end of the track
+    wait_until_ended( &player );
+    assert_eq!( player.position(), Duration::from_secs( 2 ) );
+}
+
+
+#[test]
+fn test_volume_changes_are_heard_promptly() {
+    let dir = scratch_dir( "volume" );
+    let track = dir.join( "a.wav" );
+    let output = dir.join( "out.wav" );
+    write_ramp( &track, 1000, 8000 );
+
+    let sink = WavSink::create( &output, FORMAT, Pacing::Realtime ).unwrap();
+    let player = Player::with_sink( Box::new( sink ), LoudnessCache::open( None ), Bookmarks::open( None ) ).unwrap();
+    player.play( track ).unwrap();
+    std::thread::sleep( Duration::from_millis( 400 ) );
+    let heard = player.position();
+    player.set_volume_db( MIN_VOLUME_DB );
+    std::thread::sleep( Duration::from_millis( 300 ) );
+    drop( player );
+
+    // Only what was already queued for output plays at the old volume
+    let rendered = read_ramp( &output );
+    let start = rendered.iter().position( |&v| v != 0 ).unwrap();
+    let faded = rendered[ start.. ].windows( 2 ).position( |w| w[ 1 ] != w[ 0 ] + 1 ).unwrap();
+    let lag = ( rendered[ start + faded ] - 1000 ) as f64 / RATE as f64 - heard.as_secs_f64();
+    assert!( lag < 0.1, "volume change heard {:.0} ms late", lag * 1000.0 );
+}
+
+
+#[test]
+fn test_playlist_continues_gaplessly() {
+    let dir = scratch_dir( "gapless" );
+    let first = dir.join( "a.wav" );
+    let second = dir.join( "b.wav" );
+    let output = dir.join( "out.wav" );
+    write_ramp( &first, 0, 4000 );
+    write_ramp( &second, 10000, 4000 );
+
+    let player = player( &output );
+    {
+        let playlist = player.playlist();
+        let mut playlist = playlist.write().unwrap();
+        playlist.add_many( [ first.clone(), second.clone() ] );
+        playlist.jump_to( 0 );
+    }
+    player.play( first ).unwrap();
+    wait_until_ended( &player );
+    assert_eq!( player.current_track(), Some( second ) );
+    drop( player );
+
+    let expected: Vec<i32> = ( 0..4000 ).chain( 10000..14000 ).collect();
+    assert_eq!( read_ramp( &output ), expected );
+}
+
+
+#[test]
+fn test_next_plays_whole_next_track() {
+    let dir = scratch_dir( "next" );
+    let first = dir.join( "a.wav" );
+    let second = dir.join( "b.wav" );
+    let output = dir.join( "out.wav" );
+    write_ramp( &first, 0, 16000 );
+    write_ramp( &second, 20000, 4000 );
+
+    let player = player( &output );
+    {
+        let playlist = player.playlist();
+        let mut playlist = playlist.write().unwrap();
+        playlist.add_many( [ first.clone(), second.clone() ] );
+        playlist.jump_to( 0 );
+    }
+    player.play( first ).unwrap();
+    assert!( player.play_next().unwrap() );
+    wait_until_ended( &player );
+    assert_eq!( player.current_track(), Some( second ) );
+    drop( player );
+
+    let rendered = read_ramp( &output );
+    assert!( rendered.ends_with( &( 20000..24000 ).collect::<Vec<_>>() ) );
+}
+
+
+#[test]
+fn test_cue_tracks_play_their_stretch_of_the_file() {
+    let dir = scratch_dir( "cue" );
+    let album = dir.join( "album.wav" );
+    let output = dir.join( "out.wav" );
+    write_ramp( &album, 0, 12000 );
+    // Tracks start at 0, 0.4 and 1 seconds (cue times count 75 frames per second)
+    fs::write( dir.join( "album.cue" ), concat!(
+        "FILE \"album.wav\" WAVE\n",
+        "  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n",
+        "  TRACK 02 AUDIO\n    INDEX 01 00:00:30\n",
+        "  TRACK 03 AUDIO\n    INDEX 01 00:01:00\n",
+    )).unwrap();
+
+    let tracks = cue::expand( &album );
+    assert_eq!( tracks.len(), 3 );
+
+    let player = player( &output );
+    {
+        let playlist = player.playlist();
+        let mut playlist = playlist.write().unwrap();
+        playlist.add_many( tracks.clone() );
+        playlist.jump_to( 1 );
+    }
+    player.play( tracks[ 1 ].clone() ).unwrap();
+    assert_eq!( player.duration(), Some( Duration::from_millis( 600 ) ) );
+    wait_until_ended( &player );
+    assert_eq!( player.current_track(), Some( tracks[ 2 ].clone() ) );
+    drop( player );
+
+    // The second track starts exactly on its index and runs gaplessly into the third
+    assert_eq!( read_ramp( &output ), ( 3200..12000 ).collect::<Vec<_>>() );
+}
+
+
+#[test]
+fn test_switching_audio_stream_keeps_position() {
+    let dir = scratch_dir( "streams" );
+    let track = dir.join( "streams.m4a" );
+    let output = dir.join( "out.wav" );
+    write_streams( &track, &[ 0, 20000 ], 8000 );
+    assert_eq!( Decoder::open( &track ).unwrap().streams().len(), 2 );
+
+    let player = player( &output );
+    player.play( track ).unwrap();
+    assert_eq!( player.audio_streams().len(), 2 );
+    std::thread::sleep( Duration::from_millis( 50 ) );
+    player.select_audio_stream( 1 ).unwrap();
+    wait_until_ended( &player );
+    assert_eq!( player.audio_stream(), Some( 1 ) );
+    drop( player );
+
+    // The first stream plays up to where it fades out, and once the second has faded
+    // in it carries on from there
+    let rendered = read_ramp( &output );
+    let faded_at = rendered.iter().enumerate().position( |( i, &v )| v != i as i32 ).unwrap();
+    // The fade-in can pass 20000 before it's done, so go by where the final unbroken run starts
+    let switch = rendered.windows( 2 ).rposition( |w| w[ 1 ] != w[ 0 ] + 1 ).map_or( 0, |i| i + 1 );
+    assert!( rendered[ switch ] >= 20000, "second stream never played" );
+    let resumed_at = rendered[ switch ] - 20000;
+    assert!( ( resumed_at - faded_at as i32 ).abs() < 1200, "faded out at {}, resumed at {}", faded_at, resumed_at );
+    assert_eq!( rendered.last(), Some( &27999 ) );
+}